@@ -0,0 +1,122 @@
+//! Decoder for the Bracketed-Indented (BI) text format.
+
+use std::collections::HashMap;
+use std::error::Error;
+use crate::data::Data;
+use crate::json::parse_json;
+
+/// Parse the Bracketed-Indented format written by `Data::to_bi`.
+/// `key [ ... ]` blocks become map entries and bare `[ ... ]` blocks lists. The format does not
+/// delimit maps inside a list, so a list item ends where one of its keys repeats.
+pub fn parse_bi(text: &str) -> Result<Data, Box<dyn Error>> {
+    let lines: Vec<(usize, &str)> = text.trim_start_matches('\u{feff}')
+        .lines()
+        .enumerate()
+        .map(|(number, line)| (number + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .collect();
+    let mut parser = Parser { lines, position: 0 };
+
+    let items = parser.parse_items(0)?;
+    if let Some((number, line)) = parser.lines.get(parser.position) {
+        return Err(format!("Unexpected {:?} on line {}", line, number).into());
+    }
+    block_value(items)
+}
+
+// Deeply nested input is rejected instead of overflowing the stack
+const MAX_DEPTH: usize = 128;
+
+enum Item {
+    Entry(String, Data),
+    Value(Data),
+}
+
+struct Parser<'a> {
+    lines: Vec<(usize, &'a str)>,
+    position: usize,
+}
+
+impl Parser<'_> {
+    // Items up to the next unmatched `]` or the end of input
+    fn parse_items(&mut self, depth: usize) -> Result<Vec<Item>, Box<dyn Error>> {
+        if depth > MAX_DEPTH {
+            return Err("BI nested too deeply".into());
+        }
+
+        let mut items = Vec::new();
+        while let Some(&(number, line)) = self.lines.get(self.position) {
+            if line == "]" {
+                break;
+            }
+            self.position += 1;
+
+            if line == "[" {
+                let list = self.parse_items(depth + 1)?;
+                self.expect_close(number)?;
+                items.push(Item::Value(Data::List(list_items(list))));
+            } else if let Some(key) = line.strip_suffix('[').filter(|_| !line.starts_with('"')) {
+                let value = self.parse_items(depth + 1)?;
+                self.expect_close(number)?;
+                items.push(Item::Entry(key.trim().to_string(), block_value(value)?));
+            } else {
+                let value = parse_json(line).map_err(|e| format!("Invalid value on line {}: {}", number, e))?;
+                items.push(Item::Value(value));
+            }
+        }
+        Ok(items)
+    }
+
+    fn expect_close(&mut self, opened_on: usize) -> Result<(), Box<dyn Error>> {
+        match self.lines.get(self.position) {
+            Some((_, "]")) => {
+                self.position += 1;
+                Ok(())
+            }
+            _ => Err(format!("Unclosed '[' from line {}", opened_on).into()),
+        }
+    }
+}
+
+// The contents of `key [ ... ]`: a single value, or the entries of a map
+fn block_value(items: Vec<Item>) -> Result<Data, Box<dyn Error>> {
+    let mut map = HashMap::new();
+    let count = items.len();
+    for item in items {
+        match item {
+            Item::Entry(key, value) => {
+                map.insert(key, value);
+            }
+            Item::Value(value) if count == 1 => return Ok(value),
+            Item::Value(_) => return Err("A block holds either one value or key entries".into()),
+        }
+    }
+    Ok(Data::Map(map))
+}
+
+fn list_items(items: Vec<Item>) -> Vec<Data> {
+    let mut list = Vec::new();
+    let mut current: Option<HashMap<String, Data>> = None;
+
+    for item in items {
+        match item {
+            Item::Entry(key, value) => {
+                let map = current.get_or_insert_with(HashMap::new);
+                if map.contains_key(&key) {
+                    list.push(Data::Map(std::mem::take(map)));
+                }
+                map.insert(key, value);
+            }
+            Item::Value(value) => {
+                if let Some(map) = current.take() {
+                    list.push(Data::Map(map));
+                }
+                list.push(value);
+            }
+        }
+    }
+    if let Some(map) = current {
+        list.push(Data::Map(map));
+    }
+    list
+}
@@ -0,0 +1,247 @@
+use std::env;
+use std::error::Error;
+use std::path::PathBuf;
+use lab1::daemon::{parse_formats, DaemonOptions, Schedule};
+use lab1::normalize::ConflictPolicy;
+use lab1::query::{parse_query, Query};
+use lab1::sitemap::{parse_lastmod, SitemapFilter};
+use lab1::tls::{parse_tls_version, TlsOptions};
+
+const DEFAULT_URL: &str = "https://xstore.md/";
+const DEFAULT_QUERY: &str = "where price >= 1000 and price <= 15000";
+
+const USAGE: &str = "\
+Usage: lab1 [OPTIONS] [URL]
+       lab1 diff [OPTIONS] <OLD> <NEW>
+
+Scrape products from URL (default: https://xstore.md/), or compare two saved outputs
+(see lab1 diff --help).
+
+Options:
+    --proxy <URL>       Proxy for all requests (http://, socks5://, socks5h://);
+                        overrides HTTP_PROXY / HTTPS_PROXY / ALL_PROXY
+    --no-proxy <LIST>   Comma-separated hosts that bypass the proxy; overrides NO_PROXY
+    --cookie-jar <FILE> Load cookies from FILE if it exists and save them back after the run
+    --cookie <NAME=VAL> Preset a cookie for URL's host, e.g. a language or currency choice;
+                        may be repeated
+    --cacert <FILE>     Also trust the CA certificates in this PEM file; may be repeated
+    --cert <FILE>       Client certificate: PEM chain (with --key) or PKCS#12 bundle
+    --key <FILE>        PKCS#8 PEM private key for --cert
+    --cert-password <PASSWORD>
+                        Password for a PKCS#12 --cert
+    --tls-min <VERSION> Minimum TLS version: 1.0, 1.1 or 1.2
+    --insecure          Do not verify server certificates or host names (staging only)
+    --http1.1           Do not negotiate HTTP/2; by default HTTPS servers that offer it get one
+                        shared connection for all requests
+    --show-cert         Print the server certificate for URL before scraping
+    --record <FILE>     Save every request and response to FILE as a HAR 1.2 archive, including
+                        the failed ones; written even when the scrape fails
+    --replay <FILE>     Answer requests from a HAR archive (e.g. one saved with --record) instead
+                        of the network, to reproduce a scrape exactly
+    --sitemap           Discover product pages through URL's sitemaps (robots.txt or
+                        /sitemap.xml) instead of crawling the listing page
+    --sitemap-url <URL> Read this sitemap or sitemap index instead; implies --sitemap,
+                        may be repeated
+    --match <GLOB>      Only sitemap URLs matching GLOB, e.g. '*/product/*'
+    --since <DATE>      Only sitemap URLs with lastmod on or after DATE (YYYY-MM-DD or RFC 3339)
+    --limit <N>         Scrape at most N sitemap URLs
+    --conflict <POLICY> Which duplicate wins fields they disagree on when merging products with
+                        the same canonical link: first (default), last, cheapest or priciest
+    --query <EXPR>      Filter, sort, group and aggregate the scraped products with a pipeline
+                        of '|'-separated stages (default: 'where price >= 1000 and price <= 15000'):
+                          where <FIELD> <=|!=|<|<=|>|>=|~> <VALUE> [and ...]
+                          sort [by] <FIELD> [asc|desc], ...
+                          limit <N>
+                          group [by] <FIELD>
+                          count, sum|avg|min|max|median <FIELD>, p<N> <FIELD>, ...
+                        e.g. --query 'where brand ~ apple | group by availability | count, p90 price'
+    --schedule <CRON>   Keep running and scrape on this schedule (minute hour day month weekday,
+                        local time, or @hourly/@daily/@weekly/@monthly), e.g. '*/30 * * * *'
+    --output-dir <DIR>  Where scheduled runs write timestamped snapshots (default: snapshots)
+    --formats <LIST>    Snapshot formats, comma-separated from json, xml, bi (default: all)
+    --keep <N>          Snapshots kept per format; older ones are deleted (default: 24)
+    --status-file <FILE>
+                        Status of the last run and the next one (default: <DIR>/status.json)
+    -h, --help          Print this help";
+
+const DIFF_USAGE: &str = "\
+Usage: lab1 diff [OPTIONS] <OLD> <NEW>
+
+Compare two saved product lists in any lab1 output format (JSON, XML or BI). Products are
+matched by link, then by name ignoring case, accents, punctuation and spacing.
+
+Options:
+    --format <FORMAT>   human (default), json or bi
+    -h, --help          Print this help";
+
+pub enum Command {
+    Scrape(Box<Options>),
+    Diff(DiffOptions),
+}
+
+impl Command {
+    pub fn from_args() -> Result<Command, Box<dyn Error>> {
+        let mut args = env::args().skip(1).peekable();
+        if args.peek().map(String::as_str) == Some("diff") {
+            args.next();
+            return Ok(Command::Diff(DiffOptions::parse(args)?));
+        }
+        Ok(Command::Scrape(Box::new(Options::parse(args)?)))
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OutputFormat {
+    Human,
+    Json,
+    Bi,
+}
+
+// Options of `lab1 diff`
+#[derive(Debug)]
+pub struct DiffOptions {
+    pub before: PathBuf,
+    pub after: PathBuf,
+    pub format: OutputFormat,
+}
+
+impl DiffOptions {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<DiffOptions, Box<dyn Error>> {
+        let mut paths = Vec::new();
+        let mut format = OutputFormat::Human;
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => {
+                    println!("{}", DIFF_USAGE);
+                    std::process::exit(0);
+                }
+                "--format" => {
+                    format = match value_for(&arg, args.next())?.as_str() {
+                        "human" => OutputFormat::Human,
+                        "json" => OutputFormat::Json,
+                        "bi" => OutputFormat::Bi,
+                        other => return Err(format!("Unknown diff format: {} (expected human, json or bi)", other).into()),
+                    }
+                }
+                flag if flag.starts_with('-') => {
+                    return Err(format!("Unknown option: {} (see lab1 diff --help)", flag).into());
+                }
+                _ => paths.push(PathBuf::from(arg)),
+            }
+        }
+
+        match <[PathBuf; 2]>::try_from(paths) {
+            Ok([before, after]) => Ok(DiffOptions { before, after, format }),
+            Err(_) => Err("lab1 diff needs exactly two files: <OLD> <NEW>".into()),
+        }
+    }
+}
+
+// Command line options
+#[derive(Debug)]
+pub struct Options {
+    pub url: String,
+    pub proxy: Option<String>,
+    pub no_proxy: Option<String>,
+    pub cookie_jar: Option<PathBuf>,
+    pub cookies: Vec<String>,
+    pub tls: TlsOptions,
+    pub show_cert: bool,
+    pub record: Option<PathBuf>,
+    pub replay: Option<PathBuf>,
+    pub sitemap: bool,
+    pub sitemap_urls: Vec<String>,
+    pub sitemap_filter: SitemapFilter,
+    pub conflict_policy: ConflictPolicy,
+    pub query: Query,
+    pub daemon: DaemonOptions,
+}
+
+impl Options {
+    pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Options, Box<dyn Error>> {
+        let mut options = Options {
+            url: DEFAULT_URL.to_string(),
+            proxy: None,
+            no_proxy: None,
+            cookie_jar: None,
+            cookies: Vec::new(),
+            tls: TlsOptions::default(),
+            show_cert: false,
+            record: None,
+            replay: None,
+            sitemap: false,
+            sitemap_urls: Vec::new(),
+            sitemap_filter: SitemapFilter::default(),
+            conflict_policy: ConflictPolicy::default(),
+            query: parse_query(DEFAULT_QUERY)?,
+            daemon: DaemonOptions::default(),
+        };
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    std::process::exit(0);
+                }
+                "--proxy" => options.proxy = Some(value_for(&arg, args.next())?),
+                "--no-proxy" => options.no_proxy = Some(value_for(&arg, args.next())?),
+                "--cookie-jar" => options.cookie_jar = Some(PathBuf::from(value_for(&arg, args.next())?)),
+                "--cookie" => options.cookies.push(value_for(&arg, args.next())?),
+                "--cacert" => options.tls.ca_certs.push(PathBuf::from(value_for(&arg, args.next())?)),
+                "--cert" => options.tls.client_cert = Some(PathBuf::from(value_for(&arg, args.next())?)),
+                "--key" => options.tls.client_key = Some(PathBuf::from(value_for(&arg, args.next())?)),
+                "--cert-password" => options.tls.client_cert_password = Some(value_for(&arg, args.next())?),
+                "--tls-min" => options.tls.min_version = Some(parse_tls_version(&value_for(&arg, args.next())?)?),
+                "--insecure" => options.tls.insecure = true,
+                "--http1.1" => options.tls.http1_only = true,
+                "--show-cert" => options.show_cert = true,
+                "--record" => options.record = Some(PathBuf::from(value_for(&arg, args.next())?)),
+                "--replay" => options.replay = Some(PathBuf::from(value_for(&arg, args.next())?)),
+                "--sitemap" => options.sitemap = true,
+                "--sitemap-url" => {
+                    options.sitemap = true;
+                    options.sitemap_urls.push(value_for(&arg, args.next())?);
+                }
+                "--match" => options.sitemap_filter.pattern = Some(value_for(&arg, args.next())?),
+                "--since" => {
+                    let value = value_for(&arg, args.next())?;
+                    let since = parse_lastmod(&value).ok_or_else(|| format!("Invalid date for --since: {}", value))?;
+                    options.sitemap_filter.since = Some(since);
+                }
+                "--limit" => options.sitemap_filter.limit = Some(value_for(&arg, args.next())?.parse()?),
+                "--conflict" => options.conflict_policy = ConflictPolicy::parse(&value_for(&arg, args.next())?)?,
+                "--query" => {
+                    let expression = value_for(&arg, args.next())?;
+                    options.query = parse_query(&expression).map_err(|e| format!("Invalid --query: {}", e))?;
+                }
+                "--schedule" => options.daemon.schedule = Some(Schedule::parse(&value_for(&arg, args.next())?)?),
+                "--output-dir" => options.daemon.output_dir = PathBuf::from(value_for(&arg, args.next())?),
+                "--formats" => options.daemon.formats = parse_formats(&value_for(&arg, args.next())?)?,
+                "--keep" => {
+                    let keep: usize = value_for(&arg, args.next())?.parse()?;
+                    if keep == 0 {
+                        return Err("--keep must be at least 1".into());
+                    }
+                    options.daemon.keep = keep;
+                }
+                "--status-file" => options.daemon.status_file = Some(PathBuf::from(value_for(&arg, args.next())?)),
+                flag if flag.starts_with('-') => {
+                    return Err(format!("Unknown option: {} (see --help)", flag).into());
+                }
+                _ => options.url = arg,
+            }
+        }
+
+        if options.record.is_some() && options.replay.is_some() {
+            return Err("--record and --replay cannot be used together".into());
+        }
+        Ok(options)
+    }
+}
+
+fn value_for(flag: &str, value: Option<String>) -> Result<String, Box<dyn Error>> {
+    value.ok_or_else(|| format!("Missing value for {}", flag).into())
+}
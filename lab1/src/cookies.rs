@@ -0,0 +1,276 @@
+//! Cookie storage for the HTTP client, persisted in the Netscape `cookies.txt` format.
+
+use std::error::Error;
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+use chrono::{DateTime, Duration, NaiveDateTime, TimeZone, Utc};
+use url::Url;
+
+#[derive(Debug, Clone)]
+pub struct Cookie {
+    pub name: String,
+    pub value: String,
+    pub domain: String,
+    /// Without a Domain attribute the cookie is only sent back to the exact host that set it
+    pub host_only: bool,
+    pub path: String,
+    /// `None` marks a session cookie
+    pub expires: Option<DateTime<Utc>>,
+    pub secure: bool,
+    pub http_only: bool,
+    created: DateTime<Utc>,
+}
+
+impl Cookie {
+    fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        self.expires.is_some_and(|expires| expires <= now)
+    }
+
+    fn matches(&self, url: &Url, now: DateTime<Utc>) -> bool {
+        let Some(host) = request_host(url) else { return false };
+
+        let domain_ok = if self.host_only { host == self.domain } else { domain_matches(&host, &self.domain) };
+        domain_ok
+            && path_matches(url.path(), &self.path)
+            && (!self.secure || url.scheme() == "https")
+            && !self.is_expired(now)
+    }
+}
+
+/// RFC 6265 cookie store shared by every request a scrape makes
+#[derive(Debug, Clone, Default)]
+pub struct CookieJar {
+    cookies: Vec<Cookie>,
+}
+
+impl CookieJar {
+    pub fn new() -> Self {
+        CookieJar::default()
+    }
+
+    /// Store the cookies from every `Set-Cookie` header of a response to `url`
+    pub fn store<'a>(&mut self, url: &Url, set_cookie_headers: impl IntoIterator<Item = &'a str>) {
+        let now = Utc::now();
+        for header in set_cookie_headers {
+            if let Some(cookie) = parse_set_cookie(url, header, now) {
+                self.insert(cookie, now);
+            }
+        }
+    }
+
+    /// Value for the `Cookie` request header, or `None` when nothing applies to `url`
+    pub fn cookie_header(&self, url: &Url) -> Option<String> {
+        let now = Utc::now();
+        let mut matching: Vec<&Cookie> = self.cookies.iter().filter(|c| c.matches(url, now)).collect();
+        if matching.is_empty() {
+            return None;
+        }
+
+        // Longer paths first, then older cookies first (RFC 6265 section 5.4)
+        matching.sort_by(|a, b| b.path.len().cmp(&a.path.len()).then(a.created.cmp(&b.created)));
+        Some(matching.iter().map(|c| format!("{}={}", c.name, c.value)).collect::<Vec<_>>().join("; "))
+    }
+
+    fn insert(&mut self, cookie: Cookie, now: DateTime<Utc>) {
+        let existing = self.cookies.iter().position(|c| {
+            c.name == cookie.name && c.domain == cookie.domain && c.path == cookie.path
+        });
+        let created = existing.map_or(now, |index| self.cookies.remove(index).created);
+
+        // A cookie set with a past expiry date is how servers delete it
+        if !cookie.is_expired(now) {
+            self.cookies.push(Cookie { created, ..cookie });
+        }
+    }
+
+    /// Load a jar saved by `save`, in the Netscape `cookies.txt` format used by curl and wget.
+    /// Lines that can't be read are skipped with a warning rather than losing the whole jar.
+    pub fn load(path: &Path) -> Result<CookieJar, Box<dyn Error>> {
+        let now = Utc::now();
+        let mut jar = CookieJar::new();
+
+        for (number, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let (line, http_only) = match line.strip_prefix("#HttpOnly_") {
+                Some(rest) => (rest, true),
+                None => (line, false),
+            };
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            match parse_jar_line(line, http_only, now) {
+                Ok(cookie) => jar.insert(cookie, now),
+                Err(e) => eprintln!("Skipping line {} of {}: {}", number + 1, path.display(), e),
+            }
+        }
+
+        Ok(jar)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let now = Utc::now();
+        let mut output = String::from("# Netscape HTTP Cookie File\n");
+
+        for cookie in self.cookies.iter().filter(|c| !c.is_expired(now)) {
+            output.push_str(&format!(
+                "{}{}{}\t{}\t{}\t{}\t{}\t{}\t{}\n",
+                if cookie.http_only { "#HttpOnly_" } else { "" },
+                if cookie.host_only { "" } else { "." },
+                cookie.domain,
+                if cookie.host_only { "FALSE" } else { "TRUE" },
+                cookie.path,
+                if cookie.secure { "TRUE" } else { "FALSE" },
+                cookie.expires.map_or(0, |expires| expires.timestamp()),
+                cookie.name,
+                cookie.value,
+            ));
+        }
+
+        fs::write(path, output)?;
+        Ok(())
+    }
+}
+
+// One tab-separated `cookies.txt` entry: domain, subdomains flag, path, secure flag, expiry, name, value
+fn parse_jar_line(line: &str, http_only: bool, now: DateTime<Utc>) -> Result<Cookie, String> {
+    let fields: Vec<&str> = line.split('\t').collect();
+    if fields.len() != 7 {
+        return Err(format!("expected 7 tab-separated fields, found {}", fields.len()));
+    }
+    let expires = match fields[4].parse::<i64>() {
+        Ok(0) => None,
+        Ok(timestamp) => Some(Utc.timestamp_opt(timestamp, 0).single().ok_or("invalid expiry")?),
+        Err(_) => return Err(format!("invalid expiry {:?}", fields[4])),
+    };
+
+    Ok(Cookie {
+        name: fields[5].to_string(),
+        value: fields[6].to_string(),
+        domain: fields[0].trim_start_matches('.').to_lowercase(),
+        host_only: fields[1] != "TRUE",
+        path: fields[2].to_string(),
+        expires,
+        secure: fields[3] == "TRUE",
+        http_only,
+        created: now,
+    })
+}
+
+// Parse one `Set-Cookie` header following the RFC 6265 section 5.2 algorithm
+fn parse_set_cookie(url: &Url, header: &str, now: DateTime<Utc>) -> Option<Cookie> {
+    let host = request_host(url)?;
+    let mut parts = header.split(';');
+    let (name, value) = parts.next()?.split_once('=')?;
+    let name = name.trim();
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut cookie = Cookie {
+        name: name.to_string(),
+        value: value.trim().to_string(),
+        domain: host.clone(),
+        host_only: true,
+        path: default_path(url),
+        expires: None,
+        secure: false,
+        http_only: false,
+        created: now,
+    };
+    let mut max_age: Option<DateTime<Utc>> = None;
+
+    for attribute in parts {
+        let (key, value) = attribute.split_once('=').unwrap_or((attribute, ""));
+        let value = value.trim();
+        match key.trim().to_lowercase().as_str() {
+            "expires" => {
+                if let Some(expires) = parse_cookie_date(value) {
+                    cookie.expires = Some(expires);
+                }
+            }
+            "max-age" => {
+                if let Ok(seconds) = value.parse::<i64>() {
+                    max_age = Some(if seconds <= 0 {
+                        DateTime::<Utc>::MIN_UTC
+                    } else {
+                        Duration::try_seconds(seconds)
+                            .and_then(|age| now.checked_add_signed(age))
+                            .unwrap_or(DateTime::<Utc>::MAX_UTC)
+                    });
+                }
+            }
+            "domain" => {
+                let domain = value.trim_start_matches('.').to_lowercase();
+                if domain.is_empty() {
+                    continue;
+                }
+                // A domain without a dot is a top-level domain like `md` that would share the
+                // cookie with every site under it. As with public suffixes (RFC 6265 section
+                // 5.3 step 5), the host may still name itself, e.g. `localhost`, and the cookie
+                // stays host-only.
+                if !domain.contains('.') {
+                    if domain != host {
+                        return None;
+                    }
+                    continue;
+                }
+                // Servers may only widen a cookie to a domain they belong to
+                if !domain_matches(&host, &domain) {
+                    return None;
+                }
+                cookie.domain = domain;
+                cookie.host_only = false;
+            }
+            "path" if value.starts_with('/') => cookie.path = value.to_string(),
+            "secure" => cookie.secure = true,
+            "httponly" => cookie.http_only = true,
+            _ => {}
+        }
+    }
+
+    // Max-Age wins over Expires when both are present
+    if max_age.is_some() {
+        cookie.expires = max_age;
+    }
+
+    Some(cookie)
+}
+
+fn request_host(url: &Url) -> Option<String> {
+    Some(url.host_str()?.trim_start_matches('[').trim_end_matches(']').to_lowercase())
+}
+
+// RFC 6265 section 5.1.3: IP addresses only match exactly
+fn domain_matches(host: &str, domain: &str) -> bool {
+    host == domain || (host.ends_with(&format!(".{}", domain)) && host.parse::<IpAddr>().is_err())
+}
+
+// RFC 6265 section 5.1.4
+fn path_matches(request_path: &str, cookie_path: &str) -> bool {
+    request_path == cookie_path
+        || (request_path.starts_with(cookie_path)
+            && (cookie_path.ends_with('/') || request_path[cookie_path.len()..].starts_with('/')))
+}
+
+// The directory of the request path, used when a cookie has no Path attribute
+fn default_path(url: &Url) -> String {
+    match url.path().rfind('/') {
+        Some(0) | None => "/".to_string(),
+        Some(index) => url.path()[..index].to_string(),
+    }
+}
+
+// Accepts the common `Expires` formats: RFC 1123, RFC 850 and the dashed Netscape variant
+fn parse_cookie_date(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc2822(value) {
+        return Some(date.with_timezone(&Utc));
+    }
+
+    let value = value.trim_end_matches(" GMT").trim_end_matches(" UTC");
+    let date_part = value.split_once(", ").map_or(value, |(_, rest)| rest);
+    ["%d-%b-%Y %H:%M:%S", "%d-%b-%y %H:%M:%S", "%d %b %Y %H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveDateTime::parse_from_str(date_part, format).ok())
+        .map(|naive| naive.and_utc())
+}
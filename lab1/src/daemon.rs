@@ -0,0 +1,386 @@
+//! Long-running mode: scrape on a cron schedule and keep rotating snapshots on disk.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::Duration;
+use chrono::{DateTime, Datelike, Local, NaiveDateTime, TimeDelta, Timelike, Utc};
+use crate::data::Data;
+use crate::json::parse_json;
+use crate::product::{Format, Product};
+
+// Snapshots are named `products-<UTC timestamp>.<format>`, which sorts oldest first
+const SNAPSHOT_PREFIX: &str = "products-";
+const LOCK_FILE: &str = ".lab1.lock";
+
+/// Comma-separated format names, e.g. `json,bi`; repeats are dropped
+pub fn parse_formats(list: &str) -> Result<Vec<Format>, Box<dyn Error>> {
+    let mut formats = Vec::new();
+    for name in list.split(',').map(str::trim).filter(|name| !name.is_empty()) {
+        let format = Format::parse(name)?;
+        if !formats.contains(&format) {
+            formats.push(format);
+        }
+    }
+    if formats.is_empty() {
+        return Err("No output formats given".into());
+    }
+    Ok(formats)
+}
+
+/// Settings of the long-running mode; it is enabled by giving a schedule
+#[derive(Debug, Clone)]
+pub struct DaemonOptions {
+    pub schedule: Option<Schedule>,
+    pub output_dir: PathBuf,
+    pub formats: Vec<Format>,
+    /// Snapshots kept per format; older ones are deleted after each successful run
+    pub keep: usize,
+    /// Defaults to `status.json` inside `output_dir`
+    pub status_file: Option<PathBuf>,
+}
+
+impl Default for DaemonOptions {
+    fn default() -> Self {
+        DaemonOptions {
+            schedule: None,
+            output_dir: PathBuf::from("snapshots"),
+            formats: Format::ALL.to_vec(),
+            keep: 24,
+            status_file: None,
+        }
+    }
+}
+
+impl DaemonOptions {
+    fn status_path(&self) -> PathBuf {
+        self.status_file.clone().unwrap_or_else(|| self.output_dir.join("status.json"))
+    }
+}
+
+/// A five-field cron expression (minute hour day-of-month month day-of-week) in local time.
+/// Fields take `*`, numbers, ranges `a-b`, steps `*/n` or `a-b/n` and comma-separated lists;
+/// `@hourly`, `@daily`, `@weekly` and `@monthly` are accepted as shorthands.
+#[derive(Debug, Clone)]
+pub struct Schedule {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    // Cron matches either day field when both are restricted, and only the restricted one otherwise
+    any_day: bool,
+    any_weekday: bool,
+}
+
+impl Schedule {
+    pub fn parse(expression: &str) -> Result<Schedule, Box<dyn Error>> {
+        let expanded = match expression.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expanded.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields.as_slice() else {
+            return Err(format!("Schedule needs 5 fields (minute hour day month weekday): {:?}", expression).into());
+        };
+
+        // Sunday may be written as 0 or 7
+        let mut weekdays = parse_field(weekday, 0, 7)?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays = (weekdays & !(1 << 7)) | 1;
+        }
+
+        Ok(Schedule {
+            expression: expression.trim().to_string(),
+            minutes: parse_field(minute, 0, 59)?,
+            hours: parse_field(hour, 0, 23)?,
+            days: parse_field(day, 1, 31)?,
+            months: parse_field(month, 1, 12)?,
+            weekdays,
+            any_day: *day == "*",
+            any_weekday: *weekday == "*",
+        })
+    }
+
+    /// The first matching minute strictly after `after`
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let start = after.naive_local().with_second(0)?.with_nanosecond(0)? + TimeDelta::minutes(1);
+        let mut time = start;
+        // Every valid expression fires within a few years; give up on ones like `0 0 31 2 *`
+        while time < start + TimeDelta::days(366 * 5) {
+            if !self.matches_day(time) {
+                time = (time.date() + TimeDelta::days(1)).and_hms_opt(0, 0, 0)?;
+            } else if self.hours & (1 << time.hour()) == 0 {
+                time = time.with_minute(0)? + TimeDelta::hours(1);
+            } else if self.minutes & (1 << time.minute()) == 0 {
+                time += TimeDelta::minutes(1);
+            } else if let Some(local) = time.and_local_timezone(Local).earliest() {
+                return Some(local);
+            } else {
+                // Skipped by a daylight saving change
+                time += TimeDelta::minutes(1);
+            }
+        }
+        None
+    }
+
+    fn matches_day(&self, time: NaiveDateTime) -> bool {
+        if self.months & (1 << time.month()) == 0 {
+            return false;
+        }
+        let day = self.days & (1 << time.day()) != 0;
+        let weekday = self.weekdays & (1 << time.weekday().num_days_from_sunday()) != 0;
+        match (self.any_day, self.any_weekday) {
+            (false, false) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+}
+
+// Bit `n` is set when the field allows value `n`
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64, Box<dyn Error>> {
+    let invalid = || format!("Invalid schedule field {:?} (allowed {}-{})", field, min, max);
+    let mut bits = 0u64;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().ok().filter(|s| *s > 0).ok_or_else(invalid)?),
+            None => (part, 1),
+        };
+        let (first, last) = match range {
+            "*" => (min, max),
+            _ => match range.split_once('-') {
+                Some((a, b)) => (a.parse().map_err(|_| invalid())?, b.parse().map_err(|_| invalid())?),
+                // `5/15` means from 5 to the end in steps of 15
+                None => {
+                    let value = range.parse().map_err(|_| invalid())?;
+                    (value, if part.contains('/') { max } else { value })
+                }
+            },
+        };
+        if first < min || last > max || first > last {
+            return Err(invalid().into());
+        }
+        for value in (first..=last).step_by(step as usize) {
+            bits |= 1 << value;
+        }
+    }
+
+    Ok(bits)
+}
+
+/// Scrape on the schedule until the process is stopped. A failed run is recorded and the daemon
+/// carries on; runs never overlap, and a run that overshoots its slot skips the missed ones.
+pub fn run_daemon(
+    options: &DaemonOptions,
+    mut scrape: impl FnMut() -> Result<Vec<Product>, Box<dyn Error>>,
+) -> Result<(), Box<dyn Error>> {
+    let schedule = options.schedule.as_ref().ok_or("Daemon mode needs a schedule")?;
+    fs::create_dir_all(&options.output_dir)
+        .map_err(|e| format!("Cannot create {}: {}", options.output_dir.display(), e))?;
+    let mut status = Status::load(&options.status_path());
+    println!("Scraping on schedule {:?} into {}", schedule.expression, options.output_dir.display());
+
+    loop {
+        let next = schedule.next_after(Local::now())
+            .ok_or_else(|| format!("Schedule {:?} never fires", schedule.expression))?;
+        status.next_run = Some(next.with_timezone(&Utc));
+        status.save(&options.status_path())?;
+        println!("Next run at {}", next.to_rfc3339());
+        sleep_until(next);
+
+        run_once(options, &mut status, &mut scrape);
+        status.save(&options.status_path())?;
+    }
+}
+
+fn run_once(
+    options: &DaemonOptions,
+    status: &mut Status,
+    scrape: &mut impl FnMut() -> Result<Vec<Product>, Box<dyn Error>>,
+) {
+    // Guards against a second daemon, or a manual run, writing into the same directory
+    let _lock = match RunLock::acquire(&options.output_dir.join(LOCK_FILE)) {
+        Ok(lock) => lock,
+        Err(e) => {
+            eprintln!("Skipping run: {}", e);
+            status.record_failure(Utc::now(), &e.to_string());
+            return;
+        }
+    };
+
+    let started = Utc::now();
+    status.running_since = Some(started);
+    if let Err(e) = status.save(&options.status_path()) {
+        eprintln!("Cannot write status file: {}", e);
+    }
+
+    let result = scrape().and_then(|products| {
+        let files = write_snapshots(options, &products, started)?;
+        Ok((products.len(), files))
+    });
+    status.running_since = None;
+
+    match result {
+        Ok((count, files)) => {
+            println!("Run finished: {} products written to {}", count, files.join(", "));
+            status.record_success(started, Utc::now(), count, files);
+            if let Err(e) = rotate_snapshots(options) {
+                eprintln!("Cannot rotate snapshots: {}", e);
+            }
+        }
+        Err(e) => {
+            eprintln!("Run failed: {}", e);
+            status.record_failure(Utc::now(), &e.to_string());
+        }
+    }
+}
+
+fn write_snapshots(options: &DaemonOptions, products: &[Product], started: DateTime<Utc>) -> Result<Vec<String>, Box<dyn Error>> {
+    let stamp = started.format("%Y%m%dT%H%M%SZ");
+    let mut files = Vec::new();
+    for format in &options.formats {
+        let path = options.output_dir.join(format!("{}{}.{}", SNAPSHOT_PREFIX, stamp, format.extension()));
+        write_atomically(&path, &format.encode(products))?;
+        files.push(path.display().to_string());
+    }
+    Ok(files)
+}
+
+// Keep the newest `keep` snapshots of each format
+fn rotate_snapshots(options: &DaemonOptions) -> Result<(), Box<dyn Error>> {
+    for format in &options.formats {
+        let suffix = format!(".{}", format.extension());
+        let mut snapshots: Vec<PathBuf> = fs::read_dir(&options.output_dir)?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| {
+                path.file_name().and_then(|name| name.to_str())
+                    .is_some_and(|name| name.starts_with(SNAPSHOT_PREFIX) && name.ends_with(&suffix))
+            })
+            .collect();
+        snapshots.sort();
+
+        let excess = snapshots.len().saturating_sub(options.keep);
+        for old in &snapshots[..excess] {
+            fs::remove_file(old).map_err(|e| format!("Cannot remove {}: {}", old.display(), e))?;
+        }
+    }
+    Ok(())
+}
+
+// Readers never see a half-written file
+fn write_atomically(path: &Path, contents: &str) -> Result<(), Box<dyn Error>> {
+    let temporary = path.with_extension("tmp");
+    fs::write(&temporary, contents).map_err(|e| format!("Cannot write {}: {}", temporary.display(), e))?;
+    fs::rename(&temporary, path).map_err(|e| format!("Cannot write {}: {}", path.display(), e))?;
+    Ok(())
+}
+
+// Sleep in short steps so a changed system clock is noticed
+fn sleep_until(deadline: DateTime<Local>) {
+    while let Ok(remaining) = (deadline - Local::now()).to_std() {
+        thread::sleep(remaining.min(Duration::from_secs(30)));
+    }
+}
+
+// Lock file holding the PID of the run that owns the output directory
+struct RunLock {
+    path: PathBuf,
+}
+
+impl RunLock {
+    fn acquire(path: &Path) -> Result<RunLock, Box<dyn Error>> {
+        for _ in 0..2 {
+            match OpenOptions::new().write(true).create_new(true).open(path) {
+                Ok(mut file) => {
+                    write!(file, "{}", std::process::id())?;
+                    return Ok(RunLock { path: path.to_path_buf() });
+                }
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {
+                    let owner = fs::read_to_string(path).unwrap_or_default();
+                    // A lock left behind by a crashed run; only detectable where /proc exists
+                    let stale = owner.trim().is_empty()
+                        || (Path::new("/proc").is_dir() && !Path::new("/proc").join(owner.trim()).exists());
+                    if !stale {
+                        return Err(format!("another run (pid {}) holds {}", owner.trim(), path.display()).into());
+                    }
+                    fs::remove_file(path)?;
+                }
+                Err(e) => return Err(format!("Cannot create {}: {}", path.display(), e).into()),
+            }
+        }
+        Err(format!("Cannot acquire {}", path.display()).into())
+    }
+}
+
+impl Drop for RunLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+// Contents of the status file; earlier results survive restarts
+#[derive(Debug, Default)]
+struct Status {
+    running_since: Option<DateTime<Utc>>,
+    next_run: Option<DateTime<Utc>>,
+    last_success: Option<Data>,
+    last_failure: Option<Data>,
+    consecutive_failures: i32,
+}
+
+impl Status {
+    fn load(path: &Path) -> Status {
+        let Some(data) = fs::read_to_string(path).ok().and_then(|text| parse_json(&text).ok()) else {
+            return Status::default();
+        };
+        Status {
+            last_success: data.get("last_success").filter(|d| **d != Data::Null).cloned(),
+            last_failure: data.get("last_failure").filter(|d| **d != Data::Null).cloned(),
+            consecutive_failures: match data.get("consecutive_failures") {
+                Some(Data::Int(count)) => *count,
+                _ => 0,
+            },
+            ..Status::default()
+        }
+    }
+
+    fn record_success(&mut self, started: DateTime<Utc>, finished: DateTime<Utc>, products: usize, files: Vec<String>) {
+        self.last_success = Some(Data::Map(HashMap::from([
+            ("started_at".to_string(), Data::Text(started.to_rfc3339())),
+            ("finished_at".to_string(), Data::Text(finished.to_rfc3339())),
+            ("products".to_string(), Data::Int(products as i32)),
+            ("files".to_string(), Data::List(files.into_iter().map(Data::Text).collect())),
+        ])));
+        self.consecutive_failures = 0;
+    }
+
+    fn record_failure(&mut self, finished: DateTime<Utc>, error: &str) {
+        self.last_failure = Some(Data::Map(HashMap::from([
+            ("finished_at".to_string(), Data::Text(finished.to_rfc3339())),
+            ("error".to_string(), Data::Text(error.to_string())),
+        ])));
+        self.consecutive_failures += 1;
+    }
+
+    fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let time = |t: Option<DateTime<Utc>>| t.map_or(Data::Null, |t| Data::Text(t.to_rfc3339()));
+        let status = Data::Map(HashMap::from([
+            ("state".to_string(), Data::Text(if self.running_since.is_some() { "running" } else { "idle" }.to_string())),
+            ("running_since".to_string(), time(self.running_since)),
+            ("next_run".to_string(), time(self.next_run)),
+            ("last_success".to_string(), self.last_success.clone().unwrap_or(Data::Null)),
+            ("last_failure".to_string(), self.last_failure.clone().unwrap_or(Data::Null)),
+            ("consecutive_failures".to_string(), Data::Int(self.consecutive_failures)),
+            ("pid".to_string(), Data::Int(std::process::id() as i32)),
+        ]));
+        write_atomically(path, &status.to_json(0))
+    }
+}
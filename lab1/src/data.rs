@@ -1,7 +1,11 @@
+//! The generic value tree products convert to, and its JSON and BI encoders.
+
 use std::collections::HashMap;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Data {
+    Null,
+    Bool(bool),
     Int(i32),
     Float(f64),
     Text(String),
@@ -13,14 +17,16 @@ pub const MDL_TO_EUR: f64 = 0.052; // Conversion rate from MDL to EUR
 pub const EUR_TO_MDL: f64 = 19.24;
 
 impl Data {
-    // Serialize Data to the custom Bracketed-Indented (BI) format
+    /// Serialize Data to the custom Bracketed-Indented (BI) format
     pub fn to_bi(&self, indent: usize) -> String {
         let indent_str = " ".repeat(indent);
 
         match self {
+            Data::Null => format!("{}null", indent_str),
+            Data::Bool(b) => format!("{}{}", indent_str, b),
             Data::Int(i) => format!("{}{}", indent_str, i),
             Data::Float(f) => format!("{}{}", indent_str, f),
-            Data::Text(s) => format!("{}\"{}\"", indent_str, s),
+            Data::Text(s) => format!("{}\"{}\"", indent_str, escape_json(s)),
             Data::List(items) => {
                 let bi_items: Vec<String> = items.iter().map(|item| item.to_bi(indent + 4)).collect();
                 format!("{}[\n{}\n{}]", indent_str, bi_items.join("\n"), indent_str)
@@ -34,4 +40,73 @@ impl Data {
             }
         }
     }
-}
\ No newline at end of file
+
+    /// Serialize Data to pretty-printed JSON, with map keys sorted so output is stable
+    pub fn to_json(&self, indent: usize) -> String {
+        let inner = " ".repeat(indent + 4);
+        match self {
+            Data::Null => "null".to_string(),
+            Data::Bool(b) => b.to_string(),
+            Data::Int(i) => i.to_string(),
+            Data::Float(f) if f.is_finite() => f.to_string(),
+            Data::Float(_) => "null".to_string(),
+            Data::Text(s) => format!("\"{}\"", escape_json(s)),
+            Data::List(items) if items.is_empty() => "[]".to_string(),
+            Data::List(items) => {
+                let json_items: Vec<String> = items.iter()
+                    .map(|item| format!("{}{}", inner, item.to_json(indent + 4)))
+                    .collect();
+                format!("[\n{}\n{}]", json_items.join(",\n"), " ".repeat(indent))
+            }
+            Data::Map(map) if map.is_empty() => "{}".to_string(),
+            Data::Map(map) => {
+                let mut keys: Vec<&String> = map.keys().collect();
+                keys.sort();
+                let json_entries: Vec<String> = keys.iter()
+                    .map(|k| format!("{}\"{}\": {}", inner, escape_json(k), map[*k].to_json(indent + 4)))
+                    .collect();
+                format!("{{\n{}\n{}}}", json_entries.join(",\n"), " ".repeat(indent))
+            }
+        }
+    }
+
+    /// Look up a key when this is a map
+    pub fn get(&self, key: &str) -> Option<&Data> {
+        match self {
+            Data::Map(map) => map.get(key),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Data::Text(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Data::Int(i) => Some(*i as f64),
+            Data::Float(f) => Some(*f),
+            _ => None,
+        }
+    }
+}
+
+/// Escape a string body for JSON; BI text uses the same escapes
+pub fn escape_json(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    for c in input.chars() {
+        match c {
+            '"' => output.push_str("\\\""),
+            '\\' => output.push_str("\\\\"),
+            '\n' => output.push_str("\\n"),
+            '\r' => output.push_str("\\r"),
+            '\t' => output.push_str("\\t"),
+            c if (c as u32) < 0x20 => output.push_str(&format!("\\u{:04x}", c as u32)),
+            c => output.push(c),
+        }
+    }
+    output
+}
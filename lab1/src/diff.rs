@@ -0,0 +1,157 @@
+//! Comparison of two product lists, e.g. saved outputs of two runs.
+
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use crate::data::Data;
+use crate::normalize::name_key;
+use crate::product::Product;
+
+/// One field whose value differs between the two outputs; `Null` stands for a missing field
+#[derive(Debug, Clone)]
+pub struct FieldChange {
+    pub field: String,
+    pub before: Data,
+    pub after: Data,
+}
+
+#[derive(Debug, Clone)]
+pub struct ProductChange {
+    pub name: String,
+    pub link: String,
+    /// `link` or `name`
+    pub matched_by: &'static str,
+    pub changes: Vec<FieldChange>,
+}
+
+#[derive(Debug, Default)]
+pub struct ProductDiff {
+    pub added: Vec<Product>,
+    pub removed: Vec<Product>,
+    pub changed: Vec<ProductChange>,
+    pub unchanged: usize,
+}
+
+/// Match products by link, falling back to their normalized name key, and compare matched pairs
+pub fn diff_products(before: Vec<Product>, after: Vec<Product>) -> ProductDiff {
+    let mut diff = ProductDiff::default();
+    let mut after: Vec<Option<Product>> = after.into_iter().map(Some).collect();
+    let mut unmatched = Vec::new();
+
+    let mut by_link: HashMap<String, usize> = HashMap::new();
+    for (index, product) in after.iter().enumerate().rev() {
+        if let Some(product) = product.as_ref().filter(|p| !p.link.trim().is_empty()) {
+            by_link.insert(product.link.trim().to_string(), index);
+        }
+    }
+    for old in before {
+        match by_link.remove(old.link.trim()).and_then(|index| after[index].take()) {
+            Some(new) => diff.record(old, new, "link"),
+            None => unmatched.push(old),
+        }
+    }
+
+    for old in unmatched {
+        let key = name_key(&old.name);
+        let position = after.iter().position(|p| p.as_ref().is_some_and(|p| name_key(&p.name) == key));
+        match position.and_then(|index| after[index].take()) {
+            Some(new) => diff.record(old, new, "name"),
+            None => diff.removed.push(old),
+        }
+    }
+
+    diff.added = after.into_iter().flatten().collect();
+    diff
+}
+
+impl ProductDiff {
+    fn record(&mut self, old: Product, new: Product, matched_by: &'static str) {
+        let changes = field_changes(&old.to_data(), &new.to_data());
+        if changes.is_empty() {
+            self.unchanged += 1;
+        } else {
+            self.changed.push(ProductChange { name: new.name, link: new.link, matched_by, changes });
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    pub fn to_data(&self) -> Data {
+        let changed = self.changed.iter().map(|change| {
+            let fields = change.changes.iter().map(|field| {
+                Data::Map(HashMap::from([
+                    ("field".to_string(), Data::Text(field.field.clone())),
+                    ("before".to_string(), field.before.clone()),
+                    ("after".to_string(), field.after.clone()),
+                ]))
+            }).collect();
+            Data::Map(HashMap::from([
+                ("name".to_string(), Data::Text(change.name.clone())),
+                ("link".to_string(), Data::Text(change.link.clone())),
+                ("matched_by".to_string(), Data::Text(change.matched_by.to_string())),
+                ("changes".to_string(), Data::List(fields)),
+            ]))
+        }).collect();
+
+        Data::Map(HashMap::from([
+            ("added".to_string(), Data::List(self.added.iter().map(Product::to_data).collect())),
+            ("removed".to_string(), Data::List(self.removed.iter().map(Product::to_data).collect())),
+            ("changed".to_string(), Data::List(changed)),
+            ("unchanged".to_string(), Data::Int(self.unchanged as i32)),
+        ]))
+    }
+}
+
+fn field_changes(before: &Data, after: &Data) -> Vec<FieldChange> {
+    let (Data::Map(before), Data::Map(after)) = (before, after) else {
+        return Vec::new();
+    };
+    let mut fields: Vec<&String> = before.keys().chain(after.keys()).collect::<HashSet<_>>().into_iter().collect();
+    fields.sort();
+
+    fields.into_iter()
+        .filter_map(|field| {
+            let old = before.get(field).unwrap_or(&Data::Null);
+            let new = after.get(field).unwrap_or(&Data::Null);
+            (old != new).then(|| FieldChange { field: field.clone(), before: old.clone(), after: new.clone() })
+        })
+        .collect()
+}
+
+impl fmt::Display for ProductDiff {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Added ({}):", self.added.len())?;
+        for product in &self.added {
+            writeln!(f, "  + {} <{}> {:.2} MDL", product.name, product.link, product.price)?;
+        }
+        writeln!(f, "Removed ({}):", self.removed.len())?;
+        for product in &self.removed {
+            writeln!(f, "  - {} <{}> {:.2} MDL", product.name, product.link, product.price)?;
+        }
+        writeln!(f, "Changed ({}):", self.changed.len())?;
+        for change in &self.changed {
+            writeln!(f, "  ~ {} <{}> (matched by {})", change.name, change.link, change.matched_by)?;
+            for field in &change.changes {
+                write!(f, "      {}: {} -> {}", field.field, describe(&field.before), describe(&field.after))?;
+                if let (Some(old), Some(new)) = (field.before.as_f64(), field.after.as_f64()) {
+                    if old != 0.0 {
+                        write!(f, " ({:+.2}, {:+.1}%)", new - old, (new - old) / old * 100.0)?;
+                    }
+                }
+                writeln!(f)?;
+            }
+        }
+        write!(f, "Unchanged: {}", self.unchanged)
+    }
+}
+
+fn describe(value: &Data) -> String {
+    match value {
+        Data::Null => "(none)".to_string(),
+        Data::Text(text) => format!("{:?}", text),
+        Data::Int(i) => i.to_string(),
+        Data::Float(x) => x.to_string(),
+        other => other.to_json(0).split_whitespace().collect::<Vec<_>>().join(" "),
+    }
+}
@@ -0,0 +1,296 @@
+//! HTTP traffic archives in the HAR 1.2 format, recorded by the client and replayed in its place.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+use std::sync::Mutex;
+use chrono::{DateTime, Utc};
+use url::Url;
+use crate::base64;
+use crate::data::Data;
+use crate::http::{body_too_large, Limits, Response};
+use crate::json::parse_json;
+
+/// Milliseconds spent in each phase of one request; `ssl` is `None` for plain HTTP
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Timings {
+    /// Name lookup and TCP connection, including any proxy handshake
+    pub connect: f64,
+    pub ssl: Option<f64>,
+    pub send: f64,
+    /// Until the response headers had been read
+    pub wait: f64,
+    pub receive: f64,
+}
+
+impl Timings {
+    pub fn total(&self) -> f64 {
+        self.connect + self.ssl.unwrap_or(0.0) + self.send + self.wait + self.receive
+    }
+}
+
+/// What `Client::fetch` saw of a request besides its response
+#[derive(Debug, Clone, Default)]
+pub struct Trace {
+    pub request_headers: Vec<(String, String)>,
+    pub timings: Timings,
+}
+
+/// One request with its response, or the error that took the response's place
+#[derive(Debug, Clone)]
+pub struct HarEntry {
+    pub started: DateTime<Utc>,
+    pub method: String,
+    pub url: String,
+    pub request_headers: Vec<(String, String)>,
+    pub response: Result<Response, String>,
+    pub timings: Timings,
+}
+
+/// Recorded exchanges in the order they happened. Replaying serves the recordings of a URL in
+/// that same order and keeps repeating the last one once they run out.
+#[derive(Debug, Default)]
+pub struct HarArchive {
+    entries: Vec<HarEntry>,
+    // Next recording to serve for each URL
+    replayed: Mutex<HashMap<String, usize>>,
+}
+
+impl HarArchive {
+    pub fn new() -> HarArchive {
+        HarArchive::default()
+    }
+
+    pub fn entries(&self) -> &[HarEntry] {
+        &self.entries
+    }
+
+    pub fn record(&mut self, entry: HarEntry) {
+        self.entries.push(entry);
+    }
+
+    /// The recorded response to a GET of `url`, held to the same body limit as a live fetch
+    pub fn replay(&self, url: &str, limits: &Limits) -> Result<Response, Box<dyn Error>> {
+        let key = replay_key(url);
+        let recordings: Vec<&HarEntry> = self.entries.iter()
+            .filter(|entry| entry.method.eq_ignore_ascii_case("GET") && replay_key(&entry.url) == key)
+            .collect();
+        if recordings.is_empty() {
+            return Err(format!("No recorded response for GET {}", url).into());
+        }
+
+        let mut replayed = self.replayed.lock().unwrap();
+        let next = replayed.entry(key).or_insert(0);
+        let entry = recordings[(*next).min(recordings.len() - 1)];
+        *next += 1;
+
+        let response = entry.response.clone()?;
+        if response.body.len() > limits.max_body_bytes {
+            return Err(body_too_large(limits));
+        }
+        Ok(response)
+    }
+
+    /// Read an archive written by `save`, or one exported from a browser's developer tools
+    pub fn load(path: &Path) -> Result<HarArchive, Box<dyn Error>> {
+        let text = fs::read_to_string(path)?;
+        let har = parse_json(&text).map_err(|e| format!("Invalid HAR file {}: {}", path.display(), e))?;
+        let Some(Data::List(entries)) = har.get("log").and_then(|log| log.get("entries")) else {
+            return Err(format!("HAR file {} has no log.entries list", path.display()).into());
+        };
+
+        let entries = entries.iter()
+            .enumerate()
+            .map(|(i, entry)| entry_from_data(entry).map_err(|e| format!("HAR entry {}: {}", i + 1, e)))
+            .collect::<Result<_, _>>()?;
+        Ok(HarArchive { entries, replayed: Mutex::default() })
+    }
+
+    pub fn save(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let log = object([
+            ("version", Data::Text("1.2".to_string())),
+            ("creator", object([
+                ("name", Data::Text(env!("CARGO_PKG_NAME").to_string())),
+                ("version", Data::Text(env!("CARGO_PKG_VERSION").to_string())),
+            ])),
+            ("pages", Data::List(Vec::new())),
+            ("entries", Data::List(self.entries.iter().map(entry_to_data).collect())),
+        ]);
+        fs::write(path, object([("log", log)]).to_json(0))?;
+        Ok(())
+    }
+}
+
+// Fragments never reach the server, so they do not tell recordings apart
+fn replay_key(url: &str) -> String {
+    match Url::parse(url) {
+        Ok(mut url) => {
+            url.set_fragment(None);
+            url.to_string()
+        }
+        Err(_) => url.to_string(),
+    }
+}
+
+fn object<const N: usize>(fields: [(&str, Data); N]) -> Data {
+    Data::Map(fields.into_iter().map(|(key, value)| (key.to_string(), value)).collect())
+}
+
+fn name_values<'a>(pairs: impl IntoIterator<Item = (&'a str, &'a str)>) -> Data {
+    Data::List(pairs.into_iter()
+        .map(|(name, value)| object([("name", Data::Text(name.to_string())), ("value", Data::Text(value.to_string()))]))
+        .collect())
+}
+
+fn milliseconds(value: f64) -> Data {
+    Data::Float((value * 1000.0).round() / 1000.0)
+}
+
+fn entry_to_data(entry: &HarEntry) -> Data {
+    let query: Vec<(String, String)> = Url::parse(&entry.url)
+        .map(|url| url.query_pairs().map(|(name, value)| (name.into_owned(), value.into_owned())).collect())
+        .unwrap_or_default();
+    // The request went out in whichever version the response came back in
+    let version = entry.response.as_ref().ok()
+        .and_then(|response| response.headers.first())
+        .and_then(|status_line| status_line.split(' ').next())
+        .unwrap_or("HTTP/1.1");
+    let request = object([
+        ("method", Data::Text(entry.method.clone())),
+        ("url", Data::Text(entry.url.clone())),
+        ("httpVersion", Data::Text(version.to_string())),
+        ("cookies", Data::List(Vec::new())),
+        ("headers", name_values(entry.request_headers.iter().map(|(name, value)| (name.as_str(), value.as_str())))),
+        ("queryString", name_values(query.iter().map(|(name, value)| (name.as_str(), value.as_str())))),
+        ("headersSize", Data::Int(-1)),
+        ("bodySize", Data::Int(0)),
+    ]);
+
+    let response = match &entry.response {
+        Ok(response) => response_to_data(response),
+        // Browsers record failed requests the same way: status 0 and the reason in `_error`
+        Err(error) => object([
+            ("status", Data::Int(0)),
+            ("statusText", Data::Text(String::new())),
+            ("httpVersion", Data::Text(String::new())),
+            ("cookies", Data::List(Vec::new())),
+            ("headers", Data::List(Vec::new())),
+            ("content", object([("size", Data::Int(0)), ("mimeType", Data::Text("x-unknown".to_string()))])),
+            ("redirectURL", Data::Text(String::new())),
+            ("headersSize", Data::Int(-1)),
+            ("bodySize", Data::Int(-1)),
+            ("_error", Data::Text(error.clone())),
+        ]),
+    };
+
+    let timings = entry.timings;
+    object([
+        ("startedDateTime", Data::Text(entry.started.to_rfc3339())),
+        ("time", milliseconds(timings.total())),
+        ("request", request),
+        ("response", response),
+        ("cache", Data::Map(HashMap::new())),
+        ("timings", object([
+            ("blocked", Data::Int(-1)),
+            ("dns", Data::Int(-1)),
+            ("connect", milliseconds(timings.connect)),
+            ("ssl", timings.ssl.map_or(Data::Int(-1), milliseconds)),
+            ("send", milliseconds(timings.send)),
+            ("wait", milliseconds(timings.wait)),
+            ("receive", milliseconds(timings.receive)),
+        ])),
+    ])
+}
+
+fn response_to_data(response: &Response) -> Data {
+    let status_line = response.headers.first().map(String::as_str).unwrap_or("");
+    let mut parts = status_line.splitn(3, ' ');
+    let version = parts.next().unwrap_or("HTTP/1.1");
+    let status_text = parts.nth(1).unwrap_or("");
+    let headers = response.headers.iter().skip(1).filter_map(|line| line.split_once(':')).map(|(n, v)| (n.trim(), v.trim()));
+
+    // Text is stored as is, anything else (images, other charsets) as base64
+    let mut content = vec![
+        ("size", Data::Int(response.body.len() as i32)),
+        ("mimeType", Data::Text(response.header("content-type").unwrap_or("x-unknown").to_string())),
+    ];
+    match std::str::from_utf8(&response.body) {
+        Ok(text) => content.push(("text", Data::Text(text.to_string()))),
+        Err(_) => {
+            content.push(("text", Data::Text(base64::encode(&response.body))));
+            content.push(("encoding", Data::Text("base64".to_string())));
+        }
+    }
+
+    object([
+        ("status", Data::Int(response.status as i32)),
+        ("statusText", Data::Text(status_text.to_string())),
+        ("httpVersion", Data::Text(version.to_string())),
+        ("cookies", Data::List(Vec::new())),
+        ("headers", name_values(headers)),
+        ("content", Data::Map(content.into_iter().map(|(key, value)| (key.to_string(), value)).collect())),
+        ("redirectURL", Data::Text(response.header("location").unwrap_or("").to_string())),
+        ("headersSize", Data::Int(-1)),
+        ("bodySize", Data::Int(response.body.len() as i32)),
+    ])
+}
+
+fn entry_from_data(entry: &Data) -> Result<HarEntry, Box<dyn Error>> {
+    let text = |data: &Data, key: &str| data.get(key).and_then(Data::as_str).map(str::to_string);
+    let request = entry.get("request").ok_or("no request")?;
+    let response = entry.get("response").ok_or("no response")?;
+
+    let started = text(entry, "startedDateTime")
+        .and_then(|value| DateTime::parse_from_rfc3339(&value).ok())
+        .map_or_else(Utc::now, |started| started.with_timezone(&Utc));
+
+    let headers = |data: &Data| -> Vec<(String, String)> {
+        let Some(Data::List(headers)) = data.get("headers") else {
+            return Vec::new();
+        };
+        headers.iter()
+            .filter_map(|header| Some((text(header, "name")?, text(header, "value")?)))
+            // HTTP/2 recordings from browsers include pseudo-headers such as `:status`
+            .filter(|(name, _)| !name.starts_with(':'))
+            .collect()
+    };
+
+    let status = response.get("status").and_then(Data::as_f64).unwrap_or(0.0) as u32;
+    let outcome = if let Some(error) = text(response, "_error") {
+        Err(error)
+    } else if status == 0 {
+        Err("No response was recorded".to_string())
+    } else {
+        let version = text(response, "httpVersion").filter(|v| !v.is_empty()).unwrap_or_else(|| "HTTP/1.1".to_string());
+        let status_text = text(response, "statusText").unwrap_or_default();
+        let mut lines = vec![format!("{} {} {}", version, status, status_text).trim_end().to_string()];
+        lines.extend(headers(response).into_iter().map(|(name, value)| format!("{}: {}", name, value)));
+
+        let content = response.get("content");
+        let body_text = content.and_then(|c| text(c, "text")).unwrap_or_default();
+        let body = match content.and_then(|c| text(c, "encoding")) {
+            Some(encoding) if encoding.eq_ignore_ascii_case("base64") => base64::decode(&body_text)?,
+            _ => body_text.into_bytes(),
+        };
+        Ok(Response { status, headers: lines, body })
+    };
+
+    let timing = |key: &str| {
+        entry.get("timings").and_then(|t| t.get(key)).and_then(Data::as_f64).filter(|value| *value >= 0.0)
+    };
+    Ok(HarEntry {
+        started,
+        method: text(request, "method").unwrap_or_else(|| "GET".to_string()),
+        url: text(request, "url").ok_or("request without a url")?,
+        request_headers: headers(request),
+        response: outcome,
+        timings: Timings {
+            connect: timing("connect").unwrap_or(0.0),
+            ssl: timing("ssl"),
+            send: timing("send").unwrap_or(0.0),
+            wait: timing("wait").unwrap_or(0.0),
+            receive: timing("receive").unwrap_or(0.0),
+        },
+    })
+}
@@ -0,0 +1,260 @@
+//! HPACK header compression for HTTP/2 (RFC 7541).
+
+use std::collections::{HashMap, VecDeque};
+use std::error::Error;
+use std::sync::OnceLock;
+
+/// Header name and value pairs, names lowercased as HTTP/2 requires
+pub type HeaderList = Vec<(String, String)>;
+
+// RFC 7541 Appendix A; index 1 is the first entry
+const STATIC_TABLE: [(&str, &str); 61] = [
+    (":authority", ""), (":method", "GET"), (":method", "POST"), (":path", "/"), (":path", "/index.html"),
+    (":scheme", "http"), (":scheme", "https"), (":status", "200"), (":status", "204"), (":status", "206"),
+    (":status", "304"), (":status", "400"), (":status", "404"), (":status", "500"), ("accept-charset", ""),
+    ("accept-encoding", "gzip, deflate"), ("accept-language", ""), ("accept-ranges", ""), ("accept", ""),
+    ("access-control-allow-origin", ""), ("age", ""), ("allow", ""), ("authorization", ""), ("cache-control", ""),
+    ("content-disposition", ""), ("content-encoding", ""), ("content-language", ""), ("content-length", ""),
+    ("content-location", ""), ("content-range", ""), ("content-type", ""), ("cookie", ""), ("date", ""),
+    ("etag", ""), ("expect", ""), ("expires", ""), ("from", ""), ("host", ""), ("if-match", ""),
+    ("if-modified-since", ""), ("if-none-match", ""), ("if-range", ""), ("if-unmodified-since", ""),
+    ("last-modified", ""), ("link", ""), ("location", ""), ("max-forwards", ""), ("proxy-authenticate", ""),
+    ("proxy-authorization", ""), ("range", ""), ("referer", ""), ("refresh", ""), ("retry-after", ""),
+    ("server", ""), ("set-cookie", ""), ("strict-transport-security", ""), ("transfer-encoding", ""),
+    ("user-agent", ""), ("vary", ""), ("via", ""), ("www-authenticate", ""),
+];
+
+// RFC 7541 Appendix B: code and bit length of every byte value; EOS is never valid in a string
+const HUFFMAN_CODES: [(u32, u8); 256] = [
+    (0x1ff8, 13), (0x7fffd8, 23), (0xfffffe2, 28), (0xfffffe3, 28), (0xfffffe4, 28), (0xfffffe5, 28), (0xfffffe6, 28), (0xfffffe7, 28),
+    (0xfffffe8, 28), (0xffffea, 24), (0x3ffffffc, 30), (0xfffffe9, 28), (0xfffffea, 28), (0x3ffffffd, 30), (0xfffffeb, 28), (0xfffffec, 28),
+    (0xfffffed, 28), (0xfffffee, 28), (0xfffffef, 28), (0xffffff0, 28), (0xffffff1, 28), (0xffffff2, 28), (0x3ffffffe, 30), (0xffffff3, 28),
+    (0xffffff4, 28), (0xffffff5, 28), (0xffffff6, 28), (0xffffff7, 28), (0xffffff8, 28), (0xffffff9, 28), (0xffffffa, 28), (0xffffffb, 28),
+    (0x14, 6), (0x3f8, 10), (0x3f9, 10), (0xffa, 12), (0x1ff9, 13), (0x15, 6), (0xf8, 8), (0x7fa, 11),
+    (0x3fa, 10), (0x3fb, 10), (0xf9, 8), (0x7fb, 11), (0xfa, 8), (0x16, 6), (0x17, 6), (0x18, 6),
+    (0x0, 5), (0x1, 5), (0x2, 5), (0x19, 6), (0x1a, 6), (0x1b, 6), (0x1c, 6), (0x1d, 6),
+    (0x1e, 6), (0x1f, 6), (0x5c, 7), (0xfb, 8), (0x7ffc, 15), (0x20, 6), (0xffb, 12), (0x3fc, 10),
+    (0x1ffa, 13), (0x21, 6), (0x5d, 7), (0x5e, 7), (0x5f, 7), (0x60, 7), (0x61, 7), (0x62, 7),
+    (0x63, 7), (0x64, 7), (0x65, 7), (0x66, 7), (0x67, 7), (0x68, 7), (0x69, 7), (0x6a, 7),
+    (0x6b, 7), (0x6c, 7), (0x6d, 7), (0x6e, 7), (0x6f, 7), (0x70, 7), (0x71, 7), (0x72, 7),
+    (0xfc, 8), (0x73, 7), (0xfd, 8), (0x1ffb, 13), (0x7fff0, 19), (0x1ffc, 13), (0x3ffc, 14), (0x22, 6),
+    (0x7ffd, 15), (0x3, 5), (0x23, 6), (0x4, 5), (0x24, 6), (0x5, 5), (0x25, 6), (0x26, 6),
+    (0x27, 6), (0x6, 5), (0x74, 7), (0x75, 7), (0x28, 6), (0x29, 6), (0x2a, 6), (0x7, 5),
+    (0x2b, 6), (0x76, 7), (0x2c, 6), (0x8, 5), (0x9, 5), (0x2d, 6), (0x77, 7), (0x78, 7),
+    (0x79, 7), (0x7a, 7), (0x7b, 7), (0x7ffe, 15), (0x7fc, 11), (0x3ffd, 14), (0x1ffd, 13), (0xffffffc, 28),
+    (0xfffe6, 20), (0x3fffd2, 22), (0xfffe7, 20), (0xfffe8, 20), (0x3fffd3, 22), (0x3fffd4, 22), (0x3fffd5, 22), (0x7fffd9, 23),
+    (0x3fffd6, 22), (0x7fffda, 23), (0x7fffdb, 23), (0x7fffdc, 23), (0x7fffdd, 23), (0x7fffde, 23), (0xffffeb, 24), (0x7fffdf, 23),
+    (0xffffec, 24), (0xffffed, 24), (0x3fffd7, 22), (0x7fffe0, 23), (0xffffee, 24), (0x7fffe1, 23), (0x7fffe2, 23), (0x7fffe3, 23),
+    (0x7fffe4, 23), (0x1fffdc, 21), (0x3fffd8, 22), (0x7fffe5, 23), (0x3fffd9, 22), (0x7fffe6, 23), (0x7fffe7, 23), (0xffffef, 24),
+    (0x3fffda, 22), (0x1fffdd, 21), (0xfffe9, 20), (0x3fffdb, 22), (0x3fffdc, 22), (0x7fffe8, 23), (0x7fffe9, 23), (0x1fffde, 21),
+    (0x7fffea, 23), (0x3fffdd, 22), (0x3fffde, 22), (0xfffff0, 24), (0x1fffdf, 21), (0x3fffdf, 22), (0x7fffeb, 23), (0x7fffec, 23),
+    (0x1fffe0, 21), (0x1fffe1, 21), (0x3fffe0, 22), (0x1fffe2, 21), (0x7fffed, 23), (0x3fffe1, 22), (0x7fffee, 23), (0x7fffef, 23),
+    (0xfffea, 20), (0x3fffe2, 22), (0x3fffe3, 22), (0x3fffe4, 22), (0x7ffff0, 23), (0x3fffe5, 22), (0x3fffe6, 22), (0x7ffff1, 23),
+    (0x3ffffe0, 26), (0x3ffffe1, 26), (0xfffeb, 20), (0x7fff1, 19), (0x3fffe7, 22), (0x7ffff2, 23), (0x3fffe8, 22), (0x1ffffec, 25),
+    (0x3ffffe2, 26), (0x3ffffe3, 26), (0x3ffffe4, 26), (0x7ffffde, 27), (0x7ffffdf, 27), (0x3ffffe5, 26), (0xfffff1, 24), (0x1ffffed, 25),
+    (0x7fff2, 19), (0x1fffe3, 21), (0x3ffffe6, 26), (0x7ffffe0, 27), (0x7ffffe1, 27), (0x3ffffe7, 26), (0x7ffffe2, 27), (0xfffff2, 24),
+    (0x1fffe4, 21), (0x1fffe5, 21), (0x3ffffe8, 26), (0x3ffffe9, 26), (0xffffffd, 28), (0x7ffffe3, 27), (0x7ffffe4, 27), (0x7ffffe5, 27),
+    (0xfffec, 20), (0xfffff3, 24), (0xfffed, 20), (0x1fffe6, 21), (0x3fffe9, 22), (0x1fffe7, 21), (0x1fffe8, 21), (0x7ffff3, 23),
+    (0x3fffea, 22), (0x3fffeb, 22), (0x1ffffee, 25), (0x1ffffef, 25), (0xfffff4, 24), (0xfffff5, 24), (0x3ffffea, 26), (0x7ffff4, 23),
+    (0x3ffffeb, 26), (0x7ffffe6, 27), (0x3ffffec, 26), (0x3ffffed, 26), (0x7ffffe7, 27), (0x7ffffe8, 27), (0x7ffffe9, 27), (0x7ffffea, 27),
+    (0x7ffffeb, 27), (0xffffffe, 28), (0x7ffffec, 27), (0x7ffffed, 27), (0x7ffffee, 27), (0x7ffffef, 27), (0x7fffff0, 27), (0x3ffffee, 26),
+];
+
+// Each dynamic table entry counts its name and value plus this overhead (RFC 7541 section 4.1)
+const ENTRY_OVERHEAD: usize = 32;
+
+/// Header block encoder. Headers are sent as literals that never enter the peer's dynamic table,
+/// so the encoder keeps no state; fully matching static entries are sent as an index.
+pub fn encode(headers: &[(&str, &str)]) -> Vec<u8> {
+    let mut block = Vec::new();
+    for &(name, value) in headers {
+        if let Some(index) = STATIC_TABLE.iter().position(|&entry| entry == (name, value)) {
+            encode_integer(&mut block, 0x80, 7, index + 1);
+            continue;
+        }
+        // Literal header field without indexing, with an indexed name when the table has it
+        match STATIC_TABLE.iter().position(|&(entry, _)| entry == name) {
+            Some(index) => encode_integer(&mut block, 0x00, 4, index + 1),
+            None => {
+                block.push(0x00);
+                encode_string(&mut block, name);
+            }
+        }
+        encode_string(&mut block, value);
+    }
+    block
+}
+
+fn encode_integer(block: &mut Vec<u8>, flags: u8, prefix_bits: u32, mut value: usize) {
+    let max_prefix = (1 << prefix_bits) - 1;
+    if value < max_prefix {
+        block.push(flags | value as u8);
+        return;
+    }
+    block.push(flags | max_prefix as u8);
+    value -= max_prefix;
+    while value >= 0x80 {
+        block.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    block.push(value as u8);
+}
+
+fn encode_string(block: &mut Vec<u8>, text: &str) {
+    encode_integer(block, 0x00, 7, text.len());
+    block.extend_from_slice(text.as_bytes());
+}
+
+/// Header block decoder; one per connection, fed every block in the order it arrived
+#[derive(Debug)]
+pub struct Decoder {
+    // Newest entry first, as dynamic indexes count from the most recent insertion
+    table: VecDeque<(String, String)>,
+    size: usize,
+    max_size: usize,
+}
+
+impl Default for Decoder {
+    fn default() -> Self {
+        // SETTINGS_HEADER_TABLE_SIZE starts at 4096 bytes and the client never changes it
+        Decoder { table: VecDeque::new(), size: 0, max_size: 4096 }
+    }
+}
+
+impl Decoder {
+    pub fn new() -> Decoder {
+        Decoder::default()
+    }
+
+    pub fn decode(&mut self, mut block: &[u8]) -> Result<HeaderList, Box<dyn Error>> {
+        let mut headers = Vec::new();
+
+        while let Some(&first) = block.first() {
+            if first & 0x80 != 0 {
+                // Indexed header field
+                let index = decode_integer(&mut block, 7)?;
+                headers.push(self.entry(index)?);
+            } else if first & 0xc0 == 0x40 {
+                // Literal with incremental indexing
+                let header = self.literal(&mut block, 6)?;
+                self.insert(header.clone());
+                headers.push(header);
+            } else if first & 0xe0 == 0x20 {
+                // Dynamic table size update
+                let size = decode_integer(&mut block, 5)?;
+                if size > 4096 {
+                    return Err(format!("HPACK table size {} exceeds the advertised 4096", size).into());
+                }
+                self.max_size = size;
+                self.evict();
+            } else {
+                // Literal without indexing or never indexed
+                headers.push(self.literal(&mut block, 4)?);
+            }
+        }
+
+        Ok(headers)
+    }
+
+    fn entry(&self, index: usize) -> Result<(String, String), Box<dyn Error>> {
+        match index {
+            0 => Err("HPACK index 0".into()),
+            1..=61 => {
+                let (name, value) = STATIC_TABLE[index - 1];
+                Ok((name.to_string(), value.to_string()))
+            }
+            _ => self.table.get(index - 62).cloned().ok_or_else(|| format!("HPACK index {} out of range", index).into()),
+        }
+    }
+
+    fn literal(&self, block: &mut &[u8], prefix_bits: u32) -> Result<(String, String), Box<dyn Error>> {
+        let name = match decode_integer(block, prefix_bits)? {
+            0 => decode_string(block)?,
+            index => self.entry(index)?.0,
+        };
+        Ok((name, decode_string(block)?))
+    }
+
+    fn insert(&mut self, header: (String, String)) {
+        self.size += header.0.len() + header.1.len() + ENTRY_OVERHEAD;
+        self.table.push_front(header);
+        // An entry larger than the whole table empties it
+        self.evict();
+    }
+
+    fn evict(&mut self) {
+        while self.size > self.max_size {
+            match self.table.pop_back() {
+                Some((name, value)) => self.size -= name.len() + value.len() + ENTRY_OVERHEAD,
+                None => break,
+            }
+        }
+    }
+}
+
+fn decode_integer(block: &mut &[u8], prefix_bits: u32) -> Result<usize, Box<dyn Error>> {
+    let (&first, mut rest) = block.split_first().ok_or("Truncated HPACK integer")?;
+    let max_prefix = (1usize << prefix_bits) - 1;
+    let mut value = first as usize & max_prefix;
+
+    if value == max_prefix {
+        let mut shift = 0;
+        loop {
+            let (&byte, tail) = rest.split_first().ok_or("Truncated HPACK integer")?;
+            rest = tail;
+            if shift > 28 {
+                return Err("HPACK integer too large".into());
+            }
+            value += ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                break;
+            }
+        }
+    }
+
+    *block = rest;
+    Ok(value)
+}
+
+fn decode_string(block: &mut &[u8]) -> Result<String, Box<dyn Error>> {
+    let huffman = block.first().is_some_and(|first| first & 0x80 != 0);
+    let length = decode_integer(block, 7)?;
+    if length > block.len() {
+        return Err("Truncated HPACK string".into());
+    }
+    let (bytes, rest) = block.split_at(length);
+    *block = rest;
+
+    let bytes = if huffman { huffman_decode(bytes)? } else { bytes.to_vec() };
+    String::from_utf8(bytes).map_err(|_| "HPACK string is not valid UTF-8".into())
+}
+
+fn huffman_decode(input: &[u8]) -> Result<Vec<u8>, Box<dyn Error>> {
+    static CODES: OnceLock<HashMap<(u8, u32), u8>> = OnceLock::new();
+    let codes = CODES.get_or_init(|| {
+        HUFFMAN_CODES.iter().enumerate().map(|(byte, &(code, bits))| ((bits, code), byte as u8)).collect()
+    });
+
+    let mut output = Vec::with_capacity(input.len() * 8 / 5);
+    let (mut code, mut bits) = (0u32, 0u8);
+    for byte in input {
+        for shift in (0..8).rev() {
+            code = (code << 1) | ((byte >> shift) & 1) as u32;
+            bits += 1;
+            if let Some(&symbol) = codes.get(&(bits, code)) {
+                output.push(symbol);
+                code = 0;
+                bits = 0;
+            } else if bits >= 30 {
+                return Err("Invalid Huffman code in HPACK string".into());
+            }
+        }
+    }
+
+    // What is left must be padding: fewer than eight bits of the EOS code's leading ones
+    if bits > 7 || code != (1 << bits) - 1 {
+        return Err("Invalid Huffman padding in HPACK string".into());
+    }
+    Ok(output)
+}
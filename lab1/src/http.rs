@@ -0,0 +1,631 @@
+//! Minimal HTTP/1.1 and HTTP/2 client over plain TCP or TLS, with proxy and cookie support.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::{Duration, Instant};
+use chrono::Utc;
+use encoding_rs::{Encoding, UTF_8};
+use native_tls::TlsConnector;
+use url::Url;
+use crate::cookies::CookieJar;
+use crate::har::{HarArchive, HarEntry, Timings, Trace};
+use crate::http2::{self, StreamRefused};
+use crate::proxy::{self, ProxyConfig, ProxyKind};
+use crate::tls::{PeerCertificate, TlsOptions};
+
+const USER_AGENT: &str = "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36";
+const ACCEPT: &str = "text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8";
+
+/// Limits applied while reading a single response
+#[derive(Debug, Clone)]
+pub struct Limits {
+    pub max_header_bytes: usize,
+    pub max_body_bytes: usize,
+    /// Overall time budget for connecting, sending and reading the whole response
+    pub timeout: Duration,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_header_bytes: 64 * 1024,
+            max_body_bytes: 10 * 1024 * 1024,
+            timeout: Duration::from_secs(20),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub status: u32,
+    /// Raw header lines, status line first
+    pub headers: Vec<String>,
+    pub body: Vec<u8>,
+}
+
+impl Response {
+    /// Look up a header value by name, ignoring case
+    pub fn header(&self, name: &str) -> Option<&str> {
+        header_value(&self.headers, name)
+    }
+
+    /// All values of a header that may repeat, such as `Set-Cookie`
+    pub fn header_values<'a>(&'a self, name: &'a str) -> impl Iterator<Item = &'a str> + 'a {
+        self.headers.iter().skip(1).filter_map(move |line| {
+            let (key, value) = line.split_once(':')?;
+            key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+        })
+    }
+
+    /// Media type from `Content-Type`, lowercased and without parameters
+    pub fn content_type(&self) -> Option<String> {
+        self.header("content-type")
+            .map(|value| value.split(';').next().unwrap_or("").trim().to_lowercase())
+    }
+
+    /// Decode the body as text using the declared or sniffed charset
+    pub fn text(&self) -> String {
+        let encoding = self.header("content-type")
+            .and_then(charset_from_content_type)
+            .or_else(|| sniff_meta_charset(&self.body))
+            .unwrap_or(UTF_8);
+        // `decode` also honours a byte order mark if one is present
+        let (text, _, _) = encoding.decode(&self.body);
+        text.into_owned()
+    }
+}
+
+// Trait to abstract over different types of streams
+trait StreamIO: Read + Write {
+    // The underlying socket, used to apply the remaining time budget to every read
+    fn socket(&self) -> &TcpStream;
+
+    fn write_request(&mut self, request: &str) -> Result<(), Box<dyn Error>> {
+        self.write_all(request.as_bytes())?;
+        self.flush()?;
+        Ok(())
+    }
+}
+
+// Implementation for TLS streams
+impl StreamIO for native_tls::TlsStream<TcpStream> {
+    fn socket(&self) -> &TcpStream {
+        self.get_ref()
+    }
+}
+
+// Implementation for regular TCP streams
+impl StreamIO for TcpStream {
+    fn socket(&self) -> &TcpStream {
+        self
+    }
+}
+
+// Reader that fails once the overall deadline has passed, however slowly the peer drips data
+struct DeadlineReader<S: StreamIO> {
+    stream: S,
+    deadline: Instant,
+}
+
+impl<S: StreamIO> Read for DeadlineReader<S> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let remaining = remaining_until(self.deadline)?;
+        self.stream.socket().set_read_timeout(Some(remaining))?;
+        self.stream.read(buf).map_err(|e| match e.kind() {
+            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut => deadline_exceeded(),
+            _ => e,
+        })
+    }
+}
+
+fn remaining_until(deadline: Instant) -> io::Result<Duration> {
+    deadline
+        .checked_duration_since(Instant::now())
+        .filter(|remaining| !remaining.is_zero())
+        .ok_or_else(deadline_exceeded)
+}
+
+pub(crate) fn deadline_exceeded() -> io::Error {
+    io::Error::new(io::ErrorKind::TimedOut, "Response deadline exceeded")
+}
+
+/// Connection settings shared by every request a scrape makes
+#[derive(Debug, Clone)]
+pub struct Client {
+    pub proxy: ProxyConfig,
+    /// Shared so cookies set on one request (or redirect hop) are sent on the next
+    pub cookies: Arc<Mutex<CookieJar>>,
+    /// Built once from `TlsOptions` and reused for every HTTPS connection
+    pub tls: TlsConnector,
+    pub traffic: Traffic,
+    /// HTTPS connections kept per origin (`host:port`)
+    pool: Arc<Pool>,
+}
+
+// The lock is only held to look at or update the map, never while connecting
+#[derive(Debug, Default)]
+struct Pool {
+    origins: Mutex<HashMap<String, Pooled>>,
+    /// Signalled whenever an origin stops being `Connecting`
+    settled: Condvar,
+}
+
+impl Pool {
+    // Record how an origin was reached, or forget it when `None`, and wake requests waiting for it
+    fn settle(&self, origin: String, pooled: Option<Pooled>) {
+        let mut origins = self.origins.lock().unwrap();
+        match pooled {
+            Some(pooled) => origins.insert(origin, pooled),
+            None => origins.remove(&origin),
+        };
+        self.settled.notify_all();
+    }
+}
+
+// How an origin was reached over HTTPS
+#[derive(Debug)]
+enum Pooled {
+    /// Shared by every request to the origin until the server closes it
+    Http2(Arc<http2::Connection>),
+    /// The server chose HTTP/1.1, so each request opens a connection of its own
+    Http1,
+    /// Another request is opening the first connection; wait for it and share the result
+    Connecting,
+}
+
+/// Where `Client::fetch` gets its responses
+#[derive(Debug, Clone, Default)]
+pub enum Traffic {
+    #[default]
+    Live,
+    /// From the network, adding every exchange to the archive
+    Record(Arc<Mutex<HarArchive>>),
+    /// From the archive alone; the network is never used
+    Replay(Arc<HarArchive>),
+}
+
+impl Client {
+    /// Client with its own cookie jar and a TLS connector built from `tls`
+    pub fn new(proxy: ProxyConfig, cookies: CookieJar, tls: &TlsOptions) -> Result<Client, Box<dyn Error>> {
+        Ok(Client {
+            proxy,
+            cookies: Arc::new(Mutex::new(cookies)),
+            tls: tls.connector()?,
+            traffic: Traffic::Live,
+            pool: Arc::default(),
+        })
+    }
+
+    /// Perform a GET request and read the response within the given limits
+    pub fn fetch(&self, url: &str, limits: &Limits) -> Result<Response, Box<dyn Error>> {
+        let response = match &self.traffic {
+            Traffic::Live => self.fetch_live(url, limits, &mut Trace::default())?,
+            Traffic::Record(archive) => {
+                let started = Utc::now();
+                let mut trace = Trace::default();
+                let result = self.fetch_live(url, limits, &mut trace);
+                archive.lock().unwrap().record(HarEntry {
+                    started,
+                    method: "GET".to_string(),
+                    url: url.to_string(),
+                    request_headers: trace.request_headers,
+                    response: result.as_ref().map(Response::clone).map_err(|e| e.to_string()),
+                    timings: trace.timings,
+                });
+                result?
+            }
+            Traffic::Replay(archive) => archive.replay(url, limits)?,
+        };
+
+        self.cookies.lock().unwrap().store(&Url::parse(url)?, response.header_values("set-cookie"));
+        Ok(response)
+    }
+
+    fn fetch_live(&self, url: &str, limits: &Limits, trace: &mut Trace) -> Result<Response, Box<dyn Error>> {
+        let deadline = Instant::now() + limits.timeout;
+        let parsed_url = Url::parse(url)?;
+        let host = parsed_url.host_str().ok_or("Invalid host")?;
+        let https = parsed_url.scheme() == "https";
+        let proxy = self.proxy.proxy_for(&parsed_url);
+
+        // Plain HTTP through an HTTP proxy is sent to the proxy in absolute form
+        let forward_to_proxy = !https && proxy.is_some_and(|p| matches!(p.kind, ProxyKind::Http));
+        let target = if forward_to_proxy {
+            let mut absolute = parsed_url.clone();
+            absolute.set_fragment(None);
+            absolute.to_string()
+        } else {
+            let path = match parsed_url.query() {
+                Some(query) => format!("{}?{}", parsed_url.path(), query),
+                None => parsed_url.path().to_string(),
+            };
+            if path.is_empty() { "/".to_string() } else { path }
+        };
+
+        // `port()` leaves out the scheme's default port, which Host leaves out too
+        let authority = match parsed_url.port() {
+            Some(port) => format!("{}:{}", host, port),
+            None => host.to_string(),
+        };
+        let mut fields = vec![
+            ("Host", authority),
+            ("User-Agent", USER_AGENT.to_string()),
+            ("Accept", ACCEPT.to_string()),
+            ("Connection", "close".to_string()),
+        ];
+        if let Some(authorization) = proxy.filter(|_| forward_to_proxy).and_then(|p| p.authorization()) {
+            fields.push(("Proxy-Authorization", authorization));
+        }
+        if let Some(cookie) = self.cookies.lock().unwrap().cookie_header(&parsed_url) {
+            fields.push(("Cookie", cookie));
+        }
+        // Proxy credentials are left out of recordings, which get shared when debugging
+        trace.request_headers = fields.iter()
+            .filter(|(name, _)| !name.eq_ignore_ascii_case("proxy-authorization"))
+            .map(|(name, value)| (name.to_string(), value.clone()))
+            .collect();
+
+        let request = Request { url: &parsed_url, target: &target, fields: &fields, limits, deadline };
+        if https {
+            return self.fetch_https(&request, trace);
+        }
+        let connecting = Instant::now();
+        let tcp_stream = self.open(&parsed_url, limits, deadline)?;
+        trace.timings.connect = elapsed_ms(connecting);
+        exchange(tcp_stream, &request.http1(), limits, deadline, &mut trace.timings)
+    }
+
+    // Send the request over the origin's HTTP/2 connection, opening one first if there is none
+    // yet. A connection the server closed in the meantime is replaced once.
+    fn fetch_https(&self, request: &Request, trace: &mut Trace) -> Result<Response, Box<dyn Error>> {
+        let origin = request.origin()?;
+        let mut retried = false;
+        let mut origins = self.pool.origins.lock().unwrap();
+        loop {
+            match origins.get(&origin) {
+                Some(Pooled::Http2(connection)) if connection.is_open() => {
+                    let connection = Arc::clone(connection);
+                    drop(origins);
+                    match connection.request(&request.http2(), request.limits, request.deadline, &mut trace.timings) {
+                        Err(e) if e.is::<StreamRefused>() && !retried => retried = true,
+                        result => return result,
+                    }
+                    origins = self.pool.origins.lock().unwrap();
+                }
+                Some(Pooled::Http1) => {
+                    drop(origins);
+                    return self.connect_https(request, trace, false);
+                }
+                // Concurrent requests to the origin wait for the connection being opened and share it
+                Some(Pooled::Connecting) => origins = self.pool.settled.wait(origins).unwrap(),
+                _ => {
+                    origins.insert(origin, Pooled::Connecting);
+                    drop(origins);
+                    return self.connect_https(request, trace, true);
+                }
+            }
+        }
+    }
+
+    // Open a TLS connection and speak whichever protocol the server picked during the handshake.
+    // `claimed` means the origin is marked `Connecting` in the pool and must be settled either way.
+    fn connect_https(&self, request: &Request, trace: &mut Trace, claimed: bool) -> Result<Response, Box<dyn Error>> {
+        let origin = request.origin()?;
+        let tls_stream = match self.handshake(request, trace) {
+            Ok(tls_stream) => tls_stream,
+            Err(e) => {
+                if claimed {
+                    self.pool.settle(origin, None);
+                }
+                return Err(e);
+            }
+        };
+
+        let http2 = tls_stream.negotiated_alpn().ok().flatten().is_some_and(|protocol| protocol == http2::ALPN.as_bytes());
+        if !http2 {
+            self.pool.settle(origin, Some(Pooled::Http1));
+            return exchange(tls_stream, &request.http1(), request.limits, request.deadline, &mut trace.timings);
+        }
+
+        let connection = match http2::Connection::start(tls_stream) {
+            Ok(connection) => Arc::new(connection),
+            Err(e) => {
+                self.pool.settle(origin, None);
+                return Err(e);
+            }
+        };
+        self.pool.settle(origin, Some(Pooled::Http2(Arc::clone(&connection))));
+        connection.request(&request.http2(), request.limits, request.deadline, &mut trace.timings)
+    }
+
+    fn handshake(&self, request: &Request, trace: &mut Trace) -> Result<native_tls::TlsStream<TcpStream>, Box<dyn Error>> {
+        let host = request.url.host_str().ok_or("Invalid host")?;
+        let connecting = Instant::now();
+        let tcp_stream = self.open(request.url, request.limits, request.deadline)?;
+        trace.timings.connect = elapsed_ms(connecting);
+        let handshake = Instant::now();
+        let tls_stream = self.tls.connect(host, tcp_stream)?;
+        trace.timings.ssl = Some(elapsed_ms(handshake));
+        Ok(tls_stream)
+    }
+
+    /// Complete a TLS handshake with the server behind `url` and describe its certificate
+    pub fn peer_certificate(&self, url: &str) -> Result<PeerCertificate, Box<dyn Error>> {
+        let limits = Limits::default();
+        let deadline = Instant::now() + limits.timeout;
+        let parsed_url = Url::parse(url)?;
+        if parsed_url.scheme() != "https" {
+            return Err(format!("{} is not an HTTPS URL", url).into());
+        }
+        let host = parsed_url.host_str().ok_or("Invalid host")?;
+
+        let tcp_stream = self.open(&parsed_url, &limits, deadline)?;
+        let tls_stream = self.tls.connect(host, tcp_stream)?;
+        let certificate = tls_stream.peer_certificate()?.ok_or("Server sent no certificate")?;
+        PeerCertificate::from_der(&certificate.to_der()?)
+    }
+
+    // Open a TCP connection to the target, directly or through the configured proxy
+    fn open(&self, url: &Url, limits: &Limits, deadline: Instant) -> Result<TcpStream, Box<dyn Error>> {
+        let host = url.host_str().ok_or("Invalid host")?;
+        let port = url.port_or_known_default().ok_or("Unknown port")?;
+
+        let Some(proxy) = self.proxy.proxy_for(url) else {
+            return connect(host, port, deadline);
+        };
+        let mut stream = connect(&proxy.host, proxy.port, deadline)?;
+        match proxy.kind {
+            ProxyKind::Http if url.scheme() == "https" => {
+                proxy::http_connect(&mut stream, proxy, host, port, limits.max_header_bytes)?
+            }
+            ProxyKind::Http => {}
+            ProxyKind::Socks5 { .. } => proxy::socks5_connect(&mut stream, proxy, host, port)?,
+        }
+        Ok(stream)
+    }
+}
+
+// A GET about to be sent, in a form either protocol can write out
+struct Request<'a> {
+    url: &'a Url,
+    // Path and query, or the absolute URL when going through an HTTP proxy
+    target: &'a str,
+    // Header fields in HTTP/1.1 form, Host first
+    fields: &'a [(&'static str, String)],
+    limits: &'a Limits,
+    deadline: Instant,
+}
+
+impl Request<'_> {
+    fn origin(&self) -> Result<String, Box<dyn Error>> {
+        let host = self.url.host_str().ok_or("Invalid host")?;
+        Ok(format!("{}:{}", host, self.url.port_or_known_default().ok_or("Unknown port")?))
+    }
+
+    fn http1(&self) -> String {
+        let mut request = format!("GET {} HTTP/1.1\r\n", self.target);
+        for (name, value) in self.fields {
+            request.push_str(&format!("{}: {}\r\n", name, value));
+        }
+        request.push_str("\r\n");
+        request
+    }
+
+    // HTTP/2 replaces Host with `:authority`, has no Connection header and wants lowercase names
+    fn http2(&self) -> Vec<(String, String)> {
+        let authority = self.fields.iter().find(|(name, _)| name.eq_ignore_ascii_case("host")).map(|(_, value)| value);
+        let mut fields = vec![
+            (":method".to_string(), "GET".to_string()),
+            (":scheme".to_string(), self.url.scheme().to_string()),
+            (":authority".to_string(), authority.cloned().unwrap_or_default()),
+            (":path".to_string(), self.target.to_string()),
+        ];
+        fields.extend(self.fields.iter()
+            .map(|(name, value)| (name.to_ascii_lowercase(), value.clone()))
+            .filter(|(name, _)| name != "host" && name != "connection"));
+        fields
+    }
+}
+
+fn connect(host: &str, port: u16, deadline: Instant) -> Result<TcpStream, Box<dyn Error>> {
+    let mut last_error: Option<io::Error> = None;
+
+    for addr in (host, port).to_socket_addrs()? {
+        match TcpStream::connect_timeout(&addr, remaining_until(deadline)?) {
+            Ok(stream) => {
+                // Also covers the TLS handshake, which reads before `DeadlineReader` takes over
+                stream.set_read_timeout(Some(remaining_until(deadline)?))?;
+                stream.set_write_timeout(Some(remaining_until(deadline)?))?;
+                return Ok(stream);
+            }
+            Err(e) => last_error = Some(e),
+        }
+    }
+
+    Err(match last_error {
+        Some(e) => e.into(),
+        None => format!("No addresses found for {}", host).into(),
+    })
+}
+
+fn exchange<S: StreamIO>(
+    mut stream: S,
+    request: &str,
+    limits: &Limits,
+    deadline: Instant,
+    timings: &mut Timings,
+) -> Result<Response, Box<dyn Error>> {
+    let sending = Instant::now();
+    stream.write_request(request)?;
+    timings.send = elapsed_ms(sending);
+
+    let waiting = Instant::now();
+    let mut reader = BufReader::new(DeadlineReader { stream, deadline });
+    let headers = read_headers(&mut reader, limits)?;
+    timings.wait = elapsed_ms(waiting);
+
+    let receiving = Instant::now();
+    let response = read_body(&mut reader, headers, limits)?;
+    timings.receive = elapsed_ms(receiving);
+    Ok(response)
+}
+
+pub(crate) fn elapsed_ms(since: Instant) -> f64 {
+    since.elapsed().as_secs_f64() * 1000.0
+}
+
+fn read_headers<R: BufRead>(reader: &mut R, limits: &Limits) -> Result<Vec<String>, Box<dyn Error>> {
+    // Read the status line and headers up to the blank line
+    let mut headers = Vec::new();
+    let mut header_bytes = 0;
+    loop {
+        let line = read_line(reader, limits.max_header_bytes.saturating_sub(header_bytes))?;
+        header_bytes += line.len() + 2;
+        if line.is_empty() {
+            break;
+        }
+        headers.push(line);
+    }
+    Ok(headers)
+}
+
+fn read_body<R: BufRead>(reader: &mut R, headers: Vec<String>, limits: &Limits) -> Result<Response, Box<dyn Error>> {
+    // Parse status code
+    let status_line = headers.first().ok_or("No status line")?;
+    let status = status_line
+        .split_whitespace()
+        .nth(1)
+        .ok_or("No status code")?
+        .parse::<u32>()?;
+
+    let chunked = header_value(&headers, "transfer-encoding")
+        .is_some_and(|value| value.to_lowercase().contains("chunked"));
+    let content_length = header_value(&headers, "content-length")
+        .map(|value| value.parse::<usize>())
+        .transpose()?;
+
+    let body = if chunked {
+        read_chunked_body(reader, limits)?
+    } else if let Some(length) = content_length {
+        if length > limits.max_body_bytes {
+            return Err(body_too_large(limits));
+        }
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body)?;
+        body
+    } else {
+        // No framing information: the body runs until the server closes the connection
+        read_capped(reader, limits.max_body_bytes)?
+    };
+
+    Ok(Response { status, headers, body })
+}
+
+// Reassemble a body sent with `Transfer-Encoding: chunked`
+fn read_chunked_body<R: BufRead>(reader: &mut R, limits: &Limits) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut body = Vec::new();
+
+    loop {
+        let size_line = read_line(reader, limits.max_header_bytes)?;
+        let size_hex = size_line.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size_hex, 16)?;
+
+        if size == 0 {
+            // Skip any trailer headers
+            while !read_line(reader, limits.max_header_bytes)?.is_empty() {}
+            return Ok(body);
+        }
+        if size > limits.max_body_bytes.saturating_sub(body.len()) {
+            return Err(body_too_large(limits));
+        }
+
+        let start = body.len();
+        body.resize(start + size, 0);
+        reader.read_exact(&mut body[start..])?;
+        if !read_line(reader, 0)?.is_empty() {
+            return Err("Missing chunk terminator".into());
+        }
+    }
+}
+
+fn read_capped<R: Read>(reader: &mut R, max_bytes: usize) -> Result<Vec<u8>, Box<dyn Error>> {
+    let mut body = Vec::new();
+    reader.take(max_bytes as u64 + 1).read_to_end(&mut body)?;
+    if body.len() > max_bytes {
+        return Err(format!("Response body exceeds {} bytes", max_bytes).into());
+    }
+    Ok(body)
+}
+
+// Read one CRLF-terminated line of at most `max_bytes` bytes, without the terminator
+fn read_line<R: BufRead>(reader: &mut R, max_bytes: usize) -> Result<String, Box<dyn Error>> {
+    let mut line = Vec::new();
+    reader.take(max_bytes as u64 + 2).read_until(b'\n', &mut line)?;
+
+    if line.last() != Some(&b'\n') {
+        return Err(if line.len() > max_bytes {
+            "Response header too large".into()
+        } else {
+            "Connection closed before end of headers".into()
+        });
+    }
+    line.pop();
+    if line.last() == Some(&b'\r') {
+        line.pop();
+    }
+
+    Ok(String::from_utf8_lossy(&line).into_owned())
+}
+
+pub(crate) fn body_too_large(limits: &Limits) -> Box<dyn Error> {
+    format!("Response body exceeds {} bytes", limits.max_body_bytes).into()
+}
+
+/// Look up a header value by name, ignoring case
+pub fn header_value<'a>(headers: &'a [String], name: &str) -> Option<&'a str> {
+    headers.iter().skip(1).find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        key.trim().eq_ignore_ascii_case(name).then(|| value.trim())
+    })
+}
+
+// Extract the encoding named by a `charset=` parameter
+fn charset_from_content_type(content_type: &str) -> Option<&'static Encoding> {
+    content_type
+        .split(';')
+        .skip(1)
+        .filter_map(|param| param.split_once('='))
+        .find(|(key, _)| key.trim().eq_ignore_ascii_case("charset"))
+        .and_then(|(_, value)| Encoding::for_label(value.trim().trim_matches(['"', '\'']).as_bytes()))
+}
+
+// Look for `<meta charset>` or `<meta http-equiv content>` near the start of an HTML document
+fn sniff_meta_charset(body: &[u8]) -> Option<&'static Encoding> {
+    // Charset declarations are ASCII-compatible, so a lossy view of the prefix is enough
+    let prefix = String::from_utf8_lossy(&body[..body.len().min(1024)]).to_lowercase();
+    let mut rest = prefix.as_str();
+
+    while let Some(start) = rest.find("<meta") {
+        rest = &rest[start + 5..];
+        let tag = &rest[..rest.find('>').unwrap_or(rest.len())];
+        if let Some(position) = tag.find("charset") {
+            let value = tag[position + 7..].trim_start();
+            if let Some(value) = value.strip_prefix('=') {
+                let label: String = value
+                    .trim_start()
+                    .trim_start_matches(['"', '\''])
+                    .chars()
+                    .take_while(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | ':' | '.'))
+                    .collect();
+                if let Some(encoding) = Encoding::for_label(label.as_bytes()) {
+                    return Some(encoding);
+                }
+            }
+        }
+    }
+
+    None
+}
@@ -0,0 +1,502 @@
+//! HTTP/2 client connections (RFC 9113) that carry concurrent requests over one TLS stream.
+
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::io::{self, Read, Write};
+use std::mem;
+use std::net::TcpStream;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use native_tls::TlsStream;
+use crate::har::Timings;
+use crate::hpack::{self, Decoder};
+use crate::http::{body_too_large, deadline_exceeded, elapsed_ms, Limits, Response};
+
+/// ALPN protocol id a server picks to speak HTTP/2
+pub const ALPN: &str = "h2";
+
+const PREFACE: &[u8] = b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n";
+
+const DATA: u8 = 0x0;
+const HEADERS: u8 = 0x1;
+const RST_STREAM: u8 = 0x3;
+const SETTINGS: u8 = 0x4;
+const PUSH_PROMISE: u8 = 0x5;
+const PING: u8 = 0x6;
+const GOAWAY: u8 = 0x7;
+const WINDOW_UPDATE: u8 = 0x8;
+const CONTINUATION: u8 = 0x9;
+
+const END_STREAM: u8 = 0x1;
+const ACK: u8 = 0x1;
+const END_HEADERS: u8 = 0x4;
+const PADDED: u8 = 0x8;
+const PRIORITY: u8 = 0x20;
+
+const SETTINGS_ENABLE_PUSH: u16 = 0x2;
+const SETTINGS_INITIAL_WINDOW_SIZE: u16 = 0x4;
+const SETTINGS_MAX_FRAME_SIZE: u16 = 0x5;
+
+const NO_ERROR: u32 = 0x0;
+const REFUSED_STREAM: u32 = 0x7;
+const CANCEL: u32 = 0x8;
+
+// Largest frame either side may send until told otherwise; the client never raises its own
+const DEFAULT_MAX_FRAME_SIZE: usize = 16_384;
+// Receive window of every stream and of the connection as a whole, topped up as data arrives
+const WINDOW_SIZE: u32 = 1024 * 1024;
+const DEFAULT_WINDOW_SIZE: u32 = 65_535;
+
+// A TLS stream cannot be read and written at the same time, so the connection's thread reads
+// with this timeout and writes queued frames in between. It bounds the latency of new requests.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+// How long an idle connection sleeps before checking for pings or a GOAWAY
+const IDLE_INTERVAL: Duration = Duration::from_secs(1);
+const WRITE_TIMEOUT: Duration = Duration::from_secs(20);
+
+/// The server stopped taking requests on this connection before it processed the stream,
+/// so the request can safely be repeated on a new connection
+#[derive(Debug)]
+pub struct StreamRefused(String);
+
+impl fmt::Display for StreamRefused {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl Error for StreamRefused {}
+
+/// One HTTP/2 connection, shared by every request to the same origin. A background thread owns
+/// the TLS stream; requests queue their frames and wait for the thread to complete their stream.
+#[derive(Debug)]
+pub struct Connection {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug)]
+struct Shared {
+    state: Mutex<State>,
+    // Signalled whenever a stream progresses or frames are queued for the connection's thread
+    changed: Condvar,
+}
+
+#[derive(Debug)]
+struct State {
+    streams: HashMap<u32, Stream>,
+    next_stream_id: u32,
+    // Encoded frames waiting for the connection's thread to write them
+    outgoing: Vec<u8>,
+    // Why the connection takes no new streams: a GOAWAY, a failure or the pool dropping it
+    closed: Option<String>,
+    peer_max_frame_size: usize,
+}
+
+#[derive(Debug)]
+struct Stream {
+    limits: Limits,
+    // Status line and header lines of the final response, once they arrive
+    headers: Option<Vec<String>>,
+    status: u32,
+    body: Vec<u8>,
+    finished: bool,
+    failure: Option<Failure>,
+}
+
+#[derive(Debug)]
+enum Failure {
+    Refused(String),
+    Failed(String),
+}
+
+impl Connection {
+    /// Send the connection preface on a stream that negotiated `h2` and start its thread
+    pub fn start(mut stream: TlsStream<TcpStream>) -> Result<Connection, Box<dyn Error>> {
+        let mut preface = PREFACE.to_vec();
+        let mut settings = Vec::new();
+        for (id, value) in [(SETTINGS_ENABLE_PUSH, 0), (SETTINGS_INITIAL_WINDOW_SIZE, WINDOW_SIZE)] {
+            settings.extend_from_slice(&id.to_be_bytes());
+            settings.extend_from_slice(&value.to_be_bytes());
+        }
+        write_frame(&mut preface, SETTINGS, 0, 0, &settings);
+        // The connection window is not covered by SETTINGS and always starts at the default
+        write_frame(&mut preface, WINDOW_UPDATE, 0, 0, &(WINDOW_SIZE - DEFAULT_WINDOW_SIZE).to_be_bytes());
+        stream.write_all(&preface)?;
+        stream.flush()?;
+        stream.get_ref().set_write_timeout(Some(WRITE_TIMEOUT))?;
+
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                streams: HashMap::new(),
+                next_stream_id: 1,
+                outgoing: Vec::new(),
+                closed: None,
+                peer_max_frame_size: DEFAULT_MAX_FRAME_SIZE,
+            }),
+            changed: Condvar::new(),
+        });
+        let thread_shared = Arc::clone(&shared);
+        thread::Builder::new()
+            .name("http2-connection".to_string())
+            .spawn(move || run(&thread_shared, stream))?;
+        Ok(Connection { shared })
+    }
+
+    /// Whether new requests can still be sent on this connection
+    pub fn is_open(&self) -> bool {
+        self.shared.state.lock().unwrap().closed.is_none()
+    }
+
+    /// Send a GET with the given header fields, pseudo-headers first, and wait for the whole
+    /// response within `deadline`
+    pub fn request(
+        &self,
+        headers: &[(String, String)],
+        limits: &Limits,
+        deadline: Instant,
+        timings: &mut Timings,
+    ) -> Result<Response, Box<dyn Error>> {
+        let sending = Instant::now();
+        let mut state = self.shared.state.lock().unwrap();
+        if let Some(reason) = &state.closed {
+            return Err(Box::new(StreamRefused(reason.clone())));
+        }
+
+        let id = state.next_stream_id;
+        state.next_stream_id += 2;
+        let fields: Vec<(&str, &str)> = headers.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect();
+        let block = hpack::encode(&fields);
+        let max_frame_size = state.peer_max_frame_size;
+        write_header_block(&mut state.outgoing, id, &block, max_frame_size);
+        state.streams.insert(id, Stream {
+            limits: limits.clone(),
+            headers: None,
+            status: 0,
+            body: Vec::new(),
+            finished: false,
+            failure: None,
+        });
+        self.shared.changed.notify_all();
+        timings.send = elapsed_ms(sending);
+
+        let waiting = Instant::now();
+        let mut receiving = None;
+        loop {
+            let stream = state.streams.get_mut(&id).expect("streams are removed by their request");
+            if receiving.is_none() && stream.headers.is_some() {
+                timings.wait = elapsed_ms(waiting);
+                receiving = Some(Instant::now());
+            }
+            if let Some(failure) = stream.failure.take() {
+                state.streams.remove(&id);
+                return Err(match failure {
+                    Failure::Refused(reason) => Box::new(StreamRefused(reason)),
+                    Failure::Failed(reason) => reason.into(),
+                });
+            }
+            if stream.finished {
+                break;
+            }
+
+            let remaining = deadline.checked_duration_since(Instant::now()).filter(|r| !r.is_zero());
+            let Some(remaining) = remaining else {
+                state.streams.remove(&id);
+                write_frame(&mut state.outgoing, RST_STREAM, 0, id, &CANCEL.to_be_bytes());
+                self.shared.changed.notify_all();
+                return Err(deadline_exceeded().into());
+            };
+            state = self.shared.changed.wait_timeout(state, remaining).unwrap().0;
+        }
+
+        let stream = state.streams.remove(&id).expect("finished stream is still registered");
+        timings.receive = receiving.map_or(0.0, elapsed_ms);
+        Ok(Response {
+            status: stream.status,
+            headers: stream.headers.unwrap_or_default(),
+            body: stream.body,
+        })
+    }
+}
+
+impl Drop for Connection {
+    // Let the thread finish the streams in flight, say goodbye and exit
+    fn drop(&mut self) {
+        let mut state = self.shared.state.lock().unwrap();
+        state.closed.get_or_insert_with(|| "Connection is no longer in use".to_string());
+        self.shared.changed.notify_all();
+    }
+}
+
+// Header block that is still arriving in HEADERS and CONTINUATION frames
+struct PendingBlock {
+    stream_id: u32,
+    end_stream: bool,
+    fragment: Vec<u8>,
+}
+
+// The connection's thread: writes queued frames and dispatches what the server sends
+fn run(shared: &Shared, mut stream: TlsStream<TcpStream>) {
+    let mut decoder = Decoder::new();
+    let mut pending: Option<PendingBlock> = None;
+    let mut buffer = Vec::new();
+    let mut chunk = vec![0; 16 * 1024];
+
+    let result = (|| -> Result<(), Box<dyn Error>> {
+        loop {
+            let outgoing = {
+                let mut state = shared.state.lock().unwrap();
+                if state.streams.is_empty() && state.outgoing.is_empty() {
+                    if state.closed.is_some() {
+                        write_frame(&mut state.outgoing, GOAWAY, 0, 0, &[0, 0, 0, 0, 0, 0, 0, 0]);
+                        stream.write_all(&state.outgoing)?;
+                        return Ok(());
+                    }
+                    state = shared.changed.wait_timeout(state, IDLE_INTERVAL).unwrap().0;
+                }
+                mem::take(&mut state.outgoing)
+            };
+            if !outgoing.is_empty() {
+                stream.write_all(&outgoing)?;
+                stream.flush()?;
+            }
+
+            stream.get_ref().set_read_timeout(Some(POLL_INTERVAL))?;
+            match stream.read(&mut chunk) {
+                Ok(0) => return Err("Server closed the HTTP/2 connection".into()),
+                Ok(read) => buffer.extend_from_slice(&chunk[..read]),
+                Err(e) if matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut) => continue,
+                Err(e) => return Err(e.into()),
+            }
+
+            let mut consumed = 0;
+            while let Some(frame) = parse_frame(&buffer[consumed..])? {
+                consumed += 9 + frame.payload.len();
+                let mut state = shared.state.lock().unwrap();
+                handle_frame(&mut state, &mut decoder, &mut pending, frame)?;
+            }
+            buffer.drain(..consumed);
+            shared.changed.notify_all();
+        }
+    })();
+
+    // Streams the server never answered can be retried elsewhere; the rest fail with the reason
+    let mut state = shared.state.lock().unwrap();
+    let reason = match result {
+        Ok(()) => "Connection is no longer in use".to_string(),
+        Err(e) => format!("HTTP/2 connection failed: {}", e),
+    };
+    state.closed.get_or_insert_with(|| reason.clone());
+    for stream in state.streams.values_mut().filter(|stream| !stream.finished) {
+        stream.failure.get_or_insert_with(|| match stream.headers {
+            None => Failure::Refused(reason.clone()),
+            Some(_) => Failure::Failed(reason.clone()),
+        });
+    }
+    shared.changed.notify_all();
+}
+
+struct Frame<'a> {
+    kind: u8,
+    flags: u8,
+    stream_id: u32,
+    payload: &'a [u8],
+}
+
+// Split one complete frame off the front of `buffer`, if it holds one
+fn parse_frame(buffer: &[u8]) -> Result<Option<Frame<'_>>, Box<dyn Error>> {
+    if buffer.len() < 9 {
+        return Ok(None);
+    }
+    let length = u32::from_be_bytes([0, buffer[0], buffer[1], buffer[2]]) as usize;
+    if length > DEFAULT_MAX_FRAME_SIZE {
+        return Err(format!("Frame of {} bytes exceeds the maximum frame size", length).into());
+    }
+    if buffer.len() < 9 + length {
+        return Ok(None);
+    }
+    let stream_id = u32::from_be_bytes([buffer[5], buffer[6], buffer[7], buffer[8]]) & 0x7fff_ffff;
+    Ok(Some(Frame { kind: buffer[3], flags: buffer[4], stream_id, payload: &buffer[9..9 + length] }))
+}
+
+fn handle_frame(
+    state: &mut State,
+    decoder: &mut Decoder,
+    pending: &mut Option<PendingBlock>,
+    frame: Frame,
+) -> Result<(), Box<dyn Error>> {
+    let Frame { kind, flags, stream_id, payload } = frame;
+    // A header block must arrive in one piece, with no other frames in between
+    if pending.is_some() && kind != CONTINUATION {
+        return Err("Header block interrupted by another frame".into());
+    }
+
+    match kind {
+        DATA => {
+            let data = unpad(flags, payload)?;
+            if let Some(stream) = state.streams.get_mut(&stream_id).filter(|s| s.failure.is_none()) {
+                if stream.body.len() + data.len() > stream.limits.max_body_bytes {
+                    stream.failure = Some(Failure::Failed(body_too_large(&stream.limits).to_string()));
+                    write_frame(&mut state.outgoing, RST_STREAM, 0, stream_id, &CANCEL.to_be_bytes());
+                } else {
+                    stream.body.extend_from_slice(data);
+                    if flags & END_STREAM != 0 {
+                        stream.finished = true;
+                    } else if !payload.is_empty() {
+                        let increment = (payload.len() as u32).to_be_bytes();
+                        write_frame(&mut state.outgoing, WINDOW_UPDATE, 0, stream_id, &increment);
+                    }
+                }
+            }
+            // Flow control counts whole frames, padding included, on the connection as well
+            if !payload.is_empty() {
+                write_frame(&mut state.outgoing, WINDOW_UPDATE, 0, 0, &(payload.len() as u32).to_be_bytes());
+            }
+        }
+        HEADERS => {
+            let mut fragment = unpad(flags, payload)?;
+            if flags & PRIORITY != 0 {
+                fragment = fragment.get(5..).ok_or("HEADERS frame too short for its priority")?;
+            }
+            let block = PendingBlock { stream_id, end_stream: flags & END_STREAM != 0, fragment: fragment.to_vec() };
+            if flags & END_HEADERS != 0 {
+                finish_header_block(state, decoder, block)?;
+            } else {
+                *pending = Some(block);
+            }
+        }
+        CONTINUATION => {
+            let mut block = pending.take().ok_or("CONTINUATION without a header block")?;
+            if block.stream_id != stream_id {
+                return Err("CONTINUATION for a different stream".into());
+            }
+            block.fragment.extend_from_slice(payload);
+            if flags & END_HEADERS != 0 {
+                finish_header_block(state, decoder, block)?;
+            } else {
+                *pending = Some(block);
+            }
+        }
+        RST_STREAM => {
+            let code = read_u32(payload)?;
+            if let Some(stream) = state.streams.get_mut(&stream_id) {
+                let reason = format!("Server reset the HTTP/2 stream (error code {})", code);
+                stream.failure.get_or_insert(match code {
+                    REFUSED_STREAM => Failure::Refused(reason),
+                    _ => Failure::Failed(reason),
+                });
+            }
+        }
+        SETTINGS if flags & ACK == 0 => {
+            if !payload.len().is_multiple_of(6) {
+                return Err("Malformed SETTINGS frame".into());
+            }
+            for setting in payload.chunks(6) {
+                let id = u16::from_be_bytes([setting[0], setting[1]]);
+                let value = read_u32(&setting[2..])?;
+                if id == SETTINGS_MAX_FRAME_SIZE {
+                    state.peer_max_frame_size = value as usize;
+                }
+            }
+            write_frame(&mut state.outgoing, SETTINGS, ACK, 0, &[]);
+        }
+        PUSH_PROMISE => return Err("Server pushed a stream although push is disabled".into()),
+        PING if flags & ACK == 0 => write_frame(&mut state.outgoing, PING, ACK, 0, payload),
+        GOAWAY => {
+            let last_stream_id = read_u32(payload)? & 0x7fff_ffff;
+            let code = read_u32(payload.get(4..).unwrap_or_default())?;
+            let reason = match code {
+                NO_ERROR => "Server is closing the HTTP/2 connection".to_string(),
+                _ => format!("Server closed the HTTP/2 connection (error code {})", code),
+            };
+            // Streams up to the last one the server processed still complete
+            for (_, stream) in state.streams.iter_mut().filter(|(id, _)| **id > last_stream_id) {
+                stream.failure.get_or_insert_with(|| Failure::Refused(reason.clone()));
+            }
+            state.closed.get_or_insert(reason);
+        }
+        // PRIORITY, WINDOW_UPDATE (the client sends no bodies), acknowledgements and unknown types
+        _ => {}
+    }
+    Ok(())
+}
+
+fn finish_header_block(state: &mut State, decoder: &mut Decoder, block: PendingBlock) -> Result<(), Box<dyn Error>> {
+    // Every block is decoded, even for cancelled streams, to keep the HPACK table in step
+    let fields = decoder.decode(&block.fragment)?;
+    let Some(stream) = state.streams.get_mut(&block.stream_id).filter(|s| s.failure.is_none()) else {
+        return Ok(());
+    };
+
+    // Informational (1xx) responses come before the final one; trailers after it are ignored
+    if stream.headers.is_none() {
+        let status = fields.iter()
+            .find(|(name, _)| name == ":status")
+            .and_then(|(_, value)| value.parse::<u32>().ok())
+            .ok_or("Response without a valid :status")?;
+        if (100..200).contains(&status) {
+            return Ok(());
+        }
+
+        let size: usize = fields.iter().map(|(name, value)| name.len() + value.len() + 4).sum();
+        if size > stream.limits.max_header_bytes {
+            stream.failure = Some(Failure::Failed("Response header too large".to_string()));
+            write_frame(&mut state.outgoing, RST_STREAM, 0, block.stream_id, &CANCEL.to_be_bytes());
+            return Ok(());
+        }
+
+        let mut lines = vec![format!("HTTP/2 {}", status)];
+        lines.extend(fields.iter()
+            .filter(|(name, _)| !name.starts_with(':'))
+            .map(|(name, value)| format!("{}: {}", name, value)));
+        stream.status = status;
+        stream.headers = Some(lines);
+    }
+    if block.end_stream {
+        stream.finished = true;
+    }
+    Ok(())
+}
+
+// The payload of a DATA or HEADERS frame without its padding
+fn unpad(flags: u8, payload: &[u8]) -> Result<&[u8], Box<dyn Error>> {
+    if flags & PADDED == 0 {
+        return Ok(payload);
+    }
+    let (&padding, rest) = payload.split_first().ok_or("Padded frame without a pad length")?;
+    rest.len()
+        .checked_sub(padding as usize)
+        .map(|length| &rest[..length])
+        .ok_or_else(|| "Frame padding exceeds its payload".into())
+}
+
+fn read_u32(bytes: &[u8]) -> Result<u32, Box<dyn Error>> {
+    let bytes: [u8; 4] = bytes.get(..4).and_then(|b| b.try_into().ok()).ok_or("Frame payload too short")?;
+    Ok(u32::from_be_bytes(bytes))
+}
+
+fn write_frame(out: &mut Vec<u8>, kind: u8, flags: u8, stream_id: u32, payload: &[u8]) {
+    out.extend_from_slice(&(payload.len() as u32).to_be_bytes()[1..]);
+    out.push(kind);
+    out.push(flags);
+    out.extend_from_slice(&stream_id.to_be_bytes());
+    out.extend_from_slice(payload);
+}
+
+// A request's HEADERS frame, split into CONTINUATION frames when the block is too large
+fn write_header_block(out: &mut Vec<u8>, stream_id: u32, block: &[u8], max_frame_size: usize) {
+    let mut fragments = block.chunks(max_frame_size.max(1)).peekable();
+    let mut kind = HEADERS;
+    let mut flags = END_STREAM;
+    loop {
+        let fragment = fragments.next().unwrap_or_default();
+        let last = fragments.peek().is_none();
+        write_frame(out, kind, if last { flags | END_HEADERS } else { flags }, stream_id, fragment);
+        if last {
+            break;
+        }
+        kind = CONTINUATION;
+        flags = 0;
+    }
+}
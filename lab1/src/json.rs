@@ -0,0 +1,208 @@
+//! JSON decoder producing `Data` trees.
+
+use std::collections::HashMap;
+use std::error::Error;
+use crate::data::Data;
+
+/// Parse a JSON document into `Data`
+pub fn parse_json(text: &str) -> Result<Data, Box<dyn Error>> {
+    let mut parser = Parser { bytes: text.as_bytes(), position: 0 };
+    // Tolerate a leading byte order mark
+    if text.starts_with('\u{feff}') {
+        parser.position = 3;
+    }
+
+    let value = parser.parse_value(0)?;
+    parser.skip_whitespace();
+    if parser.position != parser.bytes.len() {
+        return Err(parser.error("Unexpected trailing characters"));
+    }
+    Ok(value)
+}
+
+// Deeply nested input is rejected instead of overflowing the stack
+const MAX_DEPTH: usize = 128;
+
+struct Parser<'a> {
+    bytes: &'a [u8],
+    position: usize,
+}
+
+impl Parser<'_> {
+    fn parse_value(&mut self, depth: usize) -> Result<Data, Box<dyn Error>> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("JSON nested too deeply"));
+        }
+
+        self.skip_whitespace();
+        match self.peek() {
+            Some(b'{') => self.parse_object(depth),
+            Some(b'[') => self.parse_array(depth),
+            Some(b'"') => Ok(Data::Text(self.parse_string()?)),
+            Some(b't') => self.parse_literal("true", Data::Bool(true)),
+            Some(b'f') => self.parse_literal("false", Data::Bool(false)),
+            Some(b'n') => self.parse_literal("null", Data::Null),
+            Some(b'-' | b'0'..=b'9') => self.parse_number(),
+            Some(_) => Err(self.error("Unexpected character")),
+            None => Err(self.error("Unexpected end of input")),
+        }
+    }
+
+    fn parse_object(&mut self, depth: usize) -> Result<Data, Box<dyn Error>> {
+        self.position += 1;
+        let mut map = HashMap::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(b'}') {
+            self.position += 1;
+            return Ok(Data::Map(map));
+        }
+
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some(b'"') {
+                return Err(self.error("Expected object key"));
+            }
+            let key = self.parse_string()?;
+            self.skip_whitespace();
+            self.expect(b':')?;
+            let value = self.parse_value(depth + 1)?;
+            map.insert(key, value);
+
+            self.skip_whitespace();
+            match self.next() {
+                Some(b',') => continue,
+                Some(b'}') => return Ok(Data::Map(map)),
+                _ => return Err(self.error("Expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn parse_array(&mut self, depth: usize) -> Result<Data, Box<dyn Error>> {
+        self.position += 1;
+        let mut items = Vec::new();
+
+        self.skip_whitespace();
+        if self.peek() == Some(b']') {
+            self.position += 1;
+            return Ok(Data::List(items));
+        }
+
+        loop {
+            items.push(self.parse_value(depth + 1)?);
+            self.skip_whitespace();
+            match self.next() {
+                Some(b',') => continue,
+                Some(b']') => return Ok(Data::List(items)),
+                _ => return Err(self.error("Expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, Box<dyn Error>> {
+        self.position += 1;
+        let mut output = String::new();
+
+        loop {
+            // Copy the run of plain characters in one go
+            let start = self.position;
+            while matches!(self.peek(), Some(byte) if byte != b'"' && byte != b'\\' && byte >= 0x20) {
+                self.position += 1;
+            }
+            output.push_str(std::str::from_utf8(&self.bytes[start..self.position])?);
+
+            match self.next() {
+                Some(b'"') => return Ok(output),
+                Some(b'\\') => match self.next() {
+                    Some(b'"') => output.push('"'),
+                    Some(b'\\') => output.push('\\'),
+                    Some(b'/') => output.push('/'),
+                    Some(b'b') => output.push('\u{8}'),
+                    Some(b'f') => output.push('\u{c}'),
+                    Some(b'n') => output.push('\n'),
+                    Some(b'r') => output.push('\r'),
+                    Some(b't') => output.push('\t'),
+                    Some(b'u') => output.push(self.parse_unicode_escape()?),
+                    _ => return Err(self.error("Invalid escape sequence")),
+                },
+                Some(_) => return Err(self.error("Control character in string")),
+                None => return Err(self.error("Unterminated string")),
+            }
+        }
+    }
+
+    // `\uXXXX`, combining UTF-16 surrogate pairs; lone surrogates become U+FFFD
+    fn parse_unicode_escape(&mut self) -> Result<char, Box<dyn Error>> {
+        let first = self.parse_hex4()?;
+        if (0xD800..0xDC00).contains(&first) && self.bytes[self.position..].starts_with(b"\\u") {
+            self.position += 2;
+            let second = self.parse_hex4()?;
+            if (0xDC00..0xE000).contains(&second) {
+                let code = 0x10000 + ((first - 0xD800) << 10) + (second - 0xDC00);
+                return Ok(char::from_u32(code).unwrap_or('\u{fffd}'));
+            }
+            return Ok('\u{fffd}');
+        }
+        Ok(char::from_u32(first).unwrap_or('\u{fffd}'))
+    }
+
+    fn parse_hex4(&mut self) -> Result<u32, Box<dyn Error>> {
+        let digits = self.bytes.get(self.position..self.position + 4).ok_or_else(|| self.error("Truncated \\u escape"))?;
+        let value = u32::from_str_radix(std::str::from_utf8(digits)?, 16)
+            .map_err(|_| self.error("Invalid \\u escape"))?;
+        self.position += 4;
+        Ok(value)
+    }
+
+    fn parse_number(&mut self) -> Result<Data, Box<dyn Error>> {
+        let start = self.position;
+        while matches!(self.peek(), Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')) {
+            self.position += 1;
+        }
+        let text = std::str::from_utf8(&self.bytes[start..self.position])?;
+
+        if let Ok(integer) = text.parse::<i32>() {
+            return Ok(Data::Int(integer));
+        }
+        text.parse::<f64>()
+            .map(Data::Float)
+            .map_err(|_| self.error("Invalid number"))
+    }
+
+    fn parse_literal(&mut self, literal: &str, value: Data) -> Result<Data, Box<dyn Error>> {
+        if self.bytes[self.position..].starts_with(literal.as_bytes()) {
+            self.position += literal.len();
+            Ok(value)
+        } else {
+            Err(self.error("Invalid literal"))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while matches!(self.peek(), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.position += 1;
+        }
+    }
+
+    fn expect(&mut self, byte: u8) -> Result<(), Box<dyn Error>> {
+        if self.next() == Some(byte) {
+            Ok(())
+        } else {
+            Err(self.error(&format!("Expected '{}'", byte as char)))
+        }
+    }
+
+    fn peek(&self) -> Option<u8> {
+        self.bytes.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<u8> {
+        let byte = self.peek();
+        self.position += 1;
+        byte
+    }
+
+    fn error(&self, message: &str) -> Box<dyn Error> {
+        format!("{} at byte {}", message, self.position).into()
+    }
+}
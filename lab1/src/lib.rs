@@ -0,0 +1,54 @@
+//! Product scraping for online shops, and the formats lab1 writes products in.
+//!
+//! - [`Scraper`] crawls a shop's listing page or sitemaps over the crate's own HTTP [`Client`]
+//!   (proxies, cookies, custom TLS, HTTP/2), validates every product and merges duplicates.
+//! - [`Product`] and the generic [`Data`] tree they convert to, with encoders and decoders for
+//!   JSON, XML and the Bracketed-Indented format (see [`Format`]).
+//! - [`validation`] holds the rules scraped products must pass; the ones that fail come back as
+//!   [`Rejection`]s listing every [`Violation`].
+//!
+//! ```no_run
+//! use lab1::cookies::CookieJar;
+//! use lab1::proxy::ProxyConfig;
+//! use lab1::tls::TlsOptions;
+//! use lab1::{Client, Format, Scraper};
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = Client::new(ProxyConfig::from_env()?, CookieJar::new(), &TlsOptions::default())?;
+//! let report = Scraper::new(client, "https://xstore.md/").scrape()?;
+//! println!("{}", Format::Json.encode(&report.products));
+//! for rejection in &report.rejected {
+//!     eprintln!("{}", rejection);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod base64;
+pub mod bi;
+pub mod cookies;
+pub mod daemon;
+pub mod data;
+pub mod diff;
+pub mod har;
+pub mod hpack;
+pub mod http;
+pub mod http2;
+pub mod json;
+pub mod normalize;
+pub mod price;
+pub mod product;
+pub mod proxy;
+pub mod query;
+pub mod scraping;
+pub mod sitemap;
+pub mod structured;
+pub mod tls;
+pub mod validation;
+pub mod xml;
+
+pub use data::Data;
+pub use http::Client;
+pub use product::{deserialize_products, Format, Product, ProductImage};
+pub use scraping::{ScrapeReport, Scraper};
+pub use validation::{Rejection, Violation};
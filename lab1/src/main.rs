@@ -1,26 +1,43 @@
-mod product;
-mod scraping;
-mod validation;
-mod data;
+mod cli;
 
 use std::error::Error;
+use std::sync::{Arc, Mutex};
 use chrono::{DateTime, Utc};
-use product::{Product, serialize_products_to_json, serialize_products_to_xml, serialize_products_to_bi};
-use scraping::scrape_products;
-use data::MDL_TO_EUR;
+use lab1::cookies::CookieJar;
+use lab1::data::MDL_TO_EUR;
+use lab1::har::HarArchive;
+use lab1::http::Traffic;
+use lab1::product::{serialize_products_to_bi, serialize_products_to_json, serialize_products_to_xml};
+use lab1::proxy::ProxyConfig;
+use lab1::query::{Aggregate, Query};
+use lab1::{daemon, diff, deserialize_products, Client, Data, Product, ScrapeReport, Scraper};
+use url::Url;
+use cli::{Command, DiffOptions, Options, OutputFormat};
 
 fn main() -> Result<(), Box<dyn Error>> {
-    // Scrape products from the website
-    let products = scrape_products("https://xstore.md/")?;
-    
-    // Process products: Filter, map, and reduce
-    let filtered_products: Vec<Product> = products.into_iter()
-        .filter(|p| p.price >= 1000.0 && p.price <= 15000.0)
-        .collect();
-
-    let total_price_mdl: f64 = filtered_products.iter()
-        .map(|p| p.price)
-        .sum();
+    let options = match Command::from_args()? {
+        Command::Scrape(options) => options,
+        Command::Diff(options) => return run_diff(&options),
+    };
+    let scraper = build_scraper(&options)?;
+
+    if options.show_cert {
+        println!("Server certificate for {}:", options.url);
+        println!("{}", scraper.client().peer_certificate(&options.url)?);
+    }
+
+    if options.daemon.schedule.is_some() {
+        return daemon::run_daemon(&options.daemon, || Ok(scrape(&scraper, &options)?.products));
+    }
+
+    let ScrapeReport { products: filtered_products, rejected, scraped, .. } = scrape(&scraper, &options)?;
+
+    let total_price_mdl = Query::new()
+        .aggregate(Aggregate::Sum("price".to_string()))
+        .run(&filtered_products)
+        .get("sum_price")
+        .and_then(Data::as_f64)
+        .unwrap_or(0.0);
     let total_price_eur = total_price_mdl * MDL_TO_EUR;
     
     // Get the current UTC timestamp
@@ -36,6 +53,11 @@ fn main() -> Result<(), Box<dyn Error>> {
     println!("\nBracket Indent Custom Format Output:");
     println!("{}", serialize_products_to_bi(&filtered_products));
 
+    if options.query.is_aggregate() {
+        println!("\nQuery Result:");
+        println!("{}", options.query.run(&filtered_products).to_bi(0));
+    }
+
     // Print the summary information
     println!("\nSummary:");
     println!("Total Price of Filtered Products: {:.2} MDL (~ {:.2} EUR)", 
@@ -51,7 +73,92 @@ fn main() -> Result<(), Box<dyn Error>> {
                  product.price * MDL_TO_EUR,
                  product.link,
                  product.description);
+        if let Some(image) = &product.image {
+            println!("    Image: {} ({}, {} bytes)",
+                     product.image_url.as_deref().unwrap_or(""),
+                     image.content_type,
+                     image.data.len());
+        }
+    }
+
+    // Report every product dropped by validation and why
+    println!("\nRejection Report:");
+    println!("Rejected {} of {} scraped products", rejected.len(), scraped);
+    for rejection in &rejected {
+        println!("{}", rejection);
     }
     
     Ok(())
-}
\ No newline at end of file
+}
+
+fn build_scraper(options: &Options) -> Result<Scraper, Box<dyn Error>> {
+    // Proxy settings come from the environment, with command line flags taking precedence
+    let mut proxy = ProxyConfig::from_env()?;
+    if let Some(proxy_url) = &options.proxy {
+        proxy.set_proxy(proxy_url)?;
+    }
+    if let Some(no_proxy) = &options.no_proxy {
+        proxy.set_no_proxy(no_proxy);
+    }
+
+    // Start from the saved cookie jar, if any, plus cookies given on the command line
+    let mut jar = match &options.cookie_jar {
+        Some(path) if path.exists() => CookieJar::load(path)?,
+        _ => CookieJar::new(),
+    };
+    let start_url = Url::parse(&options.url)?;
+    jar.store(&start_url, options.cookies.iter().map(String::as_str));
+
+    if options.tls.insecure {
+        eprintln!("Warning: TLS certificate verification is disabled (--insecure)");
+    }
+    let mut client = Client::new(proxy, jar, &options.tls)?;
+    if let Some(path) = &options.replay {
+        let archive = HarArchive::load(path).map_err(|e| format!("Cannot replay {}: {}", path.display(), e))?;
+        client.traffic = Traffic::Replay(Arc::new(archive));
+    } else if options.record.is_some() {
+        client.traffic = Traffic::Record(Arc::new(Mutex::new(HarArchive::new())));
+    }
+
+    let scraper = Scraper::new(client, &options.url)
+        .conflict_policy(options.conflict_policy)
+        .query(options.query.clone());
+    Ok(if options.sitemap {
+        scraper.sitemaps(options.sitemap_urls.clone(), options.sitemap_filter.clone())
+    } else {
+        scraper
+    })
+}
+
+// Scrape once and save the cookies the site set, and the traffic when recording
+fn scrape(scraper: &Scraper, options: &Options) -> Result<ScrapeReport, Box<dyn Error>> {
+    let result = scraper.scrape();
+    if let (Some(path), Traffic::Record(archive)) = (&options.record, &scraper.client().traffic) {
+        archive.lock().unwrap().save(path)?;
+    }
+    let report = result?;
+    if let Some(path) = &options.cookie_jar {
+        scraper.client().cookies.lock().unwrap().save(path)?;
+    }
+    if report.merged > 0 {
+        println!("Merged {} duplicate products", report.merged);
+    }
+    Ok(report)
+}
+
+// Compare two saved outputs and print what changed between them
+fn run_diff(options: &DiffOptions) -> Result<(), Box<dyn Error>> {
+    let read = |path: &std::path::Path| -> Result<Vec<Product>, Box<dyn Error>> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Cannot read {}: {}", path.display(), e))?;
+        deserialize_products(&text).map_err(|e| format!("Cannot decode {}: {}", path.display(), e).into())
+    };
+    let diff = diff::diff_products(read(&options.before)?, read(&options.after)?);
+
+    match options.format {
+        OutputFormat::Human if diff.is_empty() => println!("No differences ({} products unchanged)", diff.unchanged),
+        OutputFormat::Human => println!("{}", diff),
+        OutputFormat::Json => println!("{}", diff.to_data().to_json(0)),
+        OutputFormat::Bi => println!("{}", diff.to_data().to_bi(0)),
+    }
+    Ok(())
+}
@@ -0,0 +1,132 @@
+//! Canonical links and names, and merging of duplicate products.
+
+use std::collections::HashMap;
+use std::error::Error;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use url::Url;
+use crate::product::Product;
+
+// Query parameters added by ad networks, newsletters and analytics; they never change the page
+const TRACKING_PARAMS: &[&str] = &[
+    "gclid", "gclsrc", "dclid", "fbclid", "yclid", "msclkid", "igshid", "srsltid", "gad_source",
+    "mc_cid", "mc_eid", "_ga", "_gl", "_openstat", "ref", "ref_src", "spm",
+];
+
+/// Which duplicate supplies the fields the duplicates disagree on
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    #[default]
+    First,
+    Last,
+    Cheapest,
+    Priciest,
+}
+
+impl ConflictPolicy {
+    pub fn parse(policy: &str) -> Result<ConflictPolicy, Box<dyn Error>> {
+        match policy.trim().to_lowercase().as_str() {
+            "first" => Ok(ConflictPolicy::First),
+            "last" => Ok(ConflictPolicy::Last),
+            "cheapest" => Ok(ConflictPolicy::Cheapest),
+            "priciest" => Ok(ConflictPolicy::Priciest),
+            _ => Err(format!("Unknown conflict policy: {} (expected first, last, cheapest or priciest)", policy).into()),
+        }
+    }
+}
+
+/// Drop tracking parameters and the fragment; the host is lowercased and default ports removed by `Url`
+pub fn canonical_url(link: &str) -> String {
+    let Ok(mut url) = Url::parse(link.trim()) else {
+        return link.trim().to_string();
+    };
+    url.set_fragment(None);
+
+    let kept: Vec<(String, String)> = url.query_pairs()
+        .filter(|(key, _)| {
+            let key = key.to_lowercase();
+            !key.starts_with("utm_") && !TRACKING_PARAMS.contains(&key.as_str())
+        })
+        .map(|(key, value)| (key.into_owned(), value.into_owned()))
+        .collect();
+    if kept.is_empty() {
+        url.set_query(None);
+    } else {
+        url.query_pairs_mut().clear().extend_pairs(kept);
+    }
+
+    url.to_string()
+}
+
+/// Name as displayed: compatibility-normalized (NFKC) with whitespace runs collapsed to one space
+pub fn normalize_name(name: &str) -> String {
+    name.nfkc().collect::<String>().split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Key under which names count as the same: case, accents, punctuation and spacing are ignored,
+/// so `Apple  iPhone-15 Pró` matches `apple iphone 15 pro`
+pub fn name_key(name: &str) -> String {
+    let folded: String = name.nfkd().filter(|c| !is_combining_mark(*c)).collect::<String>().to_lowercase();
+    folded.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+/// Normalize every product and merge those with the same canonical link, or the same name key
+/// when they have no link. Merged products keep the position of their first occurrence.
+pub fn deduplicate(products: Vec<Product>, policy: ConflictPolicy) -> Vec<Product> {
+    let mut groups: Vec<Vec<Product>> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+
+    for mut product in products {
+        product.name = normalize_name(&product.name);
+        // A blank link is no link, so the product is matched by name
+        product.link = if product.link.trim().is_empty() { String::new() } else { canonical_url(&product.link) };
+
+        let key = if product.link.is_empty() { format!("name:{}", name_key(&product.name)) } else { product.link.clone() };
+        match index.get(&key) {
+            Some(&group) => groups[group].push(product),
+            None => {
+                index.insert(key, groups.len());
+                groups.push(vec![product]);
+            }
+        }
+    }
+
+    groups.into_iter().map(|group| merge(group, policy)).collect()
+}
+
+// The policy's pick supplies every field it has; the others only fill the gaps
+fn merge(mut duplicates: Vec<Product>, policy: ConflictPolicy) -> Product {
+    let chosen = match policy {
+        ConflictPolicy::First => 0,
+        ConflictPolicy::Last => duplicates.len() - 1,
+        ConflictPolicy::Cheapest => position_by(&duplicates, |a, b| a < b),
+        ConflictPolicy::Priciest => position_by(&duplicates, |a, b| a > b),
+    };
+    let mut merged = duplicates.remove(chosen);
+
+    for other in duplicates {
+        if merged.description.trim().is_empty() || merged.description == "Attributes not found" {
+            merged.description = other.description;
+        }
+        merged.image_url = merged.image_url.or(other.image_url);
+        merged.image = merged.image.or(other.image);
+        merged.sku = merged.sku.or(other.sku);
+        merged.brand = merged.brand.or(other.brand);
+        merged.availability = merged.availability.or(other.availability);
+    }
+    merged
+}
+
+// Index of the first product whose price beats all others under `better`
+fn position_by(products: &[Product], better: impl Fn(f64, f64) -> bool) -> usize {
+    let mut best = 0;
+    for (i, product) in products.iter().enumerate().skip(1) {
+        if better(product.price, products[best].price) {
+            best = i;
+        }
+    }
+    best
+}
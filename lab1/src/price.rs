@@ -0,0 +1,267 @@
+//! Prices as shops display them, in Romanian, Russian and English number formats.
+
+use std::error::Error;
+use std::fmt;
+use crate::data::EUR_TO_MDL;
+
+/// Number formats seen on Moldovan shops; they differ in the decimal separator
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NumberLocale {
+    Romanian,
+    Russian,
+    English,
+}
+
+impl NumberLocale {
+    /// From a language tag such as `ro`, `ru-MD` or `en_US`
+    pub fn from_language_tag(tag: &str) -> Option<NumberLocale> {
+        let language = tag.trim().split(['-', '_']).next()?.to_lowercase();
+        match language.as_str() {
+            "ro" | "mo" => Some(NumberLocale::Romanian),
+            "ru" => Some(NumberLocale::Russian),
+            "en" => Some(NumberLocale::English),
+            _ => None,
+        }
+    }
+
+    fn decimal_separator(self) -> char {
+        match self {
+            NumberLocale::English => '.',
+            NumberLocale::Romanian | NumberLocale::Russian => ',',
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Price {
+    /// The lower bound for ranges such as `from 999 lei` or `999 - 1 299 lei`
+    pub amount: f64,
+    /// ISO 4217 code, or `None` when the text names no currency
+    pub currency: Option<String>,
+    pub max_amount: Option<f64>,
+}
+
+impl Price {
+    pub fn amount_mdl(&self) -> Option<f64> {
+        to_mdl(self.amount, self.currency.as_deref())
+    }
+}
+
+/// Convert to MDL; amounts without a currency are taken to be MDL already
+pub fn to_mdl(amount: f64, currency: Option<&str>) -> Option<f64> {
+    match currency.map(str::to_uppercase).as_deref() {
+        None | Some("MDL") => Some(amount),
+        Some("EUR") => Some(amount * EUR_TO_MDL),
+        Some(_) => None,
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum PriceError {
+    Empty,
+    Unparseable(String),
+    // A lone `.` or `,` before three digits when the locale is unknown: `1,299` may be 1299 or 1.299
+    AmbiguousSeparator(char),
+    ConflictingCurrencies(String, String),
+}
+
+impl fmt::Display for PriceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PriceError::Empty => write!(f, "no price given"),
+            PriceError::Unparseable(text) => write!(f, "cannot read {:?} as a price", text),
+            PriceError::AmbiguousSeparator(separator) => {
+                write!(f, "'{}' could be a thousands separator or a decimal point", separator)
+            }
+            PriceError::ConflictingCurrencies(first, second) => write!(f, "both {} and {} are given", first, second),
+        }
+    }
+}
+
+impl Error for PriceError {}
+
+// Words that introduce a lower bound, in English, Romanian and Russian
+const FROM_PREFIXES: &[&str] = &["starting at", "starting from", "from", "începând de la", "incepand de la", "de la", "от"];
+// Words that may separate the two ends of a range
+const RANGE_WORDS: &[&str] = &["-", "–", "—", "to", "până", "pana", "la", "до"];
+
+/// Parse a displayed price: `12 999 lei`, `1.299,00 lei`, `€ 65.50`, `$1,299.99`, `от 999 лей`, `999 - 1 299 MDL`.
+/// `locale` settles a lone separator before three digits; without one such prices are rejected as ambiguous.
+pub fn parse_price(text: &str, locale: Option<NumberLocale>) -> Result<Price, PriceError> {
+    // NBSP, narrow NBSP, thin and figure spaces all group digits
+    let normalized: String = text.chars().map(|c| if c.is_whitespace() { ' ' } else { c }).collect();
+    let mut rest = normalized.trim();
+    if rest.is_empty() {
+        return Err(PriceError::Empty);
+    }
+
+    // Drop a label such as `Preț:` or `Цена:`
+    if let Some((label, value)) = rest.split_once(':') {
+        if !label.chars().any(|c| c.is_ascii_digit()) {
+            rest = value.trim();
+        }
+    }
+    for prefix in FROM_PREFIXES {
+        if let Some(stripped) = strip_prefix_ignore_case(rest, prefix) {
+            rest = stripped.trim_start();
+            break;
+        }
+    }
+
+    let unparseable = || PriceError::Unparseable(text.trim().to_string());
+    let mut amounts = Vec::new();
+    let mut currency: Option<String> = None;
+    let mut negative = false;
+    let mut range_marker = false;
+
+    for token in tokenize(rest) {
+        match token {
+            Token::Number(number) => {
+                if amounts.len() == 2 || (amounts.len() == 1 && !range_marker) {
+                    return Err(unparseable());
+                }
+                amounts.push(parse_number(number, locale)?);
+            }
+            Token::Text(words) => {
+                // Dashes count as words even when written against a currency, as in `lei-`
+                let spaced: String = words.chars()
+                    .flat_map(|c| if matches!(c, '-' | '–' | '—') { vec![' ', c, ' '] } else { vec![c] })
+                    .collect();
+                for word in spaced.split_whitespace() {
+                    if let Some(code) = currency_code(word) {
+                        match &currency {
+                            Some(existing) if *existing != code => {
+                                return Err(PriceError::ConflictingCurrencies(existing.clone(), code));
+                            }
+                            _ => currency = Some(code),
+                        }
+                    } else if word == "-" && amounts.is_empty() {
+                        negative = true;
+                    } else if RANGE_WORDS.contains(&word.to_lowercase().as_str()) && amounts.len() == 1 {
+                        range_marker = true;
+                    } else {
+                        return Err(unparseable());
+                    }
+                }
+            }
+        }
+    }
+
+    let Some(&first) = amounts.first() else {
+        return Err(unparseable());
+    };
+    Ok(Price {
+        amount: if negative { -first } else { first },
+        currency,
+        max_amount: amounts.get(1).copied(),
+    })
+}
+
+enum Token<'a> {
+    Number(&'a str),
+    Text(&'a str),
+}
+
+// Split into digit runs (with their grouping and decimal marks) and the text between them
+fn tokenize(text: &str) -> Vec<Token<'_>> {
+    let chars: Vec<(usize, char)> = text.char_indices().collect();
+    let position = |index: usize| chars.get(index).map_or(text.len(), |(p, _)| *p);
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let start = i;
+        if chars[i].1.is_ascii_digit() {
+            i += 1;
+            while i < chars.len() {
+                let next_is_digit = chars.get(i + 1).is_some_and(|(_, c)| c.is_ascii_digit());
+                match chars[i].1 {
+                    c if c.is_ascii_digit() => i += 1,
+                    '.' | ',' | ' ' | '\'' if next_is_digit => i += 1,
+                    _ => break,
+                }
+            }
+            tokens.push(Token::Number(&text[position(start)..position(i)]));
+        } else {
+            while i < chars.len() && !chars[i].1.is_ascii_digit() {
+                i += 1;
+            }
+            let words = text[position(start)..position(i)].trim();
+            if !words.is_empty() {
+                tokens.push(Token::Text(words));
+            }
+        }
+    }
+
+    tokens
+}
+
+fn parse_number(number: &str, locale: Option<NumberLocale>) -> Result<f64, PriceError> {
+    let marks: Vec<(usize, char)> = number.char_indices().filter(|(_, c)| !c.is_ascii_digit()).collect();
+
+    let decimal = match marks.as_slice() {
+        [] => None,
+        [(position, mark @ ('.' | ','))] => {
+            if number.len() - position - 1 != 3 {
+                Some(*position)
+            } else {
+                match locale {
+                    Some(locale) if locale.decimal_separator() == *mark => Some(*position),
+                    Some(_) => None,
+                    None => return Err(PriceError::AmbiguousSeparator(*mark)),
+                }
+            }
+        }
+        [earlier @ .., (position, mark @ ('.' | ','))] if !earlier.iter().any(|(_, c)| c == mark) => Some(*position),
+        _ => None,
+    };
+
+    let (integer, fraction) = match decimal {
+        Some(position) => (&number[..position], &number[position + 1..]),
+        None => (number, ""),
+    };
+
+    // Grouped integers need one kind of mark and three-digit groups after the first
+    let groups: Vec<&str> = integer.split(|c: char| !c.is_ascii_digit()).collect();
+    let group_marks: Vec<char> = integer.chars().filter(|c| !c.is_ascii_digit()).collect();
+    if groups.len() > 1
+        && (groups[0].is_empty() || groups[0].len() > 3
+            || groups[1..].iter().any(|g| g.len() != 3)
+            || group_marks.iter().any(|c| *c != group_marks[0]))
+    {
+        return Err(PriceError::Unparseable(number.to_string()));
+    }
+
+    let digits: String = groups.concat();
+    let value = if fraction.is_empty() { digits } else { format!("{}.{}", digits, fraction) };
+    value.parse().map_err(|_| PriceError::Unparseable(number.to_string()))
+}
+
+fn currency_code(word: &str) -> Option<String> {
+    let word = word.trim_end_matches('.');
+    let code = match word.to_lowercase().as_str() {
+        "lei" | "leu" | "mdl" | "лей" | "лея" | "леев" => "MDL",
+        "€" | "eur" | "euro" | "евро" => "EUR",
+        "$" | "usd" => "USD",
+        "ron" => "RON",
+        "₽" | "rub" | "руб" => "RUB",
+        "₴" | "uah" | "грн" => "UAH",
+        "£" | "gbp" => "GBP",
+        _ if word.len() == 3 && word.chars().all(|c| c.is_ascii_uppercase()) => return Some(word.to_string()),
+        _ => return None,
+    };
+    Some(code.to_string())
+}
+
+// Case-insensitive prefix match that ends on a word boundary
+fn strip_prefix_ignore_case<'a>(text: &'a str, prefix: &str) -> Option<&'a str> {
+    let mut text_chars = text.char_indices();
+    for expected in prefix.chars() {
+        let (_, actual) = text_chars.next()?;
+        if !actual.to_lowercase().eq(expected.to_lowercase()) {
+            return None;
+        }
+    }
+    let rest = text_chars.as_str();
+    (!rest.starts_with(char::is_alphanumeric)).then_some(rest)
+}
@@ -1,64 +1,190 @@
+//! The scraped product and its JSON, XML and BI encoders and decoders.
+
 use std::collections::HashMap;
-use crate::data::Data;
+use std::error::Error;
+use crate::base64;
+use crate::bi::parse_bi;
+use crate::data::{escape_json, Data};
+use crate::json::parse_json;
+use crate::xml::{local_name, parse_xml, XmlEvent};
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Product {
     pub name: String,
     pub price: f64,
     pub link: String,
     pub description: String,
+    pub image_url: Option<String>,
+    pub image: Option<ProductImage>,
+    pub sku: Option<String>,
+    pub brand: Option<String>,
+    /// schema.org availability, e.g. `InStock` or `OutOfStock`
+    pub availability: Option<String>,
+}
+
+/// Raw image bytes downloaded for a product, ready to be saved or uploaded as a BLOB.
+/// Every output format carries it base64-encoded, so a saved output can be uploaded later.
+#[derive(Debug, Clone)]
+pub struct ProductImage {
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+impl ProductImage {
+    // `{content_type, data}` with the bytes in base64. Outputs written before images were
+    // exported only describe the image, and have no image to read back.
+    fn from_data(image: &Data) -> Result<Option<ProductImage>, Box<dyn Error>> {
+        let text = |key: &str| image.get(key).and_then(Data::as_str);
+        let (Some(content_type), Some(data)) = (text("content_type"), text("data")) else {
+            return Ok(None);
+        };
+        Ok(Some(ProductImage {
+            content_type: content_type.to_string(),
+            data: base64::decode(data).map_err(|e| format!("Invalid image data: {}", e))?,
+        }))
+    }
 }
 
 impl Product {
-    // Serialize to custom Brackets Indent Format
-    fn to_data(&self) -> Data {
+    /// Field map used by queries and diffs, which describe the image by type and size
+    pub fn to_data(&self) -> Data {
         let mut map = HashMap::new();
         map.insert("name".to_string(), Data::Text(self.name.clone()));
         map.insert("price".to_string(), Data::Float(self.price));
         map.insert("link".to_string(), Data::Text(self.link.clone()));
+        map.insert("attributes".to_string(), Data::Text(self.description.clone()));
+        if let Some(image_url) = &self.image_url {
+            map.insert("image_url".to_string(), Data::Text(image_url.clone()));
+        }
+        for (key, value) in [("sku", &self.sku), ("brand", &self.brand), ("availability", &self.availability)] {
+            if let Some(value) = value {
+                map.insert(key.to_string(), Data::Text(value.clone()));
+            }
+        }
+        if let Some(image) = &self.image {
+            let mut image_map = HashMap::new();
+            image_map.insert("content_type".to_string(), Data::Text(image.content_type.clone()));
+            image_map.insert("size".to_string(), Data::Int(image.data.len() as i32));
+            map.insert("image".to_string(), Data::Map(image_map));
+        }
         Data::Map(map)
     }
 
-    pub fn serialize_products_to_bi(products: &[Product]) -> String {
-        let data_list: Vec<Data> = products.iter().map(|p| p.to_data()).collect();
-        Data::List(data_list).to_bi(0)
+    // `to_data` plus the image bytes, as the Brackets Indent Format writes a product
+    fn to_record(&self) -> Data {
+        let mut data = self.to_data();
+        if let (Some(image), Data::Map(map)) = (&self.image, &mut data) {
+            if let Some(Data::Map(image_map)) = map.get_mut("image") {
+                image_map.insert("data".to_string(), Data::Text(base64::encode(&image.data)));
+            }
+        }
+        data
     }
 
-    // Serialize a single product to JSON
+    /// Rebuild a product from a record decoded from JSON, XML or BI, image included
+    pub fn from_data(data: &Data) -> Result<Product, Box<dyn Error>> {
+        let text = |key: &str| data.get(key).and_then(Data::as_str).map(str::to_string);
+        // XML writes missing optional fields as empty elements
+        let optional = |key: &str| text(key).filter(|value| !value.is_empty());
+
+        let name = text("name").ok_or("Product without a name")?;
+        let price = match data.get("price") {
+            Some(Data::Text(price)) => price.trim().parse().ok(),
+            Some(price) => price.as_f64(),
+            None => None,
+        };
+        Ok(Product {
+            price: price.ok_or_else(|| format!("Product {:?} has no numeric price", name))?,
+            name,
+            link: text("link").unwrap_or_default(),
+            // The JSON encoder has always called this field `attribut`
+            description: text("attributes").or_else(|| text("attribut")).or_else(|| text("attribute")).unwrap_or_default(),
+            image_url: optional("image_url"),
+            image: match data.get("image") {
+                Some(image) => ProductImage::from_data(image)?,
+                None => None,
+            },
+            sku: optional("sku"),
+            brand: optional("brand"),
+            availability: optional("availability"),
+        })
+    }
+
+    /// Serialize a single product to JSON
     pub fn to_json(&self) -> String {
         format!(
             r#"{{
     "name": "{}",
     "price": {},
     "link": "{}",
-    "attribut": "{}"
+    "attribut": "{}",
+    "image_url": {},
+    "image": {},
+    "sku": {},
+    "brand": {},
+    "availability": {}
 }}"#,
             // Escape special characters in JSON strings
-            self.name.replace('"', "\\\""),
+            escape_json(&self.name),
             self.price,
-            self.link.replace('"', "\\\""),
-            self.description,
+            escape_json(&self.link),
+            escape_json(&self.description),
+            json_optional(self.image_url.as_deref()),
+            json_image(self.image.as_ref()),
+            json_optional(self.sku.as_deref()),
+            json_optional(self.brand.as_deref()),
+            json_optional(self.availability.as_deref()),
         )
     }
 
-    // Serialize a single product to XML
+    /// Serialize a single product to XML
     pub fn to_xml(&self) -> String {
         format!(
             r#"<product>
     <name>{}</name>
     <price>{}</price>
     <link>{}</link>
-    <attribute{}</attribute>
+    <attribute>{}</attribute>
+    <image_url>{}</image_url>
+    {}
+    <sku>{}</sku>
+    <brand>{}</brand>
+    <availability>{}</availability>
 </product>"#,
             // Escape special characters in XML
             escape_xml(&self.name),
             self.price,
             escape_xml(&self.link),
-            escape_xml(&self.description)
+            escape_xml(&self.description),
+            escape_xml(self.image_url.as_deref().unwrap_or("")),
+            xml_image(self.image.as_ref()),
+            escape_xml(self.sku.as_deref().unwrap_or("")),
+            escape_xml(self.brand.as_deref().unwrap_or("")),
+            escape_xml(self.availability.as_deref().unwrap_or(""))
         )
     }
 }
 
+// Quoted JSON string, or `null` when the value is missing
+fn json_optional(input: Option<&str>) -> String {
+    input.map_or("null".to_string(), |value| format!("\"{}\"", escape_json(value)))
+}
+
+// `{"content_type": ..., "data": <base64>}`, or `null` when there is no image
+fn json_image(image: Option<&ProductImage>) -> String {
+    image.map_or("null".to_string(), |image| {
+        format!(r#"{{"content_type": "{}", "data": "{}"}}"#, escape_json(&image.content_type), base64::encode(&image.data))
+    })
+}
+
+// `<image content_type="...">base64</image>`, empty like the other missing fields when there is none
+fn xml_image(image: Option<&ProductImage>) -> String {
+    match image {
+        Some(image) => format!(r#"<image content_type="{}">{}</image>"#, escape_xml(&image.content_type), base64::encode(&image.data)),
+        None => "<image></image>".to_string(),
+    }
+}
+
 // Helper function to escape special XML characters
 fn escape_xml(input: &str) -> String {
     input
@@ -69,7 +195,7 @@ fn escape_xml(input: &str) -> String {
         .replace('\'', "&apos;")
 }
 
-// Serialize a vector of products to JSON
+/// Serialize a vector of products to JSON
 pub fn serialize_products_to_json(products: &[Product]) -> String {
     let products_json: Vec<String> = products
         .iter()
@@ -88,7 +214,7 @@ pub fn serialize_products_to_json(products: &[Product]) -> String {
     )
 }
 
-// Serialize a vector of products to XML
+/// Serialize a vector of products to XML
 pub fn serialize_products_to_xml(products: &[Product]) -> String {
     let products_xml: String = products
         .iter()
@@ -107,18 +233,139 @@ pub fn serialize_products_to_xml(products: &[Product]) -> String {
     )
 }
 
-// Serialize products into BI format
+/// Serialize products into BI format
 pub fn serialize_products_to_bi(products: &[Product]) -> String {
-    let bi_products: Vec<String> = products.iter().map(|product| {
-        let mut product_map = HashMap::new();
-        product_map.insert("name".to_string(), Data::Text(product.name.clone()));
-        product_map.insert("price".to_string(), Data::Float(product.price));
-        product_map.insert("link".to_string(), Data::Text(product.link.clone()));
-        product_map.insert("attributes".to_string(), Data::Text(product.description.clone()));
-
-        let product_data = Data::Map(product_map);
-        product_data.to_bi(4)
-    }).collect();
-
-    format!("Products [\n{}\n]", bi_products.join("\n"))
-}
\ No newline at end of file
+    let data_list: Vec<Data> = products.iter().map(|product| product.to_record()).collect();
+    format!("Products {}", Data::List(data_list).to_bi(0))
+}
+
+/// Decode products written by `serialize_products_to_json`
+pub fn deserialize_products_from_json(text: &str) -> Result<Vec<Product>, Box<dyn Error>> {
+    match parse_json(text)?.get("products") {
+        Some(Data::List(items)) => items.iter().map(Product::from_data).collect(),
+        _ => Err("JSON has no \"products\" list".into()),
+    }
+}
+
+/// Decode products written by `serialize_products_to_xml`
+pub fn deserialize_products_from_xml(text: &str) -> Result<Vec<Product>, Box<dyn Error>> {
+    let mut products = Vec::new();
+    let mut current: Option<HashMap<String, Data>> = None;
+    let mut field: Option<(String, String)> = None;
+    // The type of the image being read, from `<image content_type="...">`
+    let mut image_type: Option<String> = None;
+
+    for event in parse_xml(text)? {
+        match event {
+            XmlEvent::Start { name, attributes } => match local_name(&name) {
+                "product" => current = Some(HashMap::new()),
+                child if current.is_some() => {
+                    if child == "image" {
+                        image_type = attributes.into_iter().find(|(key, _)| key == "content_type").map(|(_, value)| value);
+                    }
+                    field = Some((child.to_string(), String::new()));
+                }
+                _ => {}
+            },
+            XmlEvent::Text(text) => {
+                if let Some((_, value)) = field.as_mut() {
+                    value.push_str(&text);
+                }
+            }
+            XmlEvent::End { name } => match local_name(&name) {
+                "product" => {
+                    if let Some(map) = current.take() {
+                        products.push(Product::from_data(&Data::Map(map))?);
+                    }
+                }
+                _ => {
+                    if let (Some(map), Some((key, value))) = (current.as_mut(), field.take()) {
+                        let value = Data::Text(value.trim().to_string());
+                        let value = match (key.as_str(), image_type.take()) {
+                            ("image", Some(content_type)) => Data::Map(HashMap::from([
+                                ("content_type".to_string(), Data::Text(content_type)),
+                                ("data".to_string(), value),
+                            ])),
+                            _ => value,
+                        };
+                        map.insert(key, value);
+                    }
+                }
+            },
+        }
+    }
+
+    Ok(products)
+}
+
+/// Decode products written by `serialize_products_to_bi`
+pub fn deserialize_products_from_bi(text: &str) -> Result<Vec<Product>, Box<dyn Error>> {
+    // The label shares its line with the list's opening bracket: `Products [`
+    let list = text.trim_start_matches('\u{feff}').trim_start()
+        .strip_prefix("Products")
+        .ok_or("BI does not start with \"Products\"")?;
+    match parse_bi(list)? {
+        Data::List(items) => items.iter().map(Product::from_data).collect(),
+        _ => Err("BI \"Products\" is not a list".into()),
+    }
+}
+
+/// Decode products in whichever of the three formats `text` is in
+pub fn deserialize_products(text: &str) -> Result<Vec<Product>, Box<dyn Error>> {
+    Format::detect(text).decode(text)
+}
+
+/// The formats products are written in, each with its encoder and decoder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    Xml,
+    Bi,
+}
+
+impl Format {
+    pub const ALL: [Format; 3] = [Format::Json, Format::Xml, Format::Bi];
+
+    /// Accepts the names used on the command line and as file extensions: `json`, `xml`, `bi`
+    pub fn parse(name: &str) -> Result<Format, Box<dyn Error>> {
+        match name.trim().to_lowercase().as_str() {
+            "json" => Ok(Format::Json),
+            "xml" => Ok(Format::Xml),
+            "bi" => Ok(Format::Bi),
+            _ => Err(format!("Unknown output format: {} (expected json, xml or bi)", name).into()),
+        }
+    }
+
+    /// Guess the format from the first character: `{` for JSON, `<` for XML, anything else is BI
+    pub fn detect(text: &str) -> Format {
+        match text.trim_start_matches('\u{feff}').trim_start().chars().next() {
+            Some('{') => Format::Json,
+            Some('<') => Format::Xml,
+            _ => Format::Bi,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Format::Json => "json",
+            Format::Xml => "xml",
+            Format::Bi => "bi",
+        }
+    }
+
+    pub fn encode(self, products: &[Product]) -> String {
+        match self {
+            Format::Json => serialize_products_to_json(products),
+            Format::Xml => serialize_products_to_xml(products),
+            Format::Bi => serialize_products_to_bi(products),
+        }
+    }
+
+    pub fn decode(self, text: &str) -> Result<Vec<Product>, Box<dyn Error>> {
+        match self {
+            Format::Json => deserialize_products_from_json(text),
+            Format::Xml => deserialize_products_from_xml(text),
+            Format::Bi => deserialize_products_from_bi(text),
+        }
+    }
+}
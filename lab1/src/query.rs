@@ -0,0 +1,467 @@
+//! Filtering, sorting, grouping and aggregation of products.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::error::Error;
+use crate::data::Data;
+use crate::product::Product;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    // Case-insensitive substring match
+    Contains,
+}
+
+/// A where-clause on one field of `Product::to_data`
+#[derive(Debug, Clone)]
+pub struct Condition {
+    pub field: String,
+    pub operator: Operator,
+    pub value: Data,
+}
+
+impl Condition {
+    // Products without the field never match
+    fn matches(&self, record: &Data) -> bool {
+        let Some(actual) = record.get(&self.field) else {
+            return false;
+        };
+        if self.operator == Operator::Contains {
+            return text_of(actual).to_lowercase().contains(&text_of(&self.value).to_lowercase());
+        }
+        match compare(actual, &self.value) {
+            Some(ordering) => match self.operator {
+                Operator::Eq => ordering == Ordering::Equal,
+                Operator::Ne => ordering != Ordering::Equal,
+                Operator::Lt => ordering == Ordering::Less,
+                Operator::Le => ordering != Ordering::Greater,
+                Operator::Gt => ordering == Ordering::Greater,
+                Operator::Ge => ordering != Ordering::Less,
+                Operator::Contains => unreachable!(),
+            },
+            // Values of different kinds are simply not equal
+            None => self.operator == Operator::Ne,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct SortKey {
+    pub field: String,
+    pub descending: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Aggregate {
+    Count,
+    Sum(String),
+    Average(String),
+    Min(String),
+    Max(String),
+    // Percentile between 0 and 100, interpolated between the closest ranks
+    Percentile(String, f64),
+}
+
+impl Aggregate {
+    /// Key of the result, e.g. `sum_price` or `p90_price`
+    pub fn name(&self) -> String {
+        match self {
+            Aggregate::Count => "count".to_string(),
+            Aggregate::Sum(field) => format!("sum_{}", field),
+            Aggregate::Average(field) => format!("avg_{}", field),
+            Aggregate::Min(field) => format!("min_{}", field),
+            Aggregate::Max(field) => format!("max_{}", field),
+            Aggregate::Percentile(field, p) => format!("p{}_{}", p, field),
+        }
+    }
+
+    // `Null` when no record has a numeric value for the field
+    fn evaluate(&self, records: &[Data]) -> Data {
+        let numbers = |field: &str| -> Vec<f64> {
+            records.iter().filter_map(|record| record.get(field)?.as_f64()).collect()
+        };
+        let result = match self {
+            Aggregate::Count => return Data::Int(records.len() as i32),
+            Aggregate::Sum(field) => return Data::Float(numbers(field).iter().sum()),
+            Aggregate::Average(field) => {
+                let values = numbers(field);
+                (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+            }
+            Aggregate::Min(field) => numbers(field).into_iter().reduce(f64::min),
+            Aggregate::Max(field) => numbers(field).into_iter().reduce(f64::max),
+            Aggregate::Percentile(field, p) => percentile(numbers(field), *p),
+        };
+        result.map_or(Data::Null, Data::Float)
+    }
+}
+
+/// Filter, sort, limit, then optionally group and aggregate
+#[derive(Debug, Clone, Default)]
+pub struct Query {
+    pub conditions: Vec<Condition>,
+    pub sort: Vec<SortKey>,
+    pub limit: Option<usize>,
+    pub group_by: Option<String>,
+    pub aggregates: Vec<Aggregate>,
+}
+
+impl Query {
+    pub fn new() -> Query {
+        Query::default()
+    }
+
+    pub fn filter(mut self, field: &str, operator: Operator, value: Data) -> Query {
+        self.conditions.push(Condition { field: field.to_string(), operator, value });
+        self
+    }
+
+    pub fn sort_by(mut self, field: &str, descending: bool) -> Query {
+        self.sort.push(SortKey { field: field.to_string(), descending });
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Query {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn group_by(mut self, field: &str) -> Query {
+        self.group_by = Some(field.to_string());
+        self
+    }
+
+    pub fn aggregate(mut self, aggregate: Aggregate) -> Query {
+        self.aggregates.push(aggregate);
+        self
+    }
+
+    /// Whether `run` summarizes products rather than listing them
+    pub fn is_aggregate(&self) -> bool {
+        self.group_by.is_some() || !self.aggregates.is_empty()
+    }
+
+    /// The where, sort and limit stages, keeping the products themselves
+    pub fn apply(&self, products: Vec<Product>) -> Vec<Product> {
+        let rows = products.into_iter().map(|product| (product.to_data(), product));
+        self.select(rows).into_iter().map(|(_, product)| product).collect()
+    }
+
+    /// The whole pipeline, as `Data` ready for any of its serializers
+    pub fn run(&self, products: &[Product]) -> Data {
+        let rows = products.iter().map(|product| (product.to_data(), ()));
+        let records: Vec<Data> = self.select(rows).into_iter().map(|(record, _)| record).collect();
+
+        let Some(field) = &self.group_by else {
+            return if self.aggregates.is_empty() { Data::List(records) } else { Data::Map(self.summarize(&records)) };
+        };
+
+        // Groups keep the order in which their first product appears
+        let mut groups: Vec<(String, Vec<Data>)> = Vec::new();
+        for record in records {
+            let key = record.get(field).map(text_of).unwrap_or_default();
+            match groups.iter_mut().find(|(existing, _)| *existing == key) {
+                Some((_, members)) => members.push(record),
+                None => groups.push((key, vec![record])),
+            }
+        }
+
+        Data::List(groups.into_iter().map(|(key, members)| {
+            let mut group = if self.aggregates.is_empty() {
+                let mut map = HashMap::new();
+                map.insert("count".to_string(), Data::Int(members.len() as i32));
+                map.insert("products".to_string(), Data::List(members));
+                map
+            } else {
+                self.summarize(&members)
+            };
+            group.insert(field.clone(), if key.is_empty() { Data::Null } else { Data::Text(key) });
+            Data::Map(group)
+        }).collect())
+    }
+
+    fn select<T>(&self, rows: impl Iterator<Item = (Data, T)>) -> Vec<(Data, T)> {
+        let mut rows: Vec<(Data, T)> = rows
+            .filter(|(record, _)| self.conditions.iter().all(|condition| condition.matches(record)))
+            .collect();
+        rows.sort_by(|(a, _), (b, _)| self.compare_records(a, b));
+        rows.truncate(self.limit.unwrap_or(usize::MAX));
+        rows
+    }
+
+    fn summarize(&self, records: &[Data]) -> HashMap<String, Data> {
+        self.aggregates.iter().map(|aggregate| (aggregate.name(), aggregate.evaluate(records))).collect()
+    }
+
+    fn compare_records(&self, a: &Data, b: &Data) -> Ordering {
+        for key in &self.sort {
+            // Products without the field sort last either way
+            let ordering = match (a.get(&key.field), b.get(&key.field)) {
+                (Some(x), Some(y)) => {
+                    let ordering = compare(x, y).unwrap_or(Ordering::Equal);
+                    if key.descending { ordering.reverse() } else { ordering }
+                }
+                (Some(_), None) => Ordering::Less,
+                (None, Some(_)) => Ordering::Greater,
+                (None, None) => Ordering::Equal,
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    }
+}
+
+// Numbers compare numerically and text case-insensitively; other pairs are incomparable
+fn compare(a: &Data, b: &Data) -> Option<Ordering> {
+    match (a, b) {
+        (Data::Text(x), Data::Text(y)) => Some(x.to_lowercase().cmp(&y.to_lowercase())),
+        (Data::Bool(x), Data::Bool(y)) => Some(x.cmp(y)),
+        _ => a.as_f64()?.partial_cmp(&b.as_f64()?),
+    }
+}
+
+fn text_of(data: &Data) -> String {
+    match data {
+        Data::Text(s) => s.clone(),
+        Data::Int(i) => i.to_string(),
+        Data::Float(f) => f.to_string(),
+        Data::Bool(b) => b.to_string(),
+        _ => String::new(),
+    }
+}
+
+fn percentile(mut values: Vec<f64>, p: f64) -> Option<f64> {
+    if values.is_empty() {
+        return None;
+    }
+    values.sort_by(f64::total_cmp);
+    let rank = p.clamp(0.0, 100.0) / 100.0 * (values.len() - 1) as f64;
+    let (lower, upper) = (rank.floor() as usize, rank.ceil() as usize);
+    Some(values[lower] + (values[upper] - values[lower]) * (rank - lower as f64))
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    Text(String),
+    Number(f64),
+    Operator(Operator),
+    Comma,
+    Pipe,
+}
+
+/// Parse a pipeline of `|`-separated stages, e.g.
+/// `where price >= 1000 and brand = "Apple" | sort price desc | group by brand | count, avg price, p90 price`
+pub fn parse_query(expression: &str) -> Result<Query, Box<dyn Error>> {
+    let tokens = tokenize(expression)?;
+    let mut query = Query::new();
+
+    for stage in tokens.split(|token| *token == Token::Pipe) {
+        let mut stage = Stage { tokens: stage, position: 0 };
+        match stage.peek_word().as_deref() {
+            Some("where") => {
+                stage.position += 1;
+                loop {
+                    let field = stage.word("field name")?;
+                    let operator = match stage.next() {
+                        Some(Token::Operator(operator)) => *operator,
+                        _ => return Err(format!("Expected an operator after '{}'", field).into()),
+                    };
+                    let value = match stage.next() {
+                        Some(Token::Number(n)) => Data::Float(*n),
+                        Some(Token::Text(s) | Token::Word(s)) => Data::Text(s.clone()),
+                        _ => return Err(format!("Expected a value after '{}'", field).into()),
+                    };
+                    query = query.filter(&field, operator, value);
+                    if !stage.keyword("and") {
+                        break;
+                    }
+                }
+            }
+            Some("sort" | "order") => {
+                stage.position += 1;
+                stage.keyword("by");
+                loop {
+                    let field = stage.word("sort field")?;
+                    let descending = stage.keyword("desc");
+                    if !descending {
+                        stage.keyword("asc");
+                    }
+                    query = query.sort_by(&field, descending);
+                    if !stage.comma() {
+                        break;
+                    }
+                }
+            }
+            Some("group") => {
+                stage.position += 1;
+                stage.keyword("by");
+                query = query.group_by(&stage.word("group field")?);
+            }
+            Some("limit") => {
+                stage.position += 1;
+                match stage.next() {
+                    Some(Token::Number(n)) if *n >= 0.0 && n.fract() == 0.0 => query = query.limit(*n as usize),
+                    _ => return Err("Expected a whole number after 'limit'".into()),
+                }
+            }
+            Some(_) => loop {
+                query = query.aggregate(stage.aggregate()?);
+                if !stage.comma() {
+                    break;
+                }
+            },
+            None => return Err("Empty query stage".into()),
+        }
+        if let Some(token) = stage.next() {
+            return Err(format!("Unexpected {:?} in query", token).into());
+        }
+    }
+
+    Ok(query)
+}
+
+struct Stage<'a> {
+    tokens: &'a [Token],
+    position: usize,
+}
+
+impl Stage<'_> {
+    fn next(&mut self) -> Option<&Token> {
+        let token = self.tokens.get(self.position);
+        self.position += 1;
+        token
+    }
+
+    fn peek_word(&self) -> Option<String> {
+        match self.tokens.get(self.position) {
+            Some(Token::Word(word)) => Some(word.to_lowercase()),
+            _ => None,
+        }
+    }
+
+    fn word(&mut self, what: &str) -> Result<String, Box<dyn Error>> {
+        match self.next() {
+            Some(Token::Word(word)) => Ok(word.clone()),
+            _ => Err(format!("Expected a {}", what).into()),
+        }
+    }
+
+    // Consume `keyword` if it comes next
+    fn keyword(&mut self, keyword: &str) -> bool {
+        let found = self.peek_word().as_deref() == Some(keyword);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    fn comma(&mut self) -> bool {
+        let found = self.tokens.get(self.position) == Some(&Token::Comma);
+        if found {
+            self.position += 1;
+        }
+        found
+    }
+
+    // `count`, `sum price`, `avg price`, `min price`, `max price`, `median price`, `p90 price`, `percentile 90 price`
+    fn aggregate(&mut self) -> Result<Aggregate, Box<dyn Error>> {
+        let function = self.word("aggregate")?.to_lowercase();
+        let aggregate = match function.as_str() {
+            "count" => Aggregate::Count,
+            "sum" => Aggregate::Sum(self.word("field after 'sum'")?),
+            "avg" | "average" | "mean" => Aggregate::Average(self.word("field after 'avg'")?),
+            "min" => Aggregate::Min(self.word("field after 'min'")?),
+            "max" => Aggregate::Max(self.word("field after 'max'")?),
+            "median" => Aggregate::Percentile(self.word("field after 'median'")?, 50.0),
+            "percentile" => {
+                let p = match self.next() {
+                    Some(Token::Number(p)) => *p,
+                    _ => return Err("Expected a number after 'percentile'".into()),
+                };
+                Aggregate::Percentile(self.word("field after the percentile")?, p)
+            }
+            _ => match function.strip_prefix('p').and_then(|p| p.parse::<f64>().ok()) {
+                Some(p) => Aggregate::Percentile(self.word("field after the percentile")?, p),
+                None => return Err(format!("Unknown query stage or aggregate: '{}'", function).into()),
+            },
+        };
+        if let Aggregate::Percentile(_, p) = &aggregate {
+            if !(0.0..=100.0).contains(p) {
+                return Err(format!("Percentile {} is outside 0-100", p).into());
+            }
+        }
+        Ok(aggregate)
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>, Box<dyn Error>> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '|' => {
+                chars.next();
+                tokens.push(Token::Pipe);
+            }
+            ',' => {
+                chars.next();
+                tokens.push(Token::Comma);
+            }
+            '"' | '\'' => {
+                chars.next();
+                let mut text = String::new();
+                loop {
+                    match chars.next() {
+                        Some(next) if next == c => break,
+                        Some(next) => text.push(next),
+                        None => return Err(format!("Unterminated string in query: {}{}", c, text).into()),
+                    }
+                }
+                tokens.push(Token::Text(text));
+            }
+            '=' | '!' | '<' | '>' | '~' => {
+                chars.next();
+                let followed_by_eq = chars.next_if_eq(&'=').is_some();
+                let operator = match (c, followed_by_eq) {
+                    ('=', _) => Operator::Eq,
+                    ('!', true) => Operator::Ne,
+                    ('<', false) => Operator::Lt,
+                    ('<', true) => Operator::Le,
+                    ('>', false) => Operator::Gt,
+                    ('>', true) => Operator::Ge,
+                    ('~', false) => Operator::Contains,
+                    _ => return Err(format!("Unknown operator starting with '{}'", c).into()),
+                };
+                tokens.push(Token::Operator(operator));
+            }
+            _ => {
+                let mut word = String::new();
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || "|,\"'=!<>~".contains(next) {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+                tokens.push(match word.parse::<f64>() {
+                    Ok(number) => Token::Number(number),
+                    Err(_) => Token::Word(word),
+                });
+            }
+        }
+    }
+
+    Ok(tokens)
+}
@@ -1,67 +1,147 @@
-use std::io::{Read, Write};
-use std::net::TcpStream;
+//! Crawling a shop's listing and product pages into validated products.
+
 use std::error::Error;
-use std::str;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
 use url::Url;
 use select::document::Document;
 use select::predicate::{Name, Class};
-use native_tls::TlsConnector;
-use crate::product::Product;
-use crate::validation::{validate_product_name, validate_price, convert_price_to_numeric};
-
-// Trait to abstract over different types of streams
-trait StreamIO {
-    fn read_response(&mut self) -> Result<String, Box<dyn Error>>;
-    fn write_request(&mut self, request: &str) -> Result<(), Box<dyn Error>>;
-}
-
-// Implementation for TLS streams
-impl StreamIO for native_tls::TlsStream<TcpStream> {
-    fn read_response(&mut self) -> Result<String, Box<dyn Error>> {
-        let mut buffer = Vec::new();
-        self.read_to_end(&mut buffer)?;
-        Ok(String::from_utf8(buffer)?)
+use crate::http::{Client, Limits};
+use crate::normalize::{deduplicate, ConflictPolicy};
+use crate::price::NumberLocale;
+use crate::product::{Product, ProductImage};
+use crate::query::Query;
+use crate::sitemap::{collect_urls, discover_sitemaps, SitemapFilter};
+use crate::structured::{extract_products, StructuredProduct};
+use crate::validation::{parse_price, validate_amount, validate_link, validate_name, Rejection, Violation};
+
+// Product pages (and their images) fetched at once; over HTTP/2 they share one connection
+const MAX_CONCURRENT_FETCHES: usize = 6;
+
+// Images larger than this are skipped instead of being attached to the product
+const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+
+/// Products that passed validation, and the ones that did not with the reasons why
+#[derive(Debug, Default)]
+pub struct ScrapeResult {
+    pub products: Vec<Product>,
+    pub rejected: Vec<Rejection>,
+}
+
+/// Where a [`Scraper`] finds product pages
+#[derive(Debug, Clone)]
+enum Source {
+    Listing,
+    Sitemaps { urls: Vec<String>, filter: SitemapFilter },
+}
+
+/// Scrapes one shop into validated, deduplicated products.
+///
+/// By default the listing page at the scraper's URL is crawled and every valid product is kept;
+/// the builder methods switch to sitemaps, pick how duplicates merge and filter the result.
+#[derive(Debug, Clone)]
+pub struct Scraper {
+    client: Client,
+    url: String,
+    source: Source,
+    conflict_policy: ConflictPolicy,
+    query: Query,
+}
+
+/// Outcome of [`Scraper::scrape`]
+#[derive(Debug, Default)]
+pub struct ScrapeReport {
+    /// Products left after merging duplicates and applying the query
+    pub products: Vec<Product>,
+    pub rejected: Vec<Rejection>,
+    /// Products found on the site, valid or not
+    pub scraped: usize,
+    /// Duplicates merged into a product listed earlier
+    pub merged: usize,
+}
+
+impl Scraper {
+    pub fn new(client: Client, url: &str) -> Scraper {
+        Scraper {
+            client,
+            url: url.to_string(),
+            source: Source::Listing,
+            conflict_policy: ConflictPolicy::default(),
+            query: Query::new(),
+        }
     }
 
-    fn write_request(&mut self, request: &str) -> Result<(), Box<dyn Error>> {
-        self.write_all(request.as_bytes())?;
-        Ok(())
+    /// Read product pages from sitemaps instead of the listing page. Without `sitemap_urls` they
+    /// are discovered through the site's robots.txt, falling back to `/sitemap.xml`.
+    pub fn sitemaps(mut self, sitemap_urls: Vec<String>, filter: SitemapFilter) -> Scraper {
+        self.source = Source::Sitemaps { urls: sitemap_urls, filter };
+        self
+    }
+
+    pub fn conflict_policy(mut self, policy: ConflictPolicy) -> Scraper {
+        self.conflict_policy = policy;
+        self
+    }
+
+    /// Only the filtering, sorting and limit of `query` apply; aggregates are left to the caller
+    pub fn query(mut self, query: Query) -> Scraper {
+        self.query = query;
+        self
     }
-}
 
-// Implementation for regular TCP streams
-impl StreamIO for TcpStream {
-    fn read_response(&mut self) -> Result<String, Box<dyn Error>> {
-        let mut buffer = Vec::new();
-        self.read_to_end(&mut buffer)?;
-        Ok(String::from_utf8(buffer)?)
+    pub fn client(&self) -> &Client {
+        &self.client
     }
 
-    fn write_request(&mut self, request: &str) -> Result<(), Box<dyn Error>> {
-        self.write_all(request.as_bytes())?;
-        Ok(())
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    pub fn scrape(&self) -> Result<ScrapeReport, Box<dyn Error>> {
+        let scraped = match &self.source {
+            Source::Listing => scrape_products(&self.client, &self.url)?,
+            Source::Sitemaps { urls, filter } => {
+                let sitemap_urls = if urls.is_empty() { discover_sitemaps(&self.client, &self.url)? } else { urls.clone() };
+                let entries = collect_urls(&self.client, &sitemap_urls, filter)?;
+                let urls: Vec<String> = entries.into_iter().map(|entry| entry.loc).collect();
+                scrape_product_pages(&self.client, &urls)
+            }
+        };
+
+        // Canonicalize links and names, then merge the same product listed more than once
+        let listed = scraped.products.len();
+        let unique_products = deduplicate(scraped.products, self.conflict_policy);
+        Ok(ScrapeReport {
+            scraped: listed + scraped.rejected.len(),
+            merged: listed - unique_products.len(),
+            products: self.query.apply(unique_products),
+            rejected: scraped.rejected,
+        })
     }
 }
 
-pub fn scrape_products(initial_url: &str) -> Result<Vec<Product>, Box<dyn Error>> {
+pub fn scrape_products(client: &Client, initial_url: &str) -> Result<ScrapeResult, Box<dyn Error>> {
     let mut current_url = initial_url.to_string();
     let mut redirect_count = 0;
     const MAX_REDIRECTS: u8 = 5;
 
     while redirect_count < MAX_REDIRECTS {
-        match fetch_url(&current_url) {
-            Ok((status, headers, body)) => {
-                if status >= 300 && status < 400 {
-                    if let Some(location) = headers.iter()
-                        .find(|line| line.to_lowercase().starts_with("location:"))
-                        .and_then(|line| line.split(": ").nth(1)) {
-                        current_url = location.trim().to_string();
+        match client.fetch(&current_url, &Limits::default()) {
+            Ok(response) => {
+                let status = response.status;
+                if (300..400).contains(&status) {
+                    if let Some(location) = response.header("location") {
+                        current_url = resolve_url(&current_url, location)?;
                         redirect_count += 1;
                         println!("Following redirect ({}) to: {}", status, current_url);
                         continue;
                     }
                 }
-                return parse_products(&body);
+                if status >= 400 {
+                    return Err(format!("Failed to fetch listing (status {})", status).into());
+                }
+                return parse_products(client, &response.text(), &current_url);
             }
             Err(e) => {
                 eprintln!("Error fetching URL {}: {}", current_url, e);
@@ -73,14 +153,39 @@ pub fn scrape_products(initial_url: &str) -> Result<Vec<Product>, Box<dyn Error>
     Err("Too many redirects".into())
 }
 
-fn scrape_product_details(product_link: &str) -> Result<String, Box<dyn Error>> {
-    let (status, _, body) = fetch_url(product_link)?;
+// A product card on a listing page
+struct ListingEntry {
+    name: String,
+    price: String,
+    link: Option<String>,
+    image_url: Option<String>,
+}
+
+// What a product page adds to a listing entry
+struct ProductDetails {
+    attributes: String,
+    structured: Option<StructuredProduct>,
+    // The page's own CSS fields, used when a product is scraped without a listing entry
+    css: CssFields,
+}
+
+// Product fields read with the shop's CSS rules
+struct CssFields {
+    name: String,
+    price: String,
+    image_url: Option<String>,
+    // Language of the page, which decides how `price` is read
+    locale: Option<NumberLocale>,
+}
+
+fn scrape_product_details(client: &Client, product_link: &str) -> Result<ProductDetails, Box<dyn Error>> {
+    let response = client.fetch(product_link, &Limits::default())?;
     
-    if status != 200 {
-        return Err("Failed to fetch product details".into());
+    if response.status != 200 {
+        return Err(format!("Failed to fetch product details (status {})", response.status).into());
     }
 
-    let document = Document::from(body.as_str());
+    let document = Document::from(response.text().as_str());
     
     // Extract the product attributes
     let attributes = document.find(Class("xp-attr"))
@@ -88,74 +193,131 @@ fn scrape_product_details(product_link: &str) -> Result<String, Box<dyn Error>>
         .map(|n| n.text())
         .unwrap_or_else(|| "Attributes not found".to_string());
 
-    Ok(attributes)
-}
-
-
-
-fn fetch_url(url: &str) -> Result<(u32, Vec<String>, String), Box<dyn Error>> {
-    let parsed_url = Url::parse(url)?;
-    let host = parsed_url.host_str().ok_or("Invalid host")?;
-    let port = if parsed_url.scheme() == "https" { 443 } else { 80 };
-    let path = if parsed_url.path().is_empty() { "/" } else { parsed_url.path() };
-
-    let request = format!(
-        "GET {} HTTP/1.1\r\n\
-        Host: {}\r\n\
-        User-Agent: Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36\r\n\
-        Accept: text/html,application/xhtml+xml,application/xml;q=0.9,*/*;q=0.8\r\n\
-        Connection: close\r\n\r\n",
-        path,
-        host
-    );
-
-    let response = if parsed_url.scheme() == "https" {
-        let connector = TlsConnector::new()?;
-        let tcp_stream = TcpStream::connect(format!("{}:{}", host, port))?;
-        tcp_stream.set_read_timeout(Some(std::time::Duration::from_secs(10)))?;
-        let mut tls_stream = connector.connect(host, tcp_stream)?;
-        tls_stream.write_request(&request)?;
-        tls_stream.read_response()?
-    } else {
-        let mut stream = TcpStream::connect(format!("{}:{}", host, port))?;
-        stream.set_read_timeout(Some(std::time::Duration::from_secs(10)))?;
-        stream.write_request(&request)?;
-        stream.read_response()?
+    // A product page describes one product; take the first schema.org Product it contains
+    let structured = extract_products(&document).into_iter().next();
+
+    let css = CssFields {
+        name: document.find(Name("h1"))
+            .next()
+            .map(|n| n.text().trim().to_string())
+            .unwrap_or_else(|| "Product name not found".to_string()),
+        price: document.find(Class("xprice"))
+            .next()
+            .map(|n| n.text())
+            .unwrap_or_else(|| "Price not found".to_string()),
+        image_url: document.find(Name("meta"))
+            .find(|n| n.attr("property") == Some("og:image"))
+            .and_then(|n| n.attr("content"))
+            .and_then(|src| resolve_url(product_link, src.trim()).ok()),
+        locale: page_locale(&document),
     };
 
-    // Split headers and body
-    let parts: Vec<&str> = response.split("\r\n\r\n").collect();
-    if parts.len() < 2 {
-        return Err("Invalid response format".into());
+    Ok(ProductDetails { attributes, structured, css })
+}
+
+/// Scrape product pages directly, e.g. URLs discovered through a sitemap
+pub fn scrape_product_pages(client: &Client, urls: &[String]) -> ScrapeResult {
+    let mut result = ScrapeResult::default();
+
+    let outcomes = fetch_concurrently(urls, |url| match scrape_product_details(client, url) {
+        Ok(ProductDetails { attributes, structured, css }) => {
+            let structured = structured.unwrap_or_default();
+            assemble_product(client, url, Some(url.clone()), css, structured, Some(attributes), None)
+        }
+        Err(e) => Err(Rejection {
+            name: String::new(),
+            link: Some(url.clone()),
+            violations: vec![Violation::new("link", "unreachable", e.to_string())],
+        }),
+    });
+    for outcome in outcomes {
+        match outcome {
+            Ok(product) => result.products.push(product),
+            Err(rejection) => result.rejected.push(rejection),
+        }
     }
 
-    let headers: Vec<String> = parts[0]
-        .lines()
-        .map(|s| s.to_string())
-        .collect();
+    println!("Scraped {} products from {} product pages", result.products.len(), urls.len());
+    result
+}
+
+// Run `fetch` on every item from a few threads at once, returning the results in item order
+fn fetch_concurrently<T: Sync, R: Send>(items: &[T], fetch: impl Fn(&T) -> R + Sync) -> Vec<R> {
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<R>>> = Mutex::new(items.iter().map(|_| None).collect());
+
+    thread::scope(|scope| {
+        for _ in 0..MAX_CONCURRENT_FETCHES.min(items.len()) {
+            scope.spawn(|| loop {
+                let index = next.fetch_add(1, Ordering::Relaxed);
+                let Some(item) = items.get(index) else {
+                    break;
+                };
+                let result = fetch(item);
+                results.lock().unwrap()[index] = Some(result);
+            });
+        }
+    });
+
+    results.into_inner().unwrap().into_iter().map(|result| result.expect("every item was fetched")).collect()
+}
+
+// Download a product image, keeping the raw bytes untouched
+fn download_image(client: &Client, image_url: &str) -> Result<ProductImage, Box<dyn Error>> {
+    // The body cap is enforced while streaming, so oversized images are never fully buffered
+    let limits = Limits { max_body_bytes: MAX_IMAGE_BYTES, ..Limits::default() };
+    let response = client.fetch(image_url, &limits)?;
+
+    if response.status != 200 {
+        return Err(format!("Failed to fetch image (status {})", response.status).into());
+    }
+
+    let content_type = response.content_type().unwrap_or_default();
+    if !content_type.starts_with("image/") {
+        return Err(format!("Unexpected image content type: {:?}", content_type).into());
+    }
 
-    // Parse status code
-    let status_line = headers.first().ok_or("No status line")?;
-    let status_code = status_line
-        .split_whitespace()
-        .nth(1)
-        .ok_or("No status code")?
-        .parse::<u32>()?;
+    Ok(ProductImage {
+        content_type,
+        data: response.body,
+    })
+}
 
-    Ok((status_code, headers, parts[1].to_string()))
+// Number format implied by `<html lang>` or a Content-Language meta tag
+fn page_locale(document: &Document) -> Option<NumberLocale> {
+    document.find(Name("html"))
+        .find_map(|n| n.attr("lang"))
+        .or_else(|| {
+            document.find(Name("meta"))
+                .find(|n| n.attr("http-equiv").is_some_and(|h| h.eq_ignore_ascii_case("content-language")))
+                .and_then(|n| n.attr("content"))
+        })
+        .and_then(NumberLocale::from_language_tag)
 }
 
-fn parse_products(body: &str) -> Result<Vec<Product>, Box<dyn Error>> {
+// Resolve a possibly relative link against the page it was found on
+fn resolve_url(base_url: &str, link: &str) -> Result<String, Box<dyn Error>> {
+    Ok(Url::parse(base_url)?.join(link)?.to_string())
+}
+
+pub fn parse_products(client: &Client, body: &str, page_url: &str) -> Result<ScrapeResult, Box<dyn Error>> {
     let document = Document::from(body);
-    let mut products = Vec::new();
+    let mut result = ScrapeResult::default();
+
+    // Listing pages sometimes carry structured data for every product they show
+    let listing_data: Vec<(String, StructuredProduct)> = extract_products(&document)
+        .into_iter()
+        .filter_map(|p| Some((resolve_url(page_url, p.url.as_deref()?).ok()?, p)))
+        .collect();
 
+    let locale = page_locale(&document);
     let product_nodes: Vec<_> = document.find(Name("figure")).collect();
     println!("Found {} product nodes", product_nodes.len());
 
-    for node in product_nodes {
-        let product_name = node.find(Name("a"))
-            .filter(|n| n.attr("class").map_or(false, |c| c.contains("xp-title")))
-            .next()
+    // Read every card first: the document cannot leave this thread, the fetches below do
+    let entries: Vec<ListingEntry> = product_nodes.into_iter().map(|node| {
+        let name = node.find(Name("a"))
+            .find(|n| n.attr("class").is_some_and(|c| c.contains("xp-title")))
             .map(|n| n.text())
             .unwrap_or_else(|| "Product name not found".to_string());
 
@@ -164,28 +326,113 @@ fn parse_products(body: &str) -> Result<Vec<Product>, Box<dyn Error>> {
             .map(|n| n.text())
             .unwrap_or_else(|| "Price not found".to_string());
 
-        let product_link = node.find(Name("a"))
-            .filter(|n| n.attr("class").map_or(false, |c| c.contains("xp-title")))
-            .next()
+        let link = node.find(Name("a"))
+            .find(|n| n.attr("class").is_some_and(|c| c.contains("xp-title")))
             .and_then(|n| n.attr("href"))
-            .unwrap_or("Link not found");
-
-            if validate_product_name(&product_name) && validate_price(&price) {
-                if let Ok(numeric_price) = convert_price_to_numeric(&price) {
-                    // Fetch description from the product link
-                    let attributes = scrape_product_details(product_link)
-                        .unwrap_or_else(|_| "Attributes not found".to_string());
-            
-                    products.push(Product {
-                        name: product_name,
-                        price: numeric_price,
-                        link: product_link.to_string(),
-                        description: attributes, // This is now of type Option<String>
-                    });
-                }
-            }
-            
+            .and_then(|href| resolve_url(page_url, href.trim()).ok());
+
+        // Lazy-loaded images keep the real URL in `data-src`
+        let image_url = node.find(Name("img"))
+            .next()
+            .and_then(|n| n.attr("data-src").or_else(|| n.attr("src")))
+            .filter(|src| !src.trim().is_empty() && !src.starts_with("data:"))
+            .and_then(|src| resolve_url(page_url, src.trim()).ok());
+
+        ListingEntry { name, price, link, image_url }
+    }).collect();
+
+    let outcomes = fetch_concurrently(&entries, |entry| {
+        // Fetch description and structured data from the product link
+        let details = entry.link.as_deref().map(|link| scrape_product_details(client, link));
+        let listed = entry.link.as_ref()
+            .and_then(|link| listing_data.iter().find(|(url, _)| url == link))
+            .map(|(_, p)| p.clone());
+
+        // Relative URLs in structured data are relative to the page it was found on
+        let (attributes, structured, structured_base, link_error) = match details {
+            Some(Ok(details)) => match details.structured {
+                Some(structured) => (Some(details.attributes), Some(structured), entry.link.as_deref(), None),
+                None => (Some(details.attributes), listed, None, None),
+            },
+            Some(Err(e)) => (None, listed, None, Some(e.to_string())),
+            None => (None, listed, None, None),
+        };
+        let css = CssFields {
+            name: entry.name.clone(),
+            price: entry.price.clone(),
+            image_url: entry.image_url.clone(),
+            locale,
+        };
+
+        let structured = structured.unwrap_or_default();
+        let structured_base = structured_base.unwrap_or(page_url);
+        assemble_product(client, structured_base, entry.link.clone(), css, structured, attributes, link_error)
+    });
+    for outcome in outcomes {
+        match outcome {
+            Ok(product) => result.products.push(product),
+            Err(rejection) => result.rejected.push(rejection),
+        }
     }
 
-    Ok(products)
+    Ok(result)
+}
+
+// Structured data wins field by field; the CSS rules fill whatever it leaves out.
+// `structured_base` is the URL of the page the structured data came from, and `link_error`
+// is why the product page could not be fetched, if it could not.
+fn assemble_product(
+    client: &Client,
+    structured_base: &str,
+    product_link: Option<String>,
+    css: CssFields,
+    structured: StructuredProduct,
+    attributes: Option<String>,
+    link_error: Option<String>,
+) -> Result<Product, Rejection> {
+    let name = structured.name.clone().unwrap_or(css.name);
+    let mut violations = validate_name(&name);
+
+    let numeric_price = match structured.price_mdl() {
+        Some(structured_price) => Ok(structured_price),
+        None => parse_price(&css.price, css.locale),
+    };
+    match &numeric_price {
+        Ok(amount) => violations.extend(validate_amount(*amount)),
+        Err(violation) => violations.push(violation.clone()),
+    }
+
+    let link_violations = validate_link(product_link.as_deref());
+    match link_error {
+        Some(error) if link_violations.is_empty() => violations.push(Violation::new("link", "unreachable", error)),
+        _ => violations.extend(link_violations),
+    }
+
+    let numeric_price = match numeric_price {
+        Ok(amount) if violations.is_empty() => amount,
+        _ => return Err(Rejection { name, link: product_link, violations }),
+    };
+
+    let attributes = attributes.unwrap_or_else(|| "Attributes not found".to_string());
+
+    let image_url = structured.image.as_deref()
+        .and_then(|src| resolve_url(structured_base, src).ok())
+        .or(css.image_url);
+    let image = image_url.as_deref().and_then(|url| {
+        download_image(client, url)
+            .map_err(|e| eprintln!("Error downloading image {}: {}", url, e))
+            .ok()
+    });
+
+    Ok(Product {
+        name,
+        price: numeric_price,
+        link: product_link.unwrap_or_default(),
+        description: attributes, // This is now of type Option<String>
+        image_url,
+        image,
+        sku: structured.sku,
+        brand: structured.brand,
+        availability: structured.availability,
+    })
 }
@@ -0,0 +1,232 @@
+//! Product page discovery through robots.txt, sitemaps and sitemap indexes.
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::io::Read;
+use chrono::{DateTime, NaiveDate, Utc};
+use flate2::read::GzDecoder;
+use url::Url;
+use crate::http::{Client, Limits};
+use crate::xml::{local_name, parse_xml, XmlEvent};
+
+// Sitemaps may hold 50,000 URLs and 50 MB uncompressed (sitemaps.org protocol limits)
+const MAX_SITEMAP_BYTES: usize = 50 * 1024 * 1024;
+// Guards against index files that point at each other
+const MAX_INDEX_DEPTH: usize = 3;
+
+#[derive(Debug, Clone)]
+pub struct SitemapEntry {
+    pub loc: String,
+    pub lastmod: Option<DateTime<Utc>>,
+}
+
+/// Which sitemap URLs to keep
+#[derive(Debug, Clone, Default)]
+pub struct SitemapFilter {
+    /// Glob over the full URL, `*` matching any run of characters, e.g. `*/product/*`
+    pub pattern: Option<String>,
+    /// Only URLs modified on or after this instant; entries without `lastmod` are kept
+    pub since: Option<DateTime<Utc>>,
+    pub limit: Option<usize>,
+}
+
+impl SitemapFilter {
+    fn accepts(&self, entry: &SitemapEntry) -> bool {
+        self.pattern.as_deref().is_none_or(|pattern| glob_matches(pattern, &entry.loc))
+            && self.is_recent(entry)
+    }
+
+    fn is_recent(&self, entry: &SitemapEntry) -> bool {
+        match (self.since, entry.lastmod) {
+            (Some(since), Some(lastmod)) => lastmod >= since,
+            _ => true,
+        }
+    }
+}
+
+enum Sitemap {
+    Index(Vec<SitemapEntry>),
+    UrlSet(Vec<SitemapEntry>),
+}
+
+/// Sitemap URLs announced in robots.txt, falling back to the conventional /sitemap.xml
+pub fn discover_sitemaps(client: &Client, site_url: &str) -> Result<Vec<String>, Box<dyn Error>> {
+    let robots_url = Url::parse(site_url)?.join("/robots.txt")?;
+    let mut sitemaps = Vec::new();
+
+    match client.fetch(robots_url.as_str(), &Limits::default()) {
+        Ok(response) if response.status == 200 => {
+            for line in response.text().lines() {
+                if let Some((key, value)) = line.split_once(':') {
+                    if key.trim().eq_ignore_ascii_case("sitemap") && !value.trim().is_empty() {
+                        sitemaps.push(robots_url.join(value.trim())?.to_string());
+                    }
+                }
+            }
+        }
+        Ok(response) => println!("No robots.txt at {} (status {})", robots_url, response.status),
+        Err(e) => eprintln!("Error fetching {}: {}", robots_url, e),
+    }
+
+    if sitemaps.is_empty() {
+        sitemaps.push(robots_url.join("/sitemap.xml")?.to_string());
+    }
+    Ok(sitemaps)
+}
+
+/// Expand sitemap indexes and collect the page URLs that pass the filter
+pub fn collect_urls(
+    client: &Client,
+    sitemap_urls: &[String],
+    filter: &SitemapFilter,
+) -> Result<Vec<SitemapEntry>, Box<dyn Error>> {
+    let mut entries = Vec::new();
+    let mut seen_sitemaps = HashSet::new();
+    let mut seen_pages = HashSet::new();
+    let mut queue: Vec<(String, usize)> = sitemap_urls.iter().rev().map(|url| (url.clone(), 0)).collect();
+
+    while let Some((sitemap_url, depth)) = queue.pop() {
+        if filter.limit.is_some_and(|limit| entries.len() >= limit) {
+            break;
+        }
+        if !seen_sitemaps.insert(sitemap_url.clone()) {
+            continue;
+        }
+
+        let sitemap = match fetch_sitemap(client, &sitemap_url) {
+            Ok(sitemap) => sitemap,
+            Err(e) => {
+                eprintln!("Error reading sitemap {}: {}", sitemap_url, e);
+                continue;
+            }
+        };
+
+        match sitemap {
+            Sitemap::Index(children) if depth < MAX_INDEX_DEPTH => {
+                println!("Sitemap index {} lists {} sitemaps", sitemap_url, children.len());
+                // A child sitemap last modified before `since` cannot contain newer URLs
+                for child in children.into_iter().rev().filter(|child| filter.is_recent(child)) {
+                    queue.push((child.loc, depth + 1));
+                }
+            }
+            Sitemap::Index(_) => eprintln!("Skipping sitemap index {}: nested too deeply", sitemap_url),
+            Sitemap::UrlSet(pages) => {
+                println!("Sitemap {} lists {} URLs", sitemap_url, pages.len());
+                for page in pages {
+                    if filter.limit.is_some_and(|limit| entries.len() >= limit) {
+                        break;
+                    }
+                    if filter.accepts(&page) && seen_pages.insert(page.loc.clone()) {
+                        entries.push(page);
+                    }
+                }
+            }
+        }
+    }
+
+    Ok(entries)
+}
+
+fn fetch_sitemap(client: &Client, sitemap_url: &str) -> Result<Sitemap, Box<dyn Error>> {
+    let limits = Limits { max_body_bytes: MAX_SITEMAP_BYTES, ..Limits::default() };
+    let response = client.fetch(sitemap_url, &limits)?;
+    if response.status != 200 {
+        return Err(format!("Status {}", response.status).into());
+    }
+
+    // Gzipped sitemaps are served as-is (`.xml.gz`), not with `Content-Encoding`
+    let body = if response.body.starts_with(&[0x1f, 0x8b]) {
+        let mut decompressed = Vec::new();
+        GzDecoder::new(response.body.as_slice())
+            .take(MAX_SITEMAP_BYTES as u64 + 1)
+            .read_to_end(&mut decompressed)?;
+        if decompressed.len() > MAX_SITEMAP_BYTES {
+            return Err(format!("Sitemap exceeds {} bytes once decompressed", MAX_SITEMAP_BYTES).into());
+        }
+        String::from_utf8(decompressed)?
+    } else {
+        response.text()
+    };
+
+    parse_sitemap(&body)
+}
+
+fn parse_sitemap(xml: &str) -> Result<Sitemap, Box<dyn Error>> {
+    let mut root: Option<String> = None;
+    let mut entries = Vec::new();
+    let mut current: Option<SitemapEntry> = None;
+    let mut field: Option<String> = None;
+
+    for event in parse_xml(xml)? {
+        match event {
+            XmlEvent::Start { name, .. } => {
+                let name = local_name(&name).to_string();
+                match name.as_str() {
+                    "sitemapindex" | "urlset" if root.is_none() => root = Some(name),
+                    "sitemap" | "url" => current = Some(SitemapEntry { loc: String::new(), lastmod: None }),
+                    "loc" | "lastmod" => field = Some(name),
+                    _ => {}
+                }
+            }
+            XmlEvent::Text(text) => {
+                if let (Some(entry), Some(field)) = (current.as_mut(), field.as_deref()) {
+                    match field {
+                        "loc" => entry.loc.push_str(text.trim()),
+                        _ => entry.lastmod = parse_lastmod(text.trim()),
+                    }
+                }
+            }
+            XmlEvent::End { name } => match local_name(&name) {
+                "sitemap" | "url" => {
+                    if let Some(entry) = current.take().filter(|entry| !entry.loc.is_empty()) {
+                        entries.push(entry);
+                    }
+                }
+                "loc" | "lastmod" => field = None,
+                _ => {}
+            },
+        }
+    }
+
+    match root.as_deref() {
+        Some("sitemapindex") => Ok(Sitemap::Index(entries)),
+        Some("urlset") => Ok(Sitemap::UrlSet(entries)),
+        _ => Err("Not a sitemap: expected <urlset> or <sitemapindex>".into()),
+    }
+}
+
+/// W3C datetime as used by sitemaps: `2024-05-01`, `2024-05-01T10:00:00+03:00`, `2024-05-01T10:00Z`
+pub fn parse_lastmod(value: &str) -> Option<DateTime<Utc>> {
+    if let Ok(date) = DateTime::parse_from_rfc3339(value) {
+        return Some(date.with_timezone(&Utc));
+    }
+    if let Ok(date) = DateTime::parse_from_str(&value.replace('Z', "+00:00"), "%Y-%m-%dT%H:%M%:z") {
+        return Some(date.with_timezone(&Utc));
+    }
+    let date = NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .or_else(|_| NaiveDate::parse_from_str(&format!("{}-01", value), "%Y-%m-%d"))
+        .ok()?;
+    Some(date.and_hms_opt(0, 0, 0)?.and_utc())
+}
+
+/// Match `text` against a pattern where `*` stands for any run of characters
+pub fn glob_matches(pattern: &str, text: &str) -> bool {
+    let parts: Vec<&str> = pattern.split('*').collect();
+    if parts.len() == 1 {
+        return pattern == text;
+    }
+
+    let (first, last) = (parts[0], parts[parts.len() - 1]);
+    if !text.starts_with(first) || text.len() < first.len() + last.len() || !text.ends_with(last) {
+        return false;
+    }
+
+    let mut rest = &text[first.len()..text.len() - last.len()];
+    for part in &parts[1..parts.len() - 1] {
+        match rest.find(part) {
+            Some(index) => rest = &rest[index + part.len()..],
+            None => return false,
+        }
+    }
+    true
+}
@@ -0,0 +1,236 @@
+//! schema.org product data embedded in pages as JSON-LD or microdata.
+
+use select::document::Document;
+use select::node::Node;
+use select::predicate::{Attr, Name};
+use crate::data::Data;
+use crate::json::parse_json;
+use crate::price::{parse_price, to_mdl, NumberLocale};
+
+/// schema.org `Product` fields found in JSON-LD or microdata markup
+#[derive(Debug, Clone, Default)]
+pub struct StructuredProduct {
+    pub name: Option<String>,
+    pub price: Option<f64>,
+    pub currency: Option<String>,
+    /// Last segment of the schema.org URL, e.g. `InStock`
+    pub availability: Option<String>,
+    pub sku: Option<String>,
+    pub brand: Option<String>,
+    pub image: Option<String>,
+    pub url: Option<String>,
+}
+
+impl StructuredProduct {
+    /// Price converted to MDL, or `None` when there is no price or no known rate for its currency
+    pub fn price_mdl(&self) -> Option<f64> {
+        to_mdl(self.price?, self.currency.as_deref())
+    }
+}
+
+/// All products described by JSON-LD scripts and microdata on a page, JSON-LD first
+pub fn extract_products(document: &Document) -> Vec<StructuredProduct> {
+    let mut products = Vec::new();
+
+    for script in document.find(Name("script")) {
+        let is_json_ld = script.attr("type")
+            .is_some_and(|t| t.trim().eq_ignore_ascii_case("application/ld+json"));
+        if !is_json_ld {
+            continue;
+        }
+
+        // Some sites still wrap inline scripts in HTML comments or CDATA markers
+        let text = script.text();
+        let text = text.trim()
+            .trim_start_matches("<!--").trim_end_matches("-->")
+            .trim().trim_start_matches("//<![CDATA[").trim_end_matches("//]]>");
+        match parse_json(text) {
+            Ok(data) => collect_json_ld(&data, &mut products, 0),
+            Err(e) => eprintln!("Skipping malformed JSON-LD: {}", e),
+        }
+    }
+
+    for scope in document.find(Attr("itemscope", ())) {
+        // Nested products (e.g. accessories inside a product) belong to their parent's markup
+        if is_schema_type(scope.attr("itemtype").unwrap_or(""), "Product") && scope.attr("itemprop").is_none() {
+            products.push(microdata_product(scope));
+        }
+    }
+
+    products
+}
+
+// Walk JSON-LD looking for `Product` nodes, including inside `@graph` and `ItemList` wrappers
+fn collect_json_ld(data: &Data, products: &mut Vec<StructuredProduct>, depth: usize) {
+    if depth > 16 {
+        return;
+    }
+
+    match data {
+        Data::List(items) => {
+            for item in items {
+                collect_json_ld(item, products, depth + 1);
+            }
+        }
+        Data::Map(_) if has_json_ld_type(data, "Product") => products.push(json_ld_product(data)),
+        Data::Map(_) => {
+            for key in ["@graph", "itemListElement", "item", "mainEntity"] {
+                if let Some(child) = data.get(key) {
+                    collect_json_ld(child, products, depth + 1);
+                }
+            }
+        }
+        _ => {}
+    }
+}
+
+fn has_json_ld_type(data: &Data, wanted: &str) -> bool {
+    match data.get("@type") {
+        Some(Data::Text(t)) => is_schema_type(t, wanted),
+        Some(Data::List(types)) => types.iter().filter_map(Data::as_str).any(|t| is_schema_type(t, wanted)),
+        _ => false,
+    }
+}
+
+// Accepts `Product`, `schema:Product` and `https://schema.org/Product`
+fn is_schema_type(value: &str, wanted: &str) -> bool {
+    value.split_whitespace().any(|t| last_segment(t) == wanted)
+}
+
+fn last_segment(value: &str) -> &str {
+    value.trim().rsplit(['/', ':', '#']).next().unwrap_or(value)
+}
+
+fn json_ld_product(data: &Data) -> StructuredProduct {
+    let mut product = StructuredProduct {
+        name: data.get("name").and_then(scalar_text),
+        sku: data.get("sku").or_else(|| data.get("mpn")).and_then(scalar_text),
+        brand: data.get("brand").and_then(|brand| scalar_text(brand).or_else(|| brand.get("name").and_then(scalar_text))),
+        image: data.get("image").and_then(json_ld_image),
+        url: data.get("url").and_then(scalar_text),
+        ..StructuredProduct::default()
+    };
+
+    // `offers` may be a single Offer, an AggregateOffer or a list of either
+    let offers: Vec<&Data> = match data.get("offers") {
+        Some(Data::List(items)) => items.iter().collect(),
+        Some(offer) => vec![offer],
+        None => Vec::new(),
+    };
+    let offer = offers.iter()
+        .find(|offer| offer_price(offer).is_some())
+        .or_else(|| offers.first());
+    if let Some(offer) = offer {
+        product.price = offer_price(offer);
+        product.currency = offer.get("priceCurrency").and_then(scalar_text);
+        product.availability = offer.get("availability").and_then(scalar_text).map(|a| last_segment(&a).to_string());
+    }
+
+    product
+}
+
+fn offer_price(offer: &Data) -> Option<f64> {
+    let price = offer.get("price").or_else(|| offer.get("lowPrice"))?;
+    price.as_f64().or_else(|| price.as_str().and_then(parse_structured_price))
+}
+
+fn json_ld_image(image: &Data) -> Option<String> {
+    match image {
+        Data::Text(url) => Some(url.trim().to_string()),
+        Data::List(items) => items.iter().find_map(json_ld_image),
+        Data::Map(_) => image.get("url").or_else(|| image.get("contentUrl")).and_then(scalar_text),
+        _ => None,
+    }
+}
+
+// Strings and numbers as trimmed text; SKUs in particular are often numeric
+fn scalar_text(data: &Data) -> Option<String> {
+    let text = match data {
+        Data::Text(s) => s.trim().to_string(),
+        Data::Int(i) => i.to_string(),
+        Data::Float(f) => f.to_string(),
+        _ => return None,
+    };
+    (!text.is_empty()).then_some(text)
+}
+
+fn microdata_product(scope: Node) -> StructuredProduct {
+    let properties = item_properties(scope);
+    let property = |name: &str| properties.iter().find(|(key, _)| key == name).map(|(_, node)| *node);
+    let value = |name: &str| property(name).map(item_value).filter(|v| !v.is_empty());
+
+    let mut product = StructuredProduct {
+        name: value("name"),
+        sku: value("sku").or_else(|| value("mpn")),
+        brand: property("brand").and_then(|brand| {
+            if brand.attr("itemscope").is_some() {
+                item_properties(brand).iter().find(|(key, _)| key == "name").map(|(_, node)| item_value(*node))
+            } else {
+                Some(item_value(brand))
+            }
+        }),
+        image: value("image"),
+        url: value("url"),
+        ..StructuredProduct::default()
+    };
+
+    // Offer fields usually sit in a nested `offers` scope, but some pages put them on the product
+    let offer_properties = match property("offers") {
+        Some(offers) if offers.attr("itemscope").is_some() => item_properties(offers),
+        _ => properties.clone(),
+    };
+    let offer_value = |name: &str| offer_properties.iter()
+        .find(|(key, _)| key == name)
+        .map(|(_, node)| item_value(*node))
+        .filter(|v| !v.is_empty());
+    product.price = offer_value("price").or_else(|| offer_value("lowPrice")).as_deref().and_then(parse_structured_price);
+    product.currency = offer_value("priceCurrency");
+    product.availability = offer_value("availability").map(|a| last_segment(&a).to_string());
+
+    product
+}
+
+// Properties owned by an item scope, skipping those of nested scopes
+fn item_properties(scope: Node) -> Vec<(String, Node)> {
+    scope.descendants()
+        .filter(|node| node.attr("itemprop").is_some())
+        .filter(|node| owning_scope(*node).map(|owner| owner.index()) == Some(scope.index()))
+        .flat_map(|node| {
+            node.attr("itemprop").unwrap_or("")
+                .split_whitespace()
+                .map(move |name| (name.to_string(), node))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+fn owning_scope(node: Node) -> Option<Node> {
+    let mut current = node.parent();
+    while let Some(ancestor) = current {
+        if ancestor.attr("itemscope").is_some() {
+            return Some(ancestor);
+        }
+        current = ancestor.parent();
+    }
+    None
+}
+
+// Property value as defined by the HTML microdata spec
+fn item_value(node: Node) -> String {
+    if let Some(content) = node.attr("content") {
+        return content.trim().to_string();
+    }
+    let attribute = match node.name() {
+        Some("a" | "area" | "link") => "href",
+        Some("img" | "source" | "video" | "audio" | "iframe" | "embed") => "src",
+        Some("data" | "meter") => "value",
+        Some("time") => "datetime",
+        _ => return node.text().split_whitespace().collect::<Vec<_>>().join(" "),
+    };
+    node.attr(attribute).unwrap_or("").trim().to_string()
+}
+
+// schema.org asks for a plain decimal like `12999.00`, but shops also emit `12 999,00`
+fn parse_structured_price(price: &str) -> Option<f64> {
+    parse_price(price, Some(NumberLocale::English)).ok().map(|price| price.amount)
+}
@@ -0,0 +1,169 @@
+//! TLS configuration for the HTTP client and server certificate details.
+
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::path::PathBuf;
+use native_tls::{Certificate, Identity, Protocol, TlsConnector};
+use sha2::{Digest, Sha256};
+use x509_parser::extensions::GeneralName;
+use x509_parser::prelude::{FromDer, X509Certificate};
+use crate::http2;
+
+/// TLS settings used to build the client's connector
+#[derive(Debug, Clone, Default)]
+pub struct TlsOptions {
+    /// Extra PEM files whose certificates are trusted in addition to the system roots
+    pub ca_certs: Vec<PathBuf>,
+    /// Client certificate: a PEM chain paired with `client_key`, or a PKCS#12 bundle on its own
+    pub client_cert: Option<PathBuf>,
+    pub client_key: Option<PathBuf>,
+    pub client_cert_password: Option<String>,
+    pub min_version: Option<Protocol>,
+    /// Skip certificate and host name verification; only meant for staging servers
+    pub insecure: bool,
+    /// Do not offer HTTP/2 during the handshake, so every server is spoken to in HTTP/1.1
+    pub http1_only: bool,
+}
+
+impl TlsOptions {
+    pub fn connector(&self) -> Result<TlsConnector, Box<dyn Error>> {
+        let mut builder = TlsConnector::builder();
+
+        for path in &self.ca_certs {
+            let pem = fs::read(path).map_err(|e| format!("Cannot read CA file {}: {}", path.display(), e))?;
+            let certificates = pem_certificates(&pem)?;
+            if certificates.is_empty() {
+                return Err(format!("No certificates found in {}", path.display()).into());
+            }
+            for certificate in certificates {
+                builder.add_root_certificate(certificate);
+            }
+        }
+
+        if let Some(identity) = self.identity()? {
+            builder.identity(identity);
+        }
+
+        builder.min_protocol_version(self.min_version);
+        builder.danger_accept_invalid_certs(self.insecure);
+        builder.danger_accept_invalid_hostnames(self.insecure);
+        if !self.http1_only {
+            builder.request_alpns(&[http2::ALPN, "http/1.1"]);
+        }
+
+        Ok(builder.build()?)
+    }
+
+    fn identity(&self) -> Result<Option<Identity>, Box<dyn Error>> {
+        let Some(cert_path) = &self.client_cert else {
+            return Ok(None);
+        };
+        let cert = fs::read(cert_path)
+            .map_err(|e| format!("Cannot read client certificate {}: {}", cert_path.display(), e))?;
+
+        let identity = match &self.client_key {
+            Some(key_path) => {
+                let key = fs::read(key_path)
+                    .map_err(|e| format!("Cannot read client key {}: {}", key_path.display(), e))?;
+                Identity::from_pkcs8(&cert, &key)?
+            }
+            None => Identity::from_pkcs12(&cert, self.client_cert_password.as_deref().unwrap_or(""))?,
+        };
+
+        Ok(Some(identity))
+    }
+}
+
+/// Parse a minimum version given as `1.0`, `1.1` or `1.2`
+pub fn parse_tls_version(version: &str) -> Result<Protocol, Box<dyn Error>> {
+    match version.trim().to_lowercase().trim_start_matches("tls").trim_start_matches('v') {
+        "1.0" | "10" => Ok(Protocol::Tlsv10),
+        "1.1" | "11" => Ok(Protocol::Tlsv11),
+        "1.2" | "12" => Ok(Protocol::Tlsv12),
+        _ => Err(format!("Unsupported minimum TLS version: {} (expected 1.0, 1.1 or 1.2)", version).into()),
+    }
+}
+
+// Split a PEM bundle into its certificates; `Certificate::from_pem` only reads the first one
+fn pem_certificates(pem: &[u8]) -> Result<Vec<Certificate>, Box<dyn Error>> {
+    const END: &str = "-----END CERTIFICATE-----";
+    let text = String::from_utf8_lossy(pem);
+    let mut certificates = Vec::new();
+    let mut rest = text.as_ref();
+
+    while let Some(start) = rest.find("-----BEGIN CERTIFICATE-----") {
+        let end = rest[start..].find(END).ok_or("Unterminated PEM certificate")? + start + END.len();
+        certificates.push(Certificate::from_pem(&rest.as_bytes()[start..end])?);
+        rest = &rest[end..];
+    }
+
+    Ok(certificates)
+}
+
+/// Details of the certificate a server presented, for diagnostics
+#[derive(Debug, Clone)]
+pub struct PeerCertificate {
+    pub subject: String,
+    pub issuer: String,
+    pub serial: String,
+    pub not_before: String,
+    pub not_after: String,
+    pub subject_alt_names: Vec<String>,
+    pub sha256_fingerprint: String,
+}
+
+impl PeerCertificate {
+    pub fn from_der(der: &[u8]) -> Result<PeerCertificate, Box<dyn Error>> {
+        let (_, certificate) = X509Certificate::from_der(der)
+            .map_err(|e| format!("Cannot parse peer certificate: {}", e))?;
+
+        let subject_alt_names = certificate
+            .subject_alternative_name()
+            .ok()
+            .flatten()
+            .map(|extension| {
+                extension.value.general_names.iter().filter_map(|name| match name {
+                    GeneralName::DNSName(dns) => Some(dns.to_string()),
+                    GeneralName::IPAddress(ip) => Some(format_ip(ip)),
+                    _ => None,
+                }).collect()
+            })
+            .unwrap_or_default();
+
+        let fingerprint: Vec<String> = Sha256::digest(der).iter().map(|b| format!("{:02X}", b)).collect();
+
+        Ok(PeerCertificate {
+            subject: certificate.subject().to_string(),
+            issuer: certificate.issuer().to_string(),
+            serial: certificate.raw_serial_as_string(),
+            not_before: certificate.validity().not_before.to_string(),
+            not_after: certificate.validity().not_after.to_string(),
+            subject_alt_names,
+            sha256_fingerprint: fingerprint.join(":"),
+        })
+    }
+}
+
+impl fmt::Display for PeerCertificate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Subject: {}", self.subject)?;
+        writeln!(f, "Issuer: {}", self.issuer)?;
+        writeln!(f, "Serial: {}", self.serial)?;
+        writeln!(f, "Valid: {} to {}", self.not_before, self.not_after)?;
+        writeln!(f, "Names: {}", self.subject_alt_names.join(", "))?;
+        write!(f, "SHA-256: {}", self.sha256_fingerprint)
+    }
+}
+
+fn format_ip(bytes: &[u8]) -> String {
+    match bytes.len() {
+        4 => std::net::Ipv4Addr::from([bytes[0], bytes[1], bytes[2], bytes[3]]).to_string(),
+        16 => {
+            let mut octets = [0u8; 16];
+            octets.copy_from_slice(bytes);
+            std::net::Ipv6Addr::from(octets).to_string()
+        }
+        _ => format!("{:?}", bytes),
+    }
+}
@@ -1,23 +1,117 @@
-use std::error::Error;
+//! Rules every scraped product must pass, reported as violations.
 
-pub fn validate_product_name(name: &str) -> bool {
-    !name.is_empty() // Ensure the name is not empty
+use std::fmt;
+use url::Url;
+use crate::price::{self, NumberLocale, PriceError};
+
+/// Prices above this are treated as scraping mistakes rather than real products
+pub const MAX_PRICE_MDL: f64 = 10_000_000.0;
+
+// Text the scraper and shops put where a product name should be
+const PLACEHOLDER_NAMES: &[&str] = &[
+    "product name not found", "n/a", "na", "none", "null", "undefined", "untitled", "product", "tbd", "-",
+];
+
+/// One broken rule on one product field
+#[derive(Debug, Clone, PartialEq)]
+pub struct Violation {
+    pub field: &'static str,
+    /// Stable identifier of the rule, e.g. `placeholder` or `thousands_separator`
+    pub rule: &'static str,
+    pub message: String,
+}
+
+impl Violation {
+    pub fn new(field: &'static str, rule: &'static str, message: impl Into<String>) -> Violation {
+        Violation { field, rule, message: message.into() }
+    }
 }
 
-pub fn validate_price(price: &str) -> bool {
-    // Check if the price is a valid number (allowing for optional decimals)
-    let cleaned_price = price.replace("lei", "").replace(" ", ""); // Clean the price string
-    !cleaned_price.is_empty() && cleaned_price.chars().all(|c| c.is_digit(10) || c == '.')
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {} [{}]", self.field, self.message, self.rule)
+    }
+}
+
+/// A scraped product that failed validation, with every rule it broke
+#[derive(Debug, Clone)]
+pub struct Rejection {
+    pub name: String,
+    pub link: Option<String>,
+    pub violations: Vec<Violation>,
 }
 
-pub fn convert_price_to_numeric(price: &str) -> Result<f64, Box<dyn Error>> {
-    let cleaned_price = price.replace("lei", "").replace(" ", "").replace(",", "."); // Clean the price string
-    let price_value: f64 = cleaned_price.trim().parse()?;
+impl fmt::Display for Rejection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = if self.name.trim().is_empty() { "(no name)" } else { self.name.trim() };
+        write!(f, "{} <{}>", name, self.link.as_deref().unwrap_or("no link"))?;
+        for violation in &self.violations {
+            write!(f, "\n    - {}", violation)?;
+        }
+        Ok(())
+    }
+}
 
-    // Check if price is in MDL or EUR and convert accordingly
-    if price.contains("EUR") {
-        Ok(price_value * super::data::EUR_TO_MDL) // Convert EUR to MDL
+pub fn validate_name(name: &str) -> Vec<Violation> {
+    let trimmed = name.trim();
+    if trimmed.is_empty() {
+        return vec![Violation::new("name", "empty", "name is empty")];
+    }
+    if PLACEHOLDER_NAMES.contains(&trimmed.to_lowercase().as_str()) || !trimmed.chars().any(char::is_alphanumeric) {
+        return vec![Violation::new("name", "placeholder", format!("{:?} is a placeholder, not a name", trimmed))];
+    }
+    Vec::new()
+}
+
+/// Parse a displayed price into MDL; `locale` is the page's language, when known
+pub fn parse_price(price: &str, locale: Option<NumberLocale>) -> Result<f64, Violation> {
+    let trimmed = price.trim();
+    if trimmed.eq_ignore_ascii_case("price not found") {
+        return Err(Violation::new("price", "missing", "no price on the page"));
+    }
+
+    let parsed = price::parse_price(trimmed, locale).map_err(|e| {
+        let rule = match e {
+            PriceError::Empty => "missing",
+            PriceError::Unparseable(_) => "unparseable",
+            PriceError::AmbiguousSeparator(_) => "thousands_separator",
+            PriceError::ConflictingCurrencies(..) => "currency",
+        };
+        match e {
+            PriceError::Unparseable(_) => Violation::new("price", rule, e.to_string()),
+            _ => Violation::new("price", rule, format!("{:?}: {}", trimmed, e)),
+        }
+    })?;
+
+    parsed.amount_mdl().ok_or_else(|| {
+        let currency = parsed.currency.unwrap_or_default();
+        Violation::new("price", "currency", format!("{:?}: no exchange rate for {}", trimmed, currency))
+    })
+}
+
+/// Range checks on a price already converted to MDL
+pub fn validate_amount(amount: f64) -> Vec<Violation> {
+    if !amount.is_finite() {
+        vec![Violation::new("price", "absurd", "price is not a finite number")]
+    } else if amount < 0.0 {
+        vec![Violation::new("price", "negative", format!("price {:.2} MDL is negative", amount))]
+    } else if amount == 0.0 {
+        vec![Violation::new("price", "absurd", "price is zero")]
+    } else if amount > MAX_PRICE_MDL {
+        vec![Violation::new("price", "absurd", format!("price {:.2} MDL exceeds {:.0} MDL", amount, MAX_PRICE_MDL))]
     } else {
-        Ok(price_value) // Assume it's already in MDL
+        Vec::new()
+    }
+}
+
+/// The product link must be an absolute http(s) URL
+pub fn validate_link(link: Option<&str>) -> Vec<Violation> {
+    let Some(link) = link else {
+        return vec![Violation::new("link", "missing", "product has no link")];
+    };
+    match Url::parse(link) {
+        Ok(url) if matches!(url.scheme(), "http" | "https") && url.host_str().is_some() => Vec::new(),
+        Ok(url) => vec![Violation::new("link", "broken", format!("unsupported link scheme {:?}", url.scheme()))],
+        Err(e) => vec![Violation::new("link", "broken", format!("invalid link {:?}: {}", link, e))],
     }
 }
@@ -0,0 +1,176 @@
+//! Small XML tokenizer, enough for sitemaps and the `productList` format.
+
+use std::error::Error;
+
+/// Attribute names and unescaped values, in document order
+pub type Attributes = Vec<(String, String)>;
+
+/// A flat stream of XML events; enough for sitemaps and our own `productList` output
+#[derive(Debug, Clone, PartialEq)]
+pub enum XmlEvent {
+    Start { name: String, attributes: Attributes },
+    End { name: String },
+    Text(String),
+}
+
+/// Tokenize an XML document; comments, processing instructions and DOCTYPE are skipped.
+/// Self-closing tags produce a `Start` immediately followed by an `End`.
+pub fn parse_xml(text: &str) -> Result<Vec<XmlEvent>, Box<dyn Error>> {
+    let mut events = Vec::new();
+    let mut rest = text.trim_start_matches('\u{feff}');
+    let mut open = Vec::new();
+
+    while !rest.is_empty() {
+        let Some(tag_start) = rest.find('<') else {
+            push_text(&mut events, &unescape_xml(rest));
+            break;
+        };
+        if tag_start > 0 {
+            push_text(&mut events, &unescape_xml(&rest[..tag_start]));
+        }
+        rest = &rest[tag_start..];
+
+        if let Some(body) = rest.strip_prefix("<!--") {
+            rest = skip_past(body, "-->")?;
+        } else if let Some(body) = rest.strip_prefix("<![CDATA[") {
+            let end = body.find("]]>").ok_or("Unterminated CDATA section")?;
+            push_text(&mut events, &body[..end]);
+            rest = &body[end + 3..];
+        } else if let Some(body) = rest.strip_prefix("<?") {
+            rest = skip_past(body, "?>")?;
+        } else if rest.starts_with("<!") {
+            rest = skip_past(&rest[2..], ">")?;
+        } else if let Some(body) = rest.strip_prefix("</") {
+            let end = body.find('>').ok_or("Unterminated closing tag")?;
+            let name = body[..end].trim().to_string();
+            match open.pop() {
+                Some(expected) if expected == name => {}
+                Some(expected) => return Err(format!("Expected </{}>, found </{}>", expected, name).into()),
+                None => return Err(format!("Unexpected </{}>", name).into()),
+            }
+            events.push(XmlEvent::End { name });
+            rest = &body[end + 1..];
+        } else {
+            let end = tag_end(rest).ok_or("Unterminated tag")?;
+            let inner = &rest[1..end];
+            let (inner, self_closing) = match inner.strip_suffix('/') {
+                Some(inner) => (inner, true),
+                None => (inner, false),
+            };
+            let (name, attributes) = parse_tag(inner)?;
+            events.push(XmlEvent::Start { name: name.clone(), attributes });
+            if self_closing {
+                events.push(XmlEvent::End { name });
+            } else {
+                open.push(name);
+            }
+            rest = &rest[end + 1..];
+        }
+    }
+
+    if let Some(name) = open.pop() {
+        return Err(format!("Unclosed element <{}>", name).into());
+    }
+    Ok(events)
+}
+
+/// Element name without its namespace prefix, e.g. `loc` for `sm:loc`
+pub fn local_name(name: &str) -> &str {
+    name.rsplit(':').next().unwrap_or(name)
+}
+
+/// Decode the predefined entities and numeric character references
+pub fn unescape_xml(input: &str) -> String {
+    let mut output = String::with_capacity(input.len());
+    let mut rest = input;
+
+    while let Some(amp) = rest.find('&') {
+        output.push_str(&rest[..amp]);
+        rest = &rest[amp..];
+        let decoded = rest.find(';').and_then(|semi| {
+            let entity = &rest[1..semi];
+            let c = match entity {
+                "amp" => Some('&'),
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                _ => entity
+                    .strip_prefix("#x").or_else(|| entity.strip_prefix("#X"))
+                    .map(|hex| u32::from_str_radix(hex, 16))
+                    .or_else(|| entity.strip_prefix('#').map(str::parse::<u32>))
+                    .and_then(Result::ok)
+                    .and_then(char::from_u32),
+            }?;
+            Some((c, semi))
+        });
+        match decoded {
+            Some((c, semi)) => {
+                output.push(c);
+                rest = &rest[semi + 1..];
+            }
+            // Leave anything that is not a known entity as it was
+            None => {
+                output.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    output.push_str(rest);
+    output
+}
+
+fn push_text(events: &mut Vec<XmlEvent>, text: &str) {
+    if text.is_empty() {
+        return;
+    }
+    // Merge with a preceding text or CDATA run
+    if let Some(XmlEvent::Text(previous)) = events.last_mut() {
+        previous.push_str(text);
+    } else {
+        events.push(XmlEvent::Text(text.to_string()));
+    }
+}
+
+fn skip_past<'a>(text: &'a str, terminator: &str) -> Result<&'a str, Box<dyn Error>> {
+    let end = text.find(terminator).ok_or_else(|| format!("Missing '{}'", terminator))?;
+    Ok(&text[end + terminator.len()..])
+}
+
+// Position of the `>` closing a start tag, ignoring any inside quoted attribute values
+fn tag_end(text: &str) -> Option<usize> {
+    let mut quote = None;
+    for (index, c) in text.char_indices() {
+        match (quote, c) {
+            (None, '"' | '\'') => quote = Some(c),
+            (Some(q), c) if c == q => quote = None,
+            (None, '>') => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn parse_tag(inner: &str) -> Result<(String, Attributes), Box<dyn Error>> {
+    let inner = inner.trim();
+    let name_end = inner.find(char::is_whitespace).unwrap_or(inner.len());
+    let name = inner[..name_end].to_string();
+    if name.is_empty() {
+        return Err("Empty tag name".into());
+    }
+
+    let mut attributes = Vec::new();
+    let mut rest = inner[name_end..].trim_start();
+    while !rest.is_empty() {
+        let eq = rest.find('=').ok_or("Attribute without value")?;
+        let key = rest[..eq].trim().to_string();
+        let value_part = rest[eq + 1..].trim_start();
+        let quote = value_part.chars().next().filter(|c| *c == '"' || *c == '\'').ok_or("Unquoted attribute value")?;
+        let value_end = value_part[1..].find(quote).ok_or("Unterminated attribute value")? + 1;
+        attributes.push((key, unescape_xml(&value_part[1..value_end])));
+        rest = value_part[value_end + 1..].trim_start();
+    }
+
+    Ok((name, attributes))
+}
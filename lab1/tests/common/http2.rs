@@ -0,0 +1,142 @@
+// HTTPS server that offers HTTP/2 during the handshake and serves the same routes as
+// `FixtureServer`, counting connections and how many streams were in flight at once.
+// Clients that do not take up HTTP/2 get one HTTP/1.1 request per connection instead.
+
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use bytes::Bytes;
+use h2::server::SendResponse;
+use h2::RecvStream;
+use native_tls::{Identity, TlsAcceptor};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::runtime::Builder;
+use super::{reason, Reply, Routes};
+
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub connections: AtomicUsize,
+    pub http2_connections: AtomicUsize,
+    pub requests: AtomicUsize,
+    active_streams: AtomicUsize,
+    pub max_concurrent_streams: AtomicUsize,
+    // `:authority` (or Host) and path of every request, in arrival order
+    pub requested: Mutex<Vec<(String, String)>>,
+}
+
+pub struct Http2Server {
+    address: SocketAddr,
+    pub stats: Arc<Stats>,
+}
+
+impl Http2Server {
+    // Every response is held back for `delay`, so requests sent together overlap on the server
+    pub fn start(routes: Routes, delay: Duration) -> Http2Server {
+        let identity = Identity::from_pkcs8(
+            include_bytes!("../fixtures/tls/server.pem"),
+            include_bytes!("../fixtures/tls/server.key"),
+        ).unwrap();
+        let acceptor = TlsAcceptor::builder(identity).accept_alpn(&["h2", "http/1.1"]).build().unwrap();
+        let acceptor = tokio_native_tls::TlsAcceptor::from(acceptor);
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.set_nonblocking(true).unwrap();
+        let address = listener.local_addr().unwrap();
+        let stats = Arc::new(Stats::default());
+        let routes = Arc::new(routes);
+
+        let server_stats = Arc::clone(&stats);
+        thread::spawn(move || {
+            let runtime = Builder::new_multi_thread().enable_all().build().unwrap();
+            runtime.block_on(async move {
+                let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                while let Ok((socket, _)) = listener.accept().await {
+                    server_stats.connections.fetch_add(1, Ordering::SeqCst);
+                    let (acceptor, routes, stats) = (acceptor.clone(), Arc::clone(&routes), Arc::clone(&server_stats));
+                    tokio::spawn(async move {
+                        let Ok(stream) = acceptor.accept(socket).await else {
+                            return;
+                        };
+                        if stream.get_ref().negotiated_alpn().ok().flatten().as_deref() == Some(b"h2") {
+                            stats.http2_connections.fetch_add(1, Ordering::SeqCst);
+                            serve_http2(stream, routes, stats, delay).await;
+                        } else {
+                            serve_http1(stream, &routes, &stats).await;
+                        }
+                    });
+                }
+            });
+        });
+
+        Http2Server { address, stats }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("https://localhost:{}{}", self.address.port(), path)
+    }
+}
+
+async fn serve_http2<S>(stream: S, routes: Arc<Routes>, stats: Arc<Stats>, delay: Duration)
+where
+    S: AsyncRead + AsyncWrite + Unpin + Send + 'static,
+{
+    let Ok(mut connection) = h2::server::handshake(stream).await else {
+        return;
+    };
+    while let Some(Ok((request, respond))) = connection.accept().await {
+        let authority = request.uri().authority().map(|a| a.to_string()).unwrap_or_default();
+        let path = request.uri().path_and_query().map(|p| p.to_string()).unwrap_or_default();
+        stats.requested.lock().unwrap().push((authority, path.clone()));
+        let reply = routes.get(path.as_str()).cloned().unwrap_or_else(|| Reply::status(404));
+        tokio::spawn(respond_http2(request.into_body(), respond, reply, Arc::clone(&stats), delay));
+    }
+}
+
+async fn respond_http2(_body: RecvStream, mut respond: SendResponse<Bytes>, reply: Reply, stats: Arc<Stats>, delay: Duration) {
+    stats.requests.fetch_add(1, Ordering::SeqCst);
+    let active = stats.active_streams.fetch_add(1, Ordering::SeqCst) + 1;
+    stats.max_concurrent_streams.fetch_max(active, Ordering::SeqCst);
+    tokio::time::sleep(delay).await;
+
+    let mut response = http::Response::builder().status(reply.status);
+    for (name, value) in &reply.headers {
+        response = response.header(name.as_str(), value.as_str());
+    }
+    if let Ok(mut body) = respond.send_response(response.body(()).unwrap(), reply.body.is_empty()) {
+        if !reply.body.is_empty() {
+            let _ = body.send_data(Bytes::from(reply.body), true);
+        }
+    }
+    stats.active_streams.fetch_sub(1, Ordering::SeqCst);
+}
+
+async fn serve_http1<S: AsyncRead + AsyncWrite + Unpin>(mut stream: S, routes: &Routes, stats: &Stats) {
+    let mut request = Vec::new();
+    let mut buffer = [0; 1024];
+    while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+        match stream.read(&mut buffer).await {
+            Ok(0) | Err(_) => return,
+            Ok(read) => request.extend_from_slice(&buffer[..read]),
+        }
+    }
+    let request = String::from_utf8_lossy(&request);
+    let path = request.split_whitespace().nth(1).unwrap_or("/").to_string();
+    let host = request.lines()
+        .find_map(|line| line.split_once(':').filter(|(name, _)| name.eq_ignore_ascii_case("host")))
+        .map(|(_, value)| value.trim().to_string())
+        .unwrap_or_default();
+    stats.requested.lock().unwrap().push((host, path.clone()));
+    stats.requests.fetch_add(1, Ordering::SeqCst);
+
+    let reply = routes.get(path.as_str()).cloned().unwrap_or_else(|| Reply::status(404));
+    let mut head = format!("HTTP/1.1 {} {}\r\n", reply.status, reason(reply.status));
+    for (name, value) in &reply.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    head.push_str(&format!("Content-Length: {}\r\nConnection: close\r\n\r\n", reply.body.len()));
+    let _ = stream.write_all(head.as_bytes()).await;
+    let _ = stream.write_all(&reply.body).await;
+    let _ = stream.shutdown().await;
+}
@@ -0,0 +1,201 @@
+// In-process HTTP/HTTPS server that replays recorded shop pages, so the scraper can be
+// exercised without the network. Each connection serves one request and is then closed,
+// matching the `Connection: close` requests the client sends.
+
+// Every test crate includes this module but uses only part of it
+#![allow(dead_code)]
+
+pub mod http2;
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{SocketAddr, TcpListener};
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::thread;
+use lab1::cookies::CookieJar;
+use lab1::proxy::ProxyConfig;
+use lab1::tls::TlsOptions;
+use lab1::Client;
+use native_tls::{Identity, TlsAcceptor, TlsAcceptorBuilder};
+
+pub const LISTING: &str = include_str!("../fixtures/listing.html");
+pub const PNG: &[u8] = include_bytes!("../fixtures/pixel.png");
+
+#[derive(Debug, Clone)]
+pub struct Reply {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+    // Send the body with `Transfer-Encoding: chunked`, in pieces of this many bytes
+    pub chunk_size: Option<usize>,
+}
+
+impl Reply {
+    pub fn html(body: &str) -> Reply {
+        Reply::bytes("text/html; charset=utf-8", body.as_bytes())
+    }
+
+    pub fn bytes(content_type: &str, body: &[u8]) -> Reply {
+        Reply {
+            status: 200,
+            headers: vec![("Content-Type".to_string(), content_type.to_string())],
+            body: body.to_vec(),
+            chunk_size: None,
+        }
+    }
+
+    pub fn status(status: u16) -> Reply {
+        Reply { status, ..Reply::html("<html><body><h1>Error</h1></body></html>") }
+    }
+
+    pub fn redirect(status: u16, location: &str) -> Reply {
+        Reply {
+            status,
+            headers: vec![("Location".to_string(), location.to_string())],
+            body: Vec::new(),
+            chunk_size: None,
+        }
+    }
+
+    pub fn chunked(mut self, chunk_size: usize) -> Reply {
+        self.chunk_size = Some(chunk_size);
+        self
+    }
+}
+
+pub type Routes = HashMap<&'static str, Reply>;
+
+// The recorded xstore.md pages: a listing of five phones whose detail pages cover a plain
+// page, structured data, an unparseable price, a missing page and a server error
+pub fn shop() -> Routes {
+    HashMap::from([
+        ("/", Reply::html(LISTING)),
+        ("/product/iphone-15", Reply::html(include_str!("../fixtures/iphone-15.html"))),
+        ("/product/galaxy-a55", Reply::html(include_str!("../fixtures/galaxy-a55.html"))),
+        ("/product/pixel-8", Reply::html(include_str!("../fixtures/pixel-8.html"))),
+        ("/product/nothing-phone-2a", Reply::status(500)),
+        ("/img/iphone-15.png", Reply::bytes("image/png", PNG)),
+        ("/img/galaxy-a55.png", Reply::bytes("image/png", PNG)),
+    ])
+}
+
+pub struct FixtureServer {
+    address: SocketAddr,
+    scheme: &'static str,
+}
+
+impl FixtureServer {
+    pub fn http(routes: Routes) -> FixtureServer {
+        FixtureServer::start(routes, None)
+    }
+
+    // HTTPS with a certificate for `localhost` and 127.0.0.1 issued by `tests/fixtures/tls/ca.pem`
+    pub fn https(routes: Routes) -> FixtureServer {
+        FixtureServer::https_with(routes, |_| {})
+    }
+
+    // HTTPS as above, with the acceptor adjusted first, e.g. to limit the protocol versions
+    pub fn https_with(routes: Routes, configure: impl FnOnce(&mut TlsAcceptorBuilder)) -> FixtureServer {
+        let identity = Identity::from_pkcs8(
+            include_bytes!("../fixtures/tls/server.pem"),
+            include_bytes!("../fixtures/tls/server.key"),
+        ).unwrap();
+        let mut builder = TlsAcceptor::builder(identity);
+        configure(&mut builder);
+        FixtureServer::start(routes, Some(builder.build().unwrap()))
+    }
+
+    fn start(routes: Routes, tls: Option<TlsAcceptor>) -> FixtureServer {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        let scheme = if tls.is_some() { "https" } else { "http" };
+        let routes = Arc::new(routes);
+
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let routes = Arc::clone(&routes);
+                let tls = tls.clone();
+                thread::spawn(move || match tls {
+                    Some(acceptor) => {
+                        if let Ok(stream) = acceptor.accept(stream) {
+                            serve(stream, &routes);
+                        }
+                    }
+                    None => serve(stream, &routes),
+                });
+            }
+        });
+
+        FixtureServer { address, scheme }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        let host = if self.scheme == "https" { "localhost" } else { "127.0.0.1" };
+        format!("{}://{}:{}{}", self.scheme, host, self.address.port(), path)
+    }
+}
+
+fn serve<S: Read + Write>(stream: S, routes: &Routes) {
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    if reader.read_line(&mut request_line).is_err() {
+        return;
+    }
+    // Skip the headers; the client never sends a body
+    let mut line = String::new();
+    while reader.read_line(&mut line).is_ok_and(|read| read > 0) && line.trim_end() != "" {
+        line.clear();
+    }
+
+    let path = request_line.split_whitespace().nth(1).unwrap_or("/");
+    let reply = routes.get(path).cloned().unwrap_or_else(|| Reply::status(404));
+    let _ = write_reply(reader.get_mut(), &reply);
+}
+
+fn write_reply<S: Write>(stream: &mut S, reply: &Reply) -> std::io::Result<()> {
+    let mut head = format!("HTTP/1.1 {} {}\r\n", reply.status, reason(reply.status));
+    for (name, value) in &reply.headers {
+        head.push_str(&format!("{}: {}\r\n", name, value));
+    }
+    match reply.chunk_size {
+        Some(_) => head.push_str("Transfer-Encoding: chunked\r\n"),
+        None => head.push_str(&format!("Content-Length: {}\r\n", reply.body.len())),
+    }
+    head.push_str("Connection: close\r\n\r\n");
+    stream.write_all(head.as_bytes())?;
+
+    match reply.chunk_size {
+        Some(size) => {
+            for chunk in reply.body.chunks(size.max(1)) {
+                write!(stream, "{:x}\r\n", chunk.len())?;
+                stream.write_all(chunk)?;
+                stream.write_all(b"\r\n")?;
+                stream.flush()?;
+            }
+            stream.write_all(b"0\r\n\r\n")?;
+        }
+        None => stream.write_all(&reply.body)?,
+    }
+    stream.flush()
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        301 => "Moved Permanently",
+        302 => "Found",
+        404 => "Not Found",
+        500 => "Internal Server Error",
+        _ => "Unknown",
+    }
+}
+
+// Direct connection (no proxy from the environment) trusting the fixture CA
+pub fn client() -> Client {
+    let tls = TlsOptions {
+        ca_certs: vec![PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls/ca.pem")],
+        ..TlsOptions::default()
+    };
+    Client::new(ProxyConfig::default(), CookieJar::new(), &tls).unwrap()
+}
@@ -0,0 +1,132 @@
+use std::fs;
+use std::path::PathBuf;
+use lab1::cookies::CookieJar;
+use url::Url;
+
+fn url(address: &str) -> Url {
+    Url::parse(address).unwrap()
+}
+
+fn jar_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("lab1-{}-{}.txt", name, std::process::id()))
+}
+
+// A jar holding the cookies one response to `address` set
+fn jar_with(address: &str, set_cookie: &[&str]) -> CookieJar {
+    let mut jar = CookieJar::new();
+    jar.store(&url(address), set_cookie.iter().copied());
+    jar
+}
+
+#[test]
+fn host_only_cookies_stay_on_their_host() {
+    let jar = jar_with("https://shop.example.md/", &["session=abc"]);
+    assert_eq!(jar.cookie_header(&url("https://shop.example.md/cart")).as_deref(), Some("session=abc"));
+    assert_eq!(jar.cookie_header(&url("https://www.shop.example.md/")), None);
+    assert_eq!(jar.cookie_header(&url("https://example.md/")), None);
+}
+
+#[test]
+fn domain_cookies_reach_subdomains_only_of_their_own_site() {
+    let jar = jar_with("https://shop.example.md/", &["lang=ro; Domain=.example.md"]);
+    assert_eq!(jar.cookie_header(&url("https://example.md/")).as_deref(), Some("lang=ro"));
+    assert_eq!(jar.cookie_header(&url("https://img.example.md/")).as_deref(), Some("lang=ro"));
+    assert_eq!(jar.cookie_header(&url("https://badexample.md/")), None);
+
+    // A site can't set cookies for an unrelated domain
+    let jar = jar_with("https://shop.example.md/", &["lang=ro; Domain=other.md"]);
+    assert_eq!(jar.cookie_header(&url("https://other.md/")), None);
+}
+
+#[test]
+fn top_level_domains_are_rejected() {
+    let jar = jar_with("https://shop.example.md/", &["tracker=1; Domain=md"]);
+    assert_eq!(jar.cookie_header(&url("https://shop.example.md/")), None);
+    assert_eq!(jar.cookie_header(&url("https://another-shop.md/")), None);
+
+    // A dotless host naming itself keeps a host-only cookie
+    let jar = jar_with("http://localhost:8080/", &["session=abc; Domain=localhost"]);
+    assert_eq!(jar.cookie_header(&url("http://localhost:8080/")).as_deref(), Some("session=abc"));
+}
+
+#[test]
+fn paths_match_on_segment_boundaries() {
+    let jar = jar_with("https://shop.md/", &["a=1; Path=/catalog", "b=2; Path=/catalog/phones", "c=3; Path=/"]);
+    // Longest path first, then oldest
+    assert_eq!(jar.cookie_header(&url("https://shop.md/catalog/phones/iphone")).as_deref(), Some("b=2; a=1; c=3"));
+    assert_eq!(jar.cookie_header(&url("https://shop.md/catalog")).as_deref(), Some("a=1; c=3"));
+    assert_eq!(jar.cookie_header(&url("https://shop.md/catalogue")).as_deref(), Some("c=3"));
+
+    // Without a Path attribute the cookie belongs to the directory of the request
+    let jar = jar_with("https://shop.md/catalog/phones", &["d=4"]);
+    assert_eq!(jar.cookie_header(&url("https://shop.md/catalog/tablets")).as_deref(), Some("d=4"));
+    assert_eq!(jar.cookie_header(&url("https://shop.md/")), None);
+}
+
+#[test]
+fn secure_cookies_need_https() {
+    let jar = jar_with("https://shop.md/", &["token=x; Secure"]);
+    assert_eq!(jar.cookie_header(&url("http://shop.md/")), None);
+    assert_eq!(jar.cookie_header(&url("https://shop.md/")).as_deref(), Some("token=x"));
+}
+
+#[test]
+fn expired_cookies_are_not_sent() {
+    let jar = jar_with("https://shop.md/", &[
+        "old=1; Expires=Wed, 21 Oct 2015 07:28:00 GMT",
+        "new=2; Expires=Fri, 01 Jan 2100 00:00:00 GMT",
+        "dashed=3; Expires=Fri, 01-Jan-2100 00:00:00 GMT",
+    ]);
+    assert_eq!(jar.cookie_header(&url("https://shop.md/")).as_deref(), Some("new=2; dashed=3"));
+}
+
+#[test]
+fn max_age_zero_deletes_a_cookie() {
+    let mut jar = jar_with("https://shop.md/", &["session=abc", "lang=ro"]);
+    jar.store(&url("https://shop.md/"), ["session=; Max-Age=0"]);
+    assert_eq!(jar.cookie_header(&url("https://shop.md/")).as_deref(), Some("lang=ro"));
+
+    // Max-Age wins over a future Expires
+    jar.store(&url("https://shop.md/"), ["lang=ro; Expires=Fri, 01 Jan 2100 00:00:00 GMT; Max-Age=-1"]);
+    assert_eq!(jar.cookie_header(&url("https://shop.md/")), None);
+}
+
+#[test]
+fn netscape_file_round_trips() {
+    let jar = jar_with("https://shop.example.md/catalog/", &[
+        "session=abc; HttpOnly",
+        "lang=ro; Domain=example.md; Path=/; Secure; Max-Age=3600",
+        "gone=1; Max-Age=0",
+    ]);
+    let path = jar_path("cookies");
+    jar.save(&path).unwrap();
+
+    let saved = fs::read_to_string(&path).unwrap();
+    assert!(saved.starts_with("# Netscape HTTP Cookie File\n"));
+    assert!(saved.contains("#HttpOnly_shop.example.md\tFALSE\t/catalog\tFALSE\t0\tsession\tabc\n"), "{}", saved);
+    assert!(saved.contains(".example.md\tTRUE\t/\tTRUE\t"), "{}", saved);
+    assert!(!saved.contains("gone"));
+
+    let loaded = CookieJar::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    for address in ["https://shop.example.md/catalog/x", "https://img.example.md/", "http://img.example.md/"] {
+        assert_eq!(loaded.cookie_header(&url(address)), jar.cookie_header(&url(address)), "{}", address);
+    }
+    assert_eq!(loaded.cookie_header(&url("https://shop.example.md/catalog/x")).as_deref(), Some("session=abc; lang=ro"));
+}
+
+#[test]
+fn malformed_jar_lines_are_skipped() {
+    let path = jar_path("malformed-cookies");
+    fs::write(&path, [
+        "# Netscape HTTP Cookie File",
+        "shop.md\tFALSE\t/\tFALSE\t0\tsession\tabc",
+        "shop.md\tFALSE\t/\tFALSE\tnever\tbad\texpiry",
+        "not a cookie line",
+        ".shop.md\tTRUE\t/\tFALSE\t4102444800\tlang\tro",
+    ].join("\n")).unwrap();
+
+    let jar = CookieJar::load(&path).unwrap();
+    fs::remove_file(&path).unwrap();
+    assert_eq!(jar.cookie_header(&url("https://shop.md/")).as_deref(), Some("session=abc; lang=ro"));
+}
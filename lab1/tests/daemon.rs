@@ -0,0 +1,93 @@
+use chrono::{DateTime, Local, TimeZone};
+use lab1::daemon::{parse_formats, Schedule};
+use lab1::Format;
+
+// January and July 2025 are clear of daylight saving changes in every time zone that has them
+fn at(year: i32, month: u32, day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+    Local.with_ymd_and_hms(year, month, day, hour, minute, 0).unwrap()
+}
+
+fn next(expression: &str, after: DateTime<Local>) -> Option<DateTime<Local>> {
+    Schedule::parse(expression).unwrap_or_else(|e| panic!("{:?}: {}", expression, e)).next_after(after)
+}
+
+#[test]
+fn next_run_is_strictly_later() {
+    // Wednesday 15 January 2025, 10:30
+    let now = at(2025, 1, 15, 10, 30);
+    assert_eq!(next("* * * * *", now), Some(at(2025, 1, 15, 10, 31)));
+    assert_eq!(next("30 10 * * *", now), Some(at(2025, 1, 16, 10, 30)));
+    assert_eq!(next("*/15 * * * *", now), Some(at(2025, 1, 15, 10, 45)));
+    assert_eq!(next("5/20 * * * *", now), Some(at(2025, 1, 15, 10, 45)));
+    assert_eq!(next("0 9-17/4 * * *", now), Some(at(2025, 1, 15, 13, 0)));
+    assert_eq!(next("0,10 8,20 * * *", now), Some(at(2025, 1, 15, 20, 0)));
+    // Seconds don't count: 10:30:59 is still within the 10:30 slot
+    let late = Local.with_ymd_and_hms(2025, 1, 15, 10, 30, 59).unwrap();
+    assert_eq!(next("31 10 * * *", late), Some(at(2025, 1, 15, 10, 31)));
+}
+
+#[test]
+fn shorthands_expand() {
+    let now = at(2025, 1, 15, 10, 30);
+    assert_eq!(next("@hourly", now), Some(at(2025, 1, 15, 11, 0)));
+    assert_eq!(next("@daily", now), Some(at(2025, 1, 16, 0, 0)));
+    assert_eq!(next("@midnight", now), Some(at(2025, 1, 16, 0, 0)));
+    assert_eq!(next("@weekly", now), Some(at(2025, 1, 19, 0, 0)));
+    assert_eq!(next("@monthly", now), Some(at(2025, 2, 1, 0, 0)));
+}
+
+#[test]
+fn day_fields_follow_cron_rules() {
+    let now = at(2025, 1, 15, 10, 30);
+    // Sunday is 0 or 7
+    assert_eq!(next("0 0 * * 7", now), Some(at(2025, 1, 19, 0, 0)));
+    assert_eq!(next("0 0 * * 1-5", now), Some(at(2025, 1, 16, 0, 0)));
+    // Month and day together
+    assert_eq!(next("0 12 1 7 *", now), Some(at(2025, 7, 1, 12, 0)));
+    // With both day fields restricted either one may match: the 20th or the next Friday (17th)
+    assert_eq!(next("0 0 20 * 5", now), Some(at(2025, 1, 17, 0, 0)));
+    // Only the restricted one counts otherwise
+    assert_eq!(next("0 0 20 * *", now), Some(at(2025, 1, 20, 0, 0)));
+    // Months without the day are skipped
+    assert_eq!(next("0 0 31 * *", at(2025, 1, 31, 12, 0)), Some(at(2025, 3, 31, 0, 0)));
+    // The 29th of February waits for a leap year
+    assert_eq!(next("0 0 29 2 *", now), Some(at(2028, 2, 29, 0, 0)));
+}
+
+#[test]
+fn impossible_dates_never_fire() {
+    let now = at(2025, 1, 15, 10, 30);
+    assert_eq!(next("0 0 31 2 *", now), None);
+    assert_eq!(next("0 0 30 2 *", now), None);
+    assert_eq!(next("0 0 31 4,6,9,11 *", now), None);
+}
+
+#[test]
+fn invalid_expressions_are_rejected() {
+    for expression in [
+        "",
+        "* * * *",
+        "* * * * * *",
+        "60 * * * *",
+        "* 24 * * *",
+        "* * 0 * *",
+        "* * 32 * *",
+        "* * * 13 *",
+        "* * * * 8",
+        "5-1 * * * *",
+        "*/0 * * * *",
+        "a * * * *",
+        "1,,2 * * * *",
+        "@yearly",
+    ] {
+        assert!(Schedule::parse(expression).is_err(), "{:?} was accepted", expression);
+    }
+}
+
+#[test]
+fn output_formats_are_listed_by_name() {
+    assert_eq!(parse_formats("json, xml,bi").unwrap(), [Format::Json, Format::Xml, Format::Bi]);
+    assert_eq!(parse_formats("bi,,json,bi").unwrap(), [Format::Bi, Format::Json]);
+    assert!(parse_formats("json,yaml").is_err());
+    assert!(parse_formats(" , ").is_err());
+}
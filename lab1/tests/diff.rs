@@ -0,0 +1,94 @@
+use lab1::diff::diff_products;
+use lab1::{Data, Product};
+
+fn product(name: &str, price: f64, link: &str) -> Product {
+    Product {
+        name: name.to_string(),
+        price,
+        link: link.to_string(),
+        description: "Ecran: 6.1\"".to_string(),
+        image_url: None,
+        image: None,
+        sku: None,
+        brand: None,
+        availability: None,
+    }
+}
+
+fn names(products: &[Product]) -> Vec<&str> {
+    products.iter().map(|p| p.name.as_str()).collect()
+}
+
+#[test]
+fn identical_lists_have_no_differences() {
+    let products = vec![product("iPhone 15", 12999.0, "https://xstore.md/p/1"), product("Pixel 8", 10999.0, "")];
+    let diff = diff_products(products.clone(), products);
+    assert!(diff.is_empty());
+    assert_eq!(diff.unchanged, 2);
+    assert!(diff.to_string().ends_with("Unchanged: 2"));
+}
+
+#[test]
+fn products_are_matched_by_link_before_name() {
+    let before = vec![
+        product("iPhone 15", 12999.0, "https://xstore.md/p/1"),
+        product("Galaxy A55", 7499.0, "https://xstore.md/p/2"),
+        product("Nokia 3310", 999.0, "https://xstore.md/p/3"),
+        product("Pixel 8", 10999.0, ""),
+    ];
+    let mut renamed = product("Apple iPhone 15 (2023)", 11999.0, " https://xstore.md/p/1 ");
+    renamed.brand = Some("Apple".to_string());
+    let after = vec![
+        product("Galaxy A55", 7499.0, "https://xstore.md/p/2"),
+        renamed,
+        // Same product under a new link and differently written name
+        product("pixel-8", 9999.0, "https://xstore.md/p/4"),
+        product("Galaxy S24", 15999.0, "https://xstore.md/p/5"),
+    ];
+
+    let diff = diff_products(before, after);
+    assert_eq!(names(&diff.added), ["Galaxy S24"]);
+    assert_eq!(names(&diff.removed), ["Nokia 3310"]);
+    assert_eq!(diff.unchanged, 1);
+
+    let changed: Vec<_> = diff.changed.iter().map(|c| (c.name.as_str(), c.matched_by)).collect();
+    assert_eq!(changed, [("Apple iPhone 15 (2023)", "link"), ("pixel-8", "name")]);
+
+    // Fields come sorted; a field only one side has is compared against Null
+    let fields: Vec<_> = diff.changed[0].changes.iter().map(|c| (c.field.as_str(), c.before.clone(), c.after.clone())).collect();
+    assert_eq!(fields, [
+        ("brand", Data::Null, Data::Text("Apple".to_string())),
+        ("link", Data::Text("https://xstore.md/p/1".to_string()), Data::Text(" https://xstore.md/p/1 ".to_string())),
+        ("name", Data::Text("iPhone 15".to_string()), Data::Text("Apple iPhone 15 (2023)".to_string())),
+        ("price", Data::Float(12999.0), Data::Float(11999.0)),
+    ]);
+}
+
+#[test]
+fn a_link_matches_only_once() {
+    let before = vec![product("Phone", 100.0, "https://xstore.md/p/1"), product("Phone", 100.0, "https://xstore.md/p/1")];
+    let after = vec![product("Phone", 100.0, "https://xstore.md/p/1")];
+    let diff = diff_products(before, after);
+    assert_eq!(diff.unchanged, 1);
+    assert_eq!(diff.removed.len(), 1);
+    assert!(diff.added.is_empty());
+}
+
+#[test]
+fn report_shows_price_changes_with_percentages() {
+    let diff = diff_products(
+        vec![product("iPhone 15", 10000.0, "https://xstore.md/p/1"), product("Old", 5.0, "https://xstore.md/p/2")],
+        vec![product("iPhone 15", 9000.0, "https://xstore.md/p/1"), product("New", 7.5, "https://xstore.md/p/3")],
+    );
+    let report = diff.to_string();
+    assert!(report.contains("Added (1):\n  + New <https://xstore.md/p/3> 7.50 MDL\n"), "{}", report);
+    assert!(report.contains("Removed (1):\n  - Old <https://xstore.md/p/2> 5.00 MDL\n"), "{}", report);
+    assert!(report.contains("  ~ iPhone 15 <https://xstore.md/p/1> (matched by link)\n"), "{}", report);
+    assert!(report.contains("      price: 10000 -> 9000 (-1000.00, -10.0%)\n"), "{}", report);
+    assert!(report.ends_with("Unchanged: 0"), "{}", report);
+
+    let Some(Data::List(changed)) = diff.to_data().get("changed").cloned() else { panic!("changed is a list") };
+    assert_eq!(changed[0].get("matched_by"), Some(&Data::Text("link".to_string())));
+    let Some(Data::List(changes)) = changed[0].get("changes").cloned() else { panic!("changes is a list") };
+    assert_eq!(changes[0].get("before"), Some(&Data::Float(10000.0)));
+}
@@ -0,0 +1,143 @@
+use lab1::json::parse_json;
+use lab1::product::{
+    deserialize_products_from_bi, deserialize_products_from_json, deserialize_products_from_xml,
+    serialize_products_to_bi, serialize_products_to_json, serialize_products_to_xml,
+};
+use lab1::{deserialize_products, Data, Format, Product, ProductImage};
+
+fn product(name: &str, price: f64) -> Product {
+    Product {
+        name: name.to_string(),
+        price,
+        link: format!("https://xstore.md/product/{}", name.to_lowercase().replace(' ', "-")),
+        description: "Ecran: 6.1\", Memorie: 128 GB".to_string(),
+        image_url: None,
+        image: None,
+        sku: None,
+        brand: None,
+        availability: None,
+    }
+}
+
+// Products covering escaping, non-ASCII text, fractional prices and optional fields
+fn catalog() -> Vec<Product> {
+    let mut iphone = product("Apple iPhone 15", 12999.0);
+    iphone.image_url = Some("https://xstore.md/img/iphone-15.png?w=300&h=300".to_string());
+    iphone.image = Some(ProductImage { content_type: "image/png".to_string(), data: vec![0x89, b'P', b'N', b'G'] });
+    iphone.sku = Some("MTP03".to_string());
+    iphone.brand = Some("Apple".to_string());
+    iphone.availability = Some("InStock".to_string());
+
+    let mut tricky = product("Husă <Silicon> & \"Sticlă\" 'Pro'", 149.5);
+    tricky.description = "Linia 1\nLinia 2\tcu tab \\ și backslash".to_string();
+    tricky.brand = Some("Ţară".to_string());
+
+    vec![iphone, tricky, product("Смартфон Redmi 13", 2999.99)]
+}
+
+fn fields(products: &[Product]) -> Vec<Data> {
+    products.iter().map(Product::to_data).collect()
+}
+
+#[test]
+fn json_round_trip() {
+    let products = catalog();
+    let decoded = deserialize_products_from_json(&serialize_products_to_json(&products)).unwrap();
+    assert_eq!(fields(&decoded), fields(&products));
+}
+
+#[test]
+fn xml_round_trip() {
+    let products = catalog();
+    let decoded = deserialize_products_from_xml(&serialize_products_to_xml(&products)).unwrap();
+    assert_eq!(fields(&decoded), fields(&products));
+}
+
+#[test]
+fn bi_round_trip() {
+    let products = catalog();
+    let decoded = deserialize_products_from_bi(&serialize_products_to_bi(&products)).unwrap();
+    assert_eq!(fields(&decoded), fields(&products));
+}
+
+#[test]
+fn images_round_trip_in_every_format() {
+    let mut products = catalog();
+    // Every byte value, so nothing survives by accident of being text
+    let png: Vec<u8> = (0..=255).chain([0x89, b'P', b'N', b'G']).collect();
+    products[1].image = Some(ProductImage { content_type: "image/png".to_string(), data: png.clone() });
+
+    for format in Format::ALL {
+        let decoded = format.decode(&format.encode(&products)).unwrap();
+        let images: Vec<Option<(&str, &[u8])>> = decoded.iter()
+            .map(|p| p.image.as_ref().map(|image| (image.content_type.as_str(), image.data.as_slice())))
+            .collect();
+        assert_eq!(images, [Some(("image/png", &[0x89, b'P', b'N', b'G'][..])), Some(("image/png", &png[..])), None], "{:?}", format);
+    }
+
+    // Outputs from before images were exported describe them without the bytes
+    let old = format!("Products {}", Data::List(fields(&products)).to_bi(0));
+    assert!(deserialize_products(&old).unwrap().iter().all(|p| p.image.is_none()));
+    let corrupt = "{\"products\": [{\"name\": \"Phone\", \"price\": 1, \"image\": {\"content_type\": \"image/png\", \"data\": \"not base64!\"}}]}";
+    assert!(deserialize_products(corrupt).is_err());
+}
+
+#[test]
+fn empty_lists_round_trip() {
+    for format in Format::ALL {
+        let decoded = format.decode(&format.encode(&[])).unwrap();
+        assert!(decoded.is_empty(), "{:?}", format);
+    }
+}
+
+#[test]
+fn json_output_is_valid_json() {
+    let json = parse_json(&serialize_products_to_json(&catalog())).unwrap();
+    assert!(json.get("timestamp").and_then(Data::as_str).is_some());
+
+    let Some(Data::List(products)) = json.get("products") else {
+        panic!("no products list in {:?}", json);
+    };
+    assert_eq!(products.len(), 3);
+    assert_eq!(products[1].get("name").and_then(Data::as_str), Some("Husă <Silicon> & \"Sticlă\" 'Pro'"));
+    assert_eq!(products[2].get("price").and_then(Data::as_f64), Some(2999.99));
+    assert_eq!(products[2].get("sku"), Some(&Data::Null));
+}
+
+#[test]
+fn xml_output_escapes_markup() {
+    let xml = serialize_products_to_xml(&catalog());
+    assert!(xml.starts_with("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<productList>"));
+    assert!(xml.contains("<name>Husă &lt;Silicon&gt; &amp; &quot;Sticlă&quot; &apos;Pro&apos;</name>"));
+    assert!(xml.contains("<image_url>https://xstore.md/img/iphone-15.png?w=300&amp;h=300</image_url>"));
+    assert!(xml.contains("<sku></sku>"));
+}
+
+#[test]
+fn bi_output_describes_images_by_size() {
+    let bi = serialize_products_to_bi(&catalog());
+    assert!(bi.starts_with("Products ["));
+    assert!(bi.contains("content_type [\n"));
+    assert!(bi.contains("\"image/png\""));
+    assert!(bi.contains("size [\n"));
+}
+
+#[test]
+fn detects_the_format_of_saved_outputs() {
+    let products = catalog();
+    for format in Format::ALL {
+        let text = format.encode(&products);
+        assert_eq!(Format::detect(&text), format);
+        assert_eq!(fields(&deserialize_products(&text).unwrap()), fields(&products), "{:?}", format);
+    }
+    assert_eq!(Format::detect("\u{feff}  {\"products\": []}"), Format::Json);
+}
+
+#[test]
+fn rejects_malformed_input() {
+    assert!(deserialize_products("{\"products\": [").is_err());
+    assert!(deserialize_products("{\"items\": []}").is_err());
+    assert!(deserialize_products("<productList><product><name>x</name></product></productList>").is_err());
+    assert!(deserialize_products("Products [\n    name [\n").is_err());
+    assert!(deserialize_products("Inventory [\n]").is_err());
+}
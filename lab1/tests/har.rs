@@ -0,0 +1,161 @@
+mod common;
+
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use common::{client, shop, FixtureServer, Reply, PNG};
+use chrono::Utc;
+use lab1::har::{HarArchive, HarEntry, Timings};
+use lab1::http::{Limits, Response, Traffic};
+use lab1::json::parse_json;
+use lab1::scraping::{scrape_products, ScrapeResult};
+use lab1::{base64, Client, Data};
+
+fn archive_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("lab1-{}-{}.har", name, std::process::id()))
+}
+
+fn recording_client() -> (Client, Arc<Mutex<HarArchive>>) {
+    let archive = Arc::new(Mutex::new(HarArchive::new()));
+    let mut client = client();
+    client.traffic = Traffic::Record(Arc::clone(&archive));
+    (client, archive)
+}
+
+fn replaying_client(path: &Path) -> Client {
+    let mut client = client();
+    client.traffic = Traffic::Replay(Arc::new(HarArchive::load(path).unwrap()));
+    client
+}
+
+fn summary(result: &ScrapeResult) -> Vec<String> {
+    let mut lines: Vec<String> = result.products.iter()
+        .map(|p| format!("{} {} {} {:?}", p.name, p.price, p.link, p.image.as_ref().map(|i| i.data.len())))
+        .collect();
+    lines.extend(result.rejected.iter().map(|r| r.to_string()));
+    lines
+}
+
+#[test]
+fn replays_a_recorded_scrape_without_the_network() {
+    let server = FixtureServer::https(shop());
+    let (recorder, archive) = recording_client();
+    let live = scrape_products(&recorder, &server.url("/")).unwrap();
+
+    let path = archive_path("replay");
+    archive.lock().unwrap().save(&path).unwrap();
+
+    let replayed = scrape_products(&replaying_client(&path), &server.url("/")).unwrap();
+    assert_eq!(summary(&replayed), summary(&live));
+    assert_eq!(replayed.products[0].image.as_ref().unwrap().data, PNG);
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn archives_are_har_1_2() {
+    let server = FixtureServer::https(shop());
+    let (recorder, archive) = recording_client();
+    scrape_products(&recorder, &server.url("/")).unwrap();
+    let path = archive_path("format");
+    archive.lock().unwrap().save(&path).unwrap();
+
+    let har = parse_json(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    std::fs::remove_file(path).unwrap();
+    let log = har.get("log").unwrap();
+    assert_eq!(log.get("version").and_then(Data::as_str), Some("1.2"));
+    let Some(Data::List(entries)) = log.get("entries") else { panic!("no entries") };
+    // Listing, five detail pages and two images
+    assert_eq!(entries.len(), 8);
+
+    let listing = &entries[0];
+    assert_eq!(listing.get("request").and_then(|r| r.get("url")).and_then(Data::as_str), Some(server.url("/").as_str()));
+    let response = listing.get("response").unwrap();
+    assert_eq!(response.get("status").and_then(Data::as_f64), Some(200.0));
+    assert_eq!(response.get("statusText").and_then(Data::as_str), Some("OK"));
+    let content = response.get("content").unwrap();
+    assert!(content.get("text").and_then(Data::as_str).unwrap().contains("xp-title"));
+    assert!(content.get("encoding").is_none());
+    let timings = listing.get("timings").unwrap();
+    assert!(timings.get("ssl").and_then(Data::as_f64).unwrap() >= 0.0);
+    assert!(timings.get("wait").and_then(Data::as_f64).unwrap() >= 0.0);
+
+    let image = entries.iter()
+        .find(|e| e.get("request").and_then(|r| r.get("url")).and_then(Data::as_str).is_some_and(|u| u.ends_with(".png")))
+        .unwrap();
+    let content = image.get("response").and_then(|r| r.get("content")).unwrap();
+    assert_eq!(content.get("mimeType").and_then(Data::as_str), Some("image/png"));
+    assert_eq!(content.get("encoding").and_then(Data::as_str), Some("base64"));
+    assert_eq!(base64::decode(content.get("text").and_then(Data::as_str).unwrap()).unwrap(), PNG);
+}
+
+#[test]
+fn replays_recorded_statuses_and_rejects_unknown_urls() {
+    let server = FixtureServer::http(shop());
+    let (recorder, archive) = recording_client();
+    recorder.fetch(&server.url("/product/pixel-8"), &Limits::default()).unwrap();
+    recorder.fetch(&server.url("/product/missing"), &Limits::default()).unwrap();
+    recorder.fetch(&server.url("/product/nothing-phone-2a"), &Limits::default()).unwrap();
+    let path = archive_path("order");
+    archive.lock().unwrap().save(&path).unwrap();
+
+    let replayer = replaying_client(&path);
+    std::fs::remove_file(path).unwrap();
+    assert_eq!(replayer.fetch(&server.url("/product/missing#specs"), &Limits::default()).unwrap().status, 404);
+    assert_eq!(replayer.fetch(&server.url("/product/nothing-phone-2a"), &Limits::default()).unwrap().status, 500);
+    let error = replayer.fetch(&server.url("/product/iphone-15"), &Limits::default()).unwrap_err();
+    assert!(error.to_string().starts_with("No recorded response for GET"), "{}", error);
+    // Body limits apply to replayed responses as well
+    let tight = Limits { max_body_bytes: 16, ..Limits::default() };
+    assert!(replayer.fetch(&server.url("/product/pixel-8"), &tight).is_err());
+}
+
+#[test]
+fn replays_recordings_of_a_url_in_order() {
+    let mut archive = HarArchive::new();
+    for status in [503, 200] {
+        archive.record(HarEntry {
+            started: Utc::now(),
+            method: "GET".to_string(),
+            url: "https://xstore.md/".to_string(),
+            request_headers: Vec::new(),
+            response: Ok(Response { status, headers: vec![format!("HTTP/1.1 {}", status)], body: Vec::new() }),
+            timings: Timings::default(),
+        });
+    }
+
+    let statuses: Vec<u32> = (0..3)
+        .map(|_| archive.replay("https://xstore.md/", &Limits::default()).unwrap().status)
+        .collect();
+    assert_eq!(statuses, [503, 200, 200]);
+}
+
+#[test]
+fn failed_requests_are_recorded_and_replayed_as_failures() {
+    let mut routes = shop();
+    routes.insert("/", Reply::html("<html></html>"));
+    let server = FixtureServer::http(routes);
+    let dead_url = format!("http://127.0.0.1:{}/", {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap().port()
+    });
+
+    let (recorder, archive) = recording_client();
+    recorder.fetch(&server.url("/"), &Limits::default()).unwrap();
+    assert!(recorder.fetch(&dead_url, &Limits::default()).is_err());
+    let path = archive_path("failure");
+    archive.lock().unwrap().save(&path).unwrap();
+
+    let replayer = replaying_client(&path);
+    std::fs::remove_file(path).unwrap();
+    assert!(replayer.fetch(&server.url("/"), &Limits::default()).is_ok());
+    assert!(replayer.fetch(&dead_url, &Limits::default()).is_err());
+}
+
+#[test]
+fn base64_round_trip() {
+    for input in [&b""[..], b"f", b"fo", b"foo", b"foob", PNG] {
+        assert_eq!(base64::decode(&base64::encode(input)).unwrap(), input);
+    }
+    assert_eq!(base64::encode(b"user:pass"), "dXNlcjpwYXNz");
+    assert_eq!(base64::decode("dXNl\r\ncjpw YXNz").unwrap(), b"user:pass");
+    assert!(base64::decode("not base64!").is_err());
+}
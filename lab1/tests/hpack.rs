@@ -0,0 +1,88 @@
+use lab1::hpack::{encode, Decoder};
+
+fn hex(text: &str) -> Vec<u8> {
+    let digits: String = text.split_whitespace().collect();
+    (0..digits.len()).step_by(2).map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap()).collect()
+}
+
+fn owned(headers: &[(&str, &str)]) -> Vec<(String, String)> {
+    headers.iter().map(|(name, value)| (name.to_string(), value.to_string())).collect()
+}
+
+// RFC 7541 C.4: three requests on one connection, Huffman-coded and sharing the dynamic table
+#[test]
+fn decodes_the_rfc_request_examples() {
+    let mut decoder = Decoder::new();
+
+    let first = decoder.decode(&hex("8286 8441 8cf1 e3c2 e5f2 3a6b a0ab 90f4 ff")).unwrap();
+    assert_eq!(first, owned(&[(":method", "GET"), (":scheme", "http"), (":path", "/"), (":authority", "www.example.com")]));
+
+    let second = decoder.decode(&hex("8286 84be 5886 a8eb 1064 9cbf")).unwrap();
+    assert_eq!(second, owned(&[
+        (":method", "GET"), (":scheme", "http"), (":path", "/"), (":authority", "www.example.com"),
+        ("cache-control", "no-cache"),
+    ]));
+
+    let third = decoder.decode(&hex("8287 85bf 4088 25a8 49e9 5ba9 7d7f 8925 a849 e95b b8e8 b4bf")).unwrap();
+    assert_eq!(third, owned(&[
+        (":method", "GET"), (":scheme", "https"), (":path", "/index.html"), (":authority", "www.example.com"),
+        ("custom-key", "custom-value"),
+    ]));
+}
+
+// RFC 7541 C.6: responses whose entries get evicted from a 256-byte table
+#[test]
+fn evicts_entries_when_the_table_shrinks() {
+    let mut decoder = Decoder::new();
+    decoder.decode(&hex("3fe1 01")).unwrap(); // Table size update to 256 bytes
+
+    let first = decoder.decode(&hex(
+        "4882 6402 5885 aec3 771a 4b61 96d0 7abe 9410 54d4 44a8 2005 9504 0b81 66e0 82a6 2d1b ff6e 919d 29ad 1718 63c7 8f0b 97c8 e9ae 82ae 43d3",
+    )).unwrap();
+    assert_eq!(first, owned(&[
+        (":status", "302"), ("cache-control", "private"), ("date", "Mon, 21 Oct 2013 20:13:21 GMT"),
+        ("location", "https://www.example.com"),
+    ]));
+
+    let second = decoder.decode(&hex("4883 640e ffc1 c0bf")).unwrap();
+    assert_eq!(second, owned(&[
+        (":status", "307"), ("cache-control", "private"), ("date", "Mon, 21 Oct 2013 20:13:21 GMT"),
+        ("location", "https://www.example.com"),
+    ]));
+
+    // Its indexes only line up if the older entries were evicted as the new ones came in
+    let third = decoder.decode(&hex(
+        "88c1 6196 d07a be94 1054 d444 a820 0595 040b 8166 e084 a62d 1bff c05a 839b d9ab 77ad 94e7 821d d7f2 e6c7 b335 dfdf cd5b 3960 d5af 2708 7f36 72c1 ab27 0fb5 291f 9587 3160 65c0 03ed 4ee5 b106 3d50 07",
+    )).unwrap();
+    assert_eq!(third, owned(&[
+        (":status", "200"), ("cache-control", "private"), ("date", "Mon, 21 Oct 2013 20:13:22 GMT"),
+        ("location", "https://www.example.com"), ("content-encoding", "gzip"),
+        ("set-cookie", "foo=ASDJKHQKBZXOQWEOPIUAXQWEOIU; max-age=3600; version=1"),
+    ]));
+}
+
+#[test]
+fn decodes_what_it_encodes() {
+    let headers = [
+        (":method", "GET"),
+        (":scheme", "https"),
+        (":authority", "localhost:8443"),
+        (":path", "/product/iphone-15?utm_source=newsletter"),
+        ("user-agent", "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36"),
+        ("cookie", "lang=ro; currency=MDL"),
+        ("x-long-value", &"ă".repeat(300)),
+    ];
+    assert_eq!(Decoder::new().decode(&encode(&headers)).unwrap(), owned(&headers));
+}
+
+#[test]
+fn rejects_malformed_blocks() {
+    // Index beyond the static and (empty) dynamic table
+    assert!(Decoder::new().decode(&hex("be")).is_err());
+    // String longer than the block
+    assert!(Decoder::new().decode(&hex("0085 6162")).is_err());
+    // Huffman string padded with zero bits instead of the EOS prefix
+    assert!(Decoder::new().decode(&hex("0081 00 00")).is_err());
+    // Integer continuation that never ends
+    assert!(Decoder::new().decode(&hex("ff ff ff")).is_err());
+}
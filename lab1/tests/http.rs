@@ -0,0 +1,128 @@
+mod common;
+
+use std::io::{BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread;
+use std::time::{Duration, Instant};
+use common::{client, FixtureServer, Reply, Routes};
+use encoding_rs::WINDOWS_1251;
+use lab1::http::Limits;
+
+// Server that reads one request and hands the connection to `respond`, for replies the
+// fixture server can't make: bodies that run until close, stalls and slow drips
+fn stand_in(respond: impl FnOnce(TcpStream) + Send + 'static) -> String {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://127.0.0.1:{}/", listener.local_addr().unwrap().port());
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut line = String::new();
+        while reader.read_line(&mut line).is_ok_and(|read| read > 0) && line != "\r\n" {
+            line.clear();
+        }
+        respond(stream);
+    });
+    url
+}
+
+#[test]
+fn decodes_text_in_the_declared_charset() {
+    let (cyrillic, _, _) = WINDOWS_1251.encode("Телефон — 12 999 лей");
+    let meta = [
+        &b"<html><head><meta charset=\"windows-1251\"></head><body>"[..],
+        &cyrillic,
+        b"</body></html>",
+    ]
+    .concat();
+    let http_equiv = [
+        &b"<meta http-equiv=\"Content-Type\" content=\"text/html; charset=cp1251\">"[..],
+        &cyrillic,
+    ]
+    .concat();
+    let server = FixtureServer::http(Routes::from([
+        ("/header", Reply::bytes("text/html; charset=\"Windows-1251\"", &cyrillic)),
+        ("/meta", Reply::bytes("text/html", &meta)),
+        ("/http-equiv", Reply::bytes("text/html", &http_equiv)),
+        // The header wins over the document
+        ("/both", Reply::bytes("text/html; charset=utf-8", "<meta charset=\"windows-1251\">Телефон".as_bytes())),
+        ("/none", Reply::bytes("text/html", "Телефон".as_bytes())),
+    ]));
+    let client = client();
+    let text = |path: &str| client.fetch(&server.url(path), &Limits::default()).unwrap().text();
+
+    assert_eq!(text("/header"), "Телефон — 12 999 лей");
+    assert_eq!(text("/meta"), "<html><head><meta charset=\"windows-1251\"></head><body>Телефон — 12 999 лей</body></html>");
+    assert!(text("/http-equiv").ends_with("Телефон — 12 999 лей"));
+    assert_eq!(text("/both"), "<meta charset=\"windows-1251\">Телефон");
+    // Without a declaration the body is taken to be UTF-8
+    assert_eq!(text("/none"), "Телефон");
+}
+
+#[test]
+fn bodies_over_the_limit_are_refused_however_they_are_framed() {
+    let body = vec![b'x'; 100];
+    let server = FixtureServer::http(Routes::from([
+        ("/length", Reply::bytes("text/plain", &body)),
+        ("/chunked", Reply::bytes("text/plain", &body).chunked(30)),
+    ]));
+    let client = client();
+    let exact = Limits { max_body_bytes: 100, ..Limits::default() };
+    let tight = Limits { max_body_bytes: 99, ..Limits::default() };
+
+    for path in ["/length", "/chunked"] {
+        assert_eq!(client.fetch(&server.url(path), &exact).unwrap().body, body, "{}", path);
+        let error = client.fetch(&server.url(path), &tight).unwrap_err();
+        assert_eq!(error.to_string(), "Response body exceeds 99 bytes", "{}", path);
+    }
+
+    // Without Content-Length or chunking the body runs until the server closes
+    let until_close = |body: Vec<u8>| {
+        stand_in(move |mut stream| {
+            stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nConnection: close\r\n\r\n").unwrap();
+            stream.write_all(&body).unwrap();
+        })
+    };
+    assert_eq!(client.fetch(&until_close(body.clone()), &exact).unwrap().body, body);
+    let error = client.fetch(&until_close(body.clone()), &tight).unwrap_err();
+    assert_eq!(error.to_string(), "Response body exceeds 99 bytes");
+}
+
+#[test]
+fn a_huge_chunk_size_is_refused_rather_than_overflowing() {
+    let url = stand_in(|mut stream| {
+        let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nabcd\r\nffffffffffffffff\r\n");
+    });
+    let error = client().fetch(&url, &Limits::default()).unwrap_err();
+    assert_eq!(error.to_string(), format!("Response body exceeds {} bytes", Limits::default().max_body_bytes));
+}
+
+#[test]
+fn the_timeout_covers_stalled_and_slow_servers() {
+    let limits = Limits { timeout: Duration::from_millis(500), ..Limits::default() };
+    let client = client();
+
+    // Reads the request and never answers
+    let stalled = stand_in(|stream| {
+        thread::sleep(Duration::from_secs(3));
+        drop(stream);
+    });
+    let started = Instant::now();
+    let error = client.fetch(&stalled, &limits).unwrap_err();
+    assert_eq!(error.to_string(), "Response deadline exceeded");
+    assert!(started.elapsed() < Duration::from_secs(2), "took {:?}", started.elapsed());
+
+    // Answers at once, then drips the body a byte at a time, each well within the timeout
+    let slow = stand_in(|mut stream| {
+        let _ = stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 50\r\n\r\n");
+        for _ in 0..50 {
+            thread::sleep(Duration::from_millis(100));
+            if stream.write_all(b"x").is_err() {
+                return;
+            }
+        }
+    });
+    let started = Instant::now();
+    let error = client.fetch(&slow, &limits).unwrap_err();
+    assert_eq!(error.to_string(), "Response deadline exceeded");
+    assert!(started.elapsed() < Duration::from_secs(2), "took {:?}", started.elapsed());
+}
@@ -0,0 +1,183 @@
+mod common;
+
+use std::path::PathBuf;
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use common::http2::Http2Server;
+use common::{client, shop, FixtureServer, Reply, PNG};
+use lab1::cookies::CookieJar;
+use lab1::har::HarArchive;
+use lab1::http::{Limits, Traffic};
+use lab1::json::parse_json;
+use lab1::proxy::ProxyConfig;
+use lab1::scraping::scrape_products;
+use lab1::tls::TlsOptions;
+use lab1::{Client, Data};
+
+fn http1_client() -> Client {
+    let tls = TlsOptions {
+        ca_certs: vec![PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls/ca.pem")],
+        http1_only: true,
+        ..TlsOptions::default()
+    };
+    Client::new(ProxyConfig::default(), CookieJar::new(), &tls).unwrap()
+}
+
+#[test]
+fn scrapes_the_shop_over_one_http2_connection() {
+    let server = Http2Server::start(shop(), Duration::from_millis(50));
+    let result = scrape_products(&client(), &server.url("/")).unwrap();
+
+    let names: Vec<&str> = result.products.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["Apple iPhone 15 128GB Black", "Samsung Galaxy A55 8/256GB Awesome Navy"]);
+    assert_eq!(result.rejected.len(), 3);
+    assert_eq!(result.products[1].sku.as_deref(), Some("SM-A556B"));
+    assert_eq!(result.products[0].image.as_ref().map(|image| image.data.as_slice()), Some(PNG));
+
+    // The listing, five product pages and two images
+    assert_eq!(server.stats.requests.load(Ordering::SeqCst), 8);
+    assert_eq!(server.stats.connections.load(Ordering::SeqCst), 1);
+    assert_eq!(server.stats.http2_connections.load(Ordering::SeqCst), 1);
+    assert!(server.stats.max_concurrent_streams.load(Ordering::SeqCst) > 1, "detail pages were fetched one by one");
+}
+
+#[test]
+fn concurrent_requests_share_one_connection() {
+    let mut routes = shop();
+    routes.insert("/slow", Reply::html("<html><body>slow</body></html>"));
+    let server = Http2Server::start(routes, Duration::from_millis(300));
+    let client = client();
+
+    let responses: Vec<_> = thread::scope(|scope| {
+        let fetches: Vec<_> = (0..6)
+            .map(|_| scope.spawn(|| client.fetch(&server.url("/slow"), &Limits::default()).unwrap()))
+            .collect();
+        fetches.into_iter().map(|fetch| fetch.join().unwrap()).collect()
+    });
+
+    for response in &responses {
+        assert_eq!(response.status, 200);
+        assert_eq!(response.headers[0], "HTTP/2 200");
+        assert_eq!(response.content_type().as_deref(), Some("text/html"));
+        assert_eq!(response.text(), "<html><body>slow</body></html>");
+    }
+    assert_eq!(server.stats.connections.load(Ordering::SeqCst), 1);
+    assert_eq!(server.stats.max_concurrent_streams.load(Ordering::SeqCst), 6);
+}
+
+#[test]
+fn a_stalled_handshake_does_not_hold_up_other_origins() {
+    // Accepts connections but never answers the TLS handshake
+    let stalled = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let stalled_url = format!("https://localhost:{}/", stalled.local_addr().unwrap().port());
+    let server = Http2Server::start(shop(), Duration::ZERO);
+    let client = client();
+    let limits = Limits { timeout: Duration::from_secs(3), ..Limits::default() };
+
+    thread::scope(|scope| {
+        let stalled_fetch = scope.spawn(|| client.fetch(&stalled_url, &limits).is_err());
+        thread::sleep(Duration::from_millis(200));
+
+        let started = std::time::Instant::now();
+        client.fetch(&server.url("/"), &Limits::default()).unwrap();
+        assert!(started.elapsed() < Duration::from_secs(2), "waited for the stalled origin");
+        assert!(stalled_fetch.join().unwrap());
+    });
+    drop(stalled);
+}
+
+#[test]
+fn sends_the_authority_with_its_port() {
+    let server = Http2Server::start(shop(), Duration::ZERO);
+    client().fetch(&server.url("/product/pixel-8?ref=listing"), &Limits::default()).unwrap();
+    http1_client().fetch(&server.url("/"), &Limits::default()).unwrap();
+
+    let port = server.url("").rsplit(':').next().unwrap().to_string();
+    let requested = server.stats.requested.lock().unwrap().clone();
+    assert_eq!(requested, [
+        (format!("localhost:{}", port), "/product/pixel-8?ref=listing".to_string()),
+        (format!("localhost:{}", port), "/".to_string()),
+    ]);
+}
+
+#[test]
+fn reads_bodies_larger_than_the_flow_control_window() {
+    let large: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i % 251) as u8).collect();
+    let mut routes = shop();
+    routes.insert("/large.bin", Reply::bytes("application/octet-stream", &large));
+    let server = Http2Server::start(routes, Duration::ZERO);
+
+    let response = client().fetch(&server.url("/large.bin"), &Limits::default()).unwrap();
+    assert_eq!(response.body.len(), large.len());
+    assert!(response.body == large);
+
+    let limits = Limits { max_body_bytes: 1024 * 1024, ..Limits::default() };
+    let error = client().fetch(&server.url("/large.bin"), &limits).unwrap_err();
+    assert_eq!(error.to_string(), "Response body exceeds 1048576 bytes");
+}
+
+#[test]
+fn error_statuses_and_empty_bodies_come_through() {
+    let mut routes = shop();
+    routes.insert("/moved", Reply::redirect(301, "/"));
+    let server = Http2Server::start(routes, Duration::ZERO);
+    let client = client();
+
+    let moved = client.fetch(&server.url("/moved"), &Limits::default()).unwrap();
+    assert_eq!(moved.status, 301);
+    assert_eq!(moved.header("location"), Some("/"));
+    assert!(moved.body.is_empty());
+
+    let missing = client.fetch(&server.url("/no-such-page"), &Limits::default()).unwrap();
+    assert_eq!(missing.status, 404);
+    assert_eq!(server.stats.connections.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn falls_back_to_http1_when_the_server_does_not_offer_http2() {
+    let server = FixtureServer::https(shop());
+    let client = client();
+    let first = client.fetch(&server.url("/"), &Limits::default()).unwrap();
+    let second = client.fetch(&server.url("/product/iphone-15"), &Limits::default()).unwrap();
+    assert!(first.headers[0].starts_with("HTTP/1.1 200"), "{}", first.headers[0]);
+    assert!(second.headers[0].starts_with("HTTP/1.1 200"), "{}", second.headers[0]);
+}
+
+#[test]
+fn http1_only_clients_never_negotiate_http2() {
+    let server = Http2Server::start(shop(), Duration::ZERO);
+    let result = scrape_products(&http1_client(), &server.url("/")).unwrap();
+
+    assert_eq!(result.products.len(), 2);
+    assert_eq!(server.stats.http2_connections.load(Ordering::SeqCst), 0);
+    // Every HTTP/1.1 request has a connection of its own
+    assert_eq!(server.stats.connections.load(Ordering::SeqCst), 8);
+}
+
+#[test]
+fn recordings_keep_the_http_version() {
+    let server = Http2Server::start(shop(), Duration::ZERO);
+    let mut client = client();
+    let archive = Arc::new(Mutex::new(HarArchive::new()));
+    client.traffic = Traffic::Record(Arc::clone(&archive));
+    client.fetch(&server.url("/"), &Limits::default()).unwrap();
+
+    let path = std::env::temp_dir().join(format!("lab1-http2-{}.har", std::process::id()));
+    archive.lock().unwrap().save(&path).unwrap();
+    let har = parse_json(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    let replayed = HarArchive::load(&path).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    let Some(Data::List(entries)) = har.get("log").and_then(|log| log.get("entries")) else {
+        panic!("no entries in {:?}", har);
+    };
+    for message in ["request", "response"] {
+        let version = entries[0].get(message).and_then(|m| m.get("httpVersion")).and_then(Data::as_str);
+        assert_eq!(version, Some("HTTP/2"), "{}", message);
+    }
+    let response = replayed.entries()[0].response.as_ref().unwrap();
+    assert_eq!(response.headers[0], "HTTP/2 200");
+    assert_eq!(response.body, shop()["/"].body);
+}
@@ -0,0 +1,99 @@
+use lab1::normalize::{canonical_url, deduplicate, name_key, normalize_name, ConflictPolicy};
+use lab1::Product;
+
+fn product(name: &str, price: f64, link: &str) -> Product {
+    Product {
+        name: name.to_string(),
+        price,
+        link: link.to_string(),
+        description: String::new(),
+        image_url: None,
+        image: None,
+        sku: None,
+        brand: None,
+        availability: None,
+    }
+}
+
+#[test]
+fn canonical_urls_drop_tracking_and_fragments() {
+    assert_eq!(
+        canonical_url(" HTTPS://XStore.md:443/product/iphone-15?utm_source=mail&color=black&gclid=abc&UTM_Medium=x#reviews "),
+        "https://xstore.md/product/iphone-15?color=black",
+    );
+    assert_eq!(canonical_url("https://xstore.md/product/1?fbclid=1&ref=home"), "https://xstore.md/product/1");
+    assert_eq!(canonical_url("http://xstore.md:80"), "http://xstore.md/");
+    // Parameters that may change the page stay, in order
+    assert_eq!(canonical_url("https://xstore.md/p?b=2&a=1"), "https://xstore.md/p?b=2&a=1");
+    // Relative links can't be parsed and are only trimmed
+    assert_eq!(canonical_url(" /product/1?utm_source=x "), "/product/1?utm_source=x");
+}
+
+#[test]
+fn names_are_normalized_for_display() {
+    assert_eq!(normalize_name("  Apple\u{a0}iPhone   15\t128GB "), "Apple iPhone 15 128GB");
+    // Compatibility forms such as full-width letters and ligatures fold to plain text
+    assert_eq!(normalize_name("ｉPhone ﬁ"), "iPhone fi");
+    // Accents are kept for display
+    assert_eq!(normalize_name("Telefon Pró"), "Telefon Pró");
+}
+
+#[test]
+fn name_keys_ignore_case_accents_and_punctuation() {
+    assert_eq!(name_key("Apple  iPhone-15 Pró"), "apple iphone 15 pro");
+    assert_eq!(name_key("apple iphone 15 pro"), "apple iphone 15 pro");
+    assert_eq!(name_key("Șurubelniță (set)"), "surubelnita set");
+    assert_ne!(name_key("iPhone 15"), name_key("iPhone 15 Pro"));
+}
+
+#[test]
+fn duplicates_merge_by_canonical_link_or_name() {
+    let products = vec![
+        product("iPhone  15", 12999.0, "https://xstore.md/p/1?utm_source=a"),
+        product("Pixel 8", 10999.0, ""),
+        product("iPhone 15", 11999.0, "https://XSTORE.md/p/1#specs"),
+        product("pixel-8", 9999.0, " "),
+        product("Pixel 8", 8999.0, "https://xstore.md/p/8"),
+    ];
+    let merged = deduplicate(products, ConflictPolicy::First);
+    let rows: Vec<_> = merged.iter().map(|p| (p.name.as_str(), p.price, p.link.as_str())).collect();
+    assert_eq!(rows, [
+        ("iPhone 15", 12999.0, "https://xstore.md/p/1"),
+        ("Pixel 8", 10999.0, ""),
+        // A product with a link is never merged by name
+        ("Pixel 8", 8999.0, "https://xstore.md/p/8"),
+    ]);
+}
+
+#[test]
+fn the_policy_picks_the_product_and_others_fill_gaps() {
+    let mut first = product("Phone", 300.0, "https://xstore.md/p/1");
+    first.description = "Attributes not found".to_string();
+    let mut cheap = product("Phone", 100.0, "https://xstore.md/p/1");
+    cheap.description = "Ecran: 6.1\"".to_string();
+    cheap.brand = Some("Acme".to_string());
+    let mut last = product("Phone", 200.0, "https://xstore.md/p/1");
+    last.sku = Some("PH-1".to_string());
+    last.brand = Some("Other".to_string());
+    let products = vec![first, cheap, last];
+
+    let pick = |policy| deduplicate(products.clone(), policy).remove(0);
+    assert_eq!(pick(ConflictPolicy::First).price, 300.0);
+    assert_eq!(pick(ConflictPolicy::Last).price, 200.0);
+    assert_eq!(pick(ConflictPolicy::Cheapest).price, 100.0);
+    assert_eq!(pick(ConflictPolicy::Priciest).price, 300.0);
+
+    // The placeholder description counts as missing
+    let first = pick(ConflictPolicy::First);
+    assert_eq!(first.description, "Ecran: 6.1\"");
+    assert_eq!((first.brand.as_deref(), first.sku.as_deref()), (Some("Acme"), Some("PH-1")));
+    // The pick's own fields win over the others
+    assert_eq!(pick(ConflictPolicy::Last).brand.as_deref(), Some("Other"));
+}
+
+#[test]
+fn conflict_policies_parse() {
+    assert_eq!(ConflictPolicy::parse(" Cheapest ").unwrap(), ConflictPolicy::Cheapest);
+    assert_eq!(ConflictPolicy::parse("last").unwrap(), ConflictPolicy::Last);
+    assert!(ConflictPolicy::parse("random").is_err());
+}
@@ -0,0 +1,113 @@
+use lab1::price::{parse_price, to_mdl, NumberLocale, Price, PriceError};
+
+const RO: Option<NumberLocale> = Some(NumberLocale::Romanian);
+const RU: Option<NumberLocale> = Some(NumberLocale::Russian);
+const EN: Option<NumberLocale> = Some(NumberLocale::English);
+
+fn amount(text: &str, locale: Option<NumberLocale>) -> f64 {
+    parse_price(text, locale).unwrap_or_else(|e| panic!("{:?}: {}", text, e)).amount
+}
+
+fn price(amount: f64, currency: &str) -> Price {
+    Price { amount, currency: Some(currency.to_string()), max_amount: None }
+}
+
+#[test]
+fn a_lone_separator_before_three_digits_needs_a_locale() {
+    assert_eq!(parse_price("1.234", None), Err(PriceError::AmbiguousSeparator('.')));
+    assert_eq!(parse_price("1,234 lei", None), Err(PriceError::AmbiguousSeparator(',')));
+
+    // English reads a dot as the decimal point, Romanian and Russian as grouping
+    assert_eq!(amount("1.234", EN), 1.234);
+    assert_eq!(amount("1.234", RO), 1234.0);
+    assert_eq!(amount("1.234", RU), 1234.0);
+    assert_eq!(amount("1,234", EN), 1234.0);
+    assert_eq!(amount("1,234", RO), 1.234);
+}
+
+#[test]
+fn unambiguous_separators_need_no_locale() {
+    // Not three digits after the mark, so it can only be a decimal point
+    assert_eq!(amount("65.50", None), 65.5);
+    assert_eq!(amount("65,5", None), 65.5);
+    assert_eq!(amount("1.2345", None), 1.2345);
+    // Two different marks: the last one is the decimal point
+    assert_eq!(amount("1.299,00", None), 1299.0);
+    assert_eq!(amount("1,299.99", None), 1299.99);
+    // Repeated marks can only be grouping
+    assert_eq!(amount("1.234.567", None), 1234567.0);
+    assert_eq!(amount("1,234,567", EN), 1234567.0);
+    // Spaces and apostrophes group too, even the non-breaking kinds
+    assert_eq!(amount("12 999", None), 12999.0);
+    assert_eq!(amount("12\u{a0}999", None), 12999.0);
+    assert_eq!(amount("1'299.50", None), 1299.5);
+}
+
+#[test]
+fn grouping_must_use_three_digit_groups_and_one_mark() {
+    for text in ["1.23.456", "1234.567.890", "1 234,567 890.5", "12 34"] {
+        assert!(matches!(parse_price(text, RO), Err(PriceError::Unparseable(_))), "{:?} was accepted", text);
+    }
+    assert!(matches!(parse_price("1.234 567", None), Err(PriceError::Unparseable(_))));
+}
+
+#[test]
+fn currencies_are_recognised_in_every_language() {
+    assert_eq!(parse_price("12 999 lei", None), Ok(price(12999.0, "MDL")));
+    assert_eq!(parse_price("999 лей", None), Ok(price(999.0, "MDL")));
+    assert_eq!(parse_price("€ 65.50", None), Ok(price(65.5, "EUR")));
+    assert_eq!(parse_price("$1,299.99", None), Ok(price(1299.99, "USD")));
+    assert_eq!(parse_price("1 500 MDL", None), Ok(price(1500.0, "MDL")));
+    assert_eq!(parse_price("1 500 CHF", None), Ok(price(1500.0, "CHF")));
+    assert_eq!(parse_price("1 500", None).unwrap().currency, None);
+    assert_eq!(
+        parse_price("€ 65 lei", None),
+        Err(PriceError::ConflictingCurrencies("EUR".to_string(), "MDL".to_string())),
+    );
+}
+
+#[test]
+fn labels_prefixes_and_ranges() {
+    assert_eq!(parse_price("Preț: 1 299 lei", None), Ok(price(1299.0, "MDL")));
+    assert_eq!(parse_price("Цена: от 999 лей", None), Ok(price(999.0, "MDL")));
+    assert_eq!(parse_price("de la 999 lei", None), Ok(price(999.0, "MDL")));
+    assert_eq!(parse_price("Starting at $5", None), Ok(price(5.0, "USD")));
+
+    let range = parse_price("999 - 1 299 MDL", None).unwrap();
+    assert_eq!((range.amount, range.max_amount), (999.0, Some(1299.0)));
+    let range = parse_price("999 lei - 1 299 lei", None).unwrap();
+    assert_eq!((range.amount, range.max_amount, range.currency.as_deref()), (999.0, Some(1299.0), Some("MDL")));
+    let range = parse_price("от 100 до 200 руб", None).unwrap();
+    assert_eq!((range.amount, range.max_amount, range.currency.as_deref()), (100.0, Some(200.0), Some("RUB")));
+
+    assert_eq!(amount("-50 lei", None), -50.0);
+    // A space can group digits as well as separate words
+    assert_eq!(amount("100 200", None), 100200.0);
+}
+
+#[test]
+fn text_that_is_not_a_price_is_rejected() {
+    assert_eq!(parse_price("", None), Err(PriceError::Empty));
+    assert_eq!(parse_price(" \u{a0} ", None), Err(PriceError::Empty));
+    for text in ["Preț la cerere", "lei", "100 lei 200", "1 2 3", "call 100"] {
+        assert!(matches!(parse_price(text, None), Err(PriceError::Unparseable(_))), "{:?} was accepted", text);
+    }
+}
+
+#[test]
+fn conversion_to_mdl() {
+    assert_eq!(to_mdl(100.0, None), Some(100.0));
+    assert_eq!(to_mdl(100.0, Some("mdl")), Some(100.0));
+    assert!(to_mdl(1.0, Some("EUR")).unwrap() > 1.0);
+    assert_eq!(to_mdl(1.0, Some("USD")), None);
+    assert_eq!(price(10.0, "EUR").amount_mdl(), to_mdl(10.0, Some("EUR")));
+}
+
+#[test]
+fn language_tags_pick_a_locale() {
+    assert_eq!(NumberLocale::from_language_tag("ro"), RO);
+    assert_eq!(NumberLocale::from_language_tag("ru-MD"), RU);
+    assert_eq!(NumberLocale::from_language_tag("en_US"), EN);
+    assert_eq!(NumberLocale::from_language_tag("mo"), RO);
+    assert_eq!(NumberLocale::from_language_tag("de"), None);
+}
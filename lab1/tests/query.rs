@@ -0,0 +1,163 @@
+use lab1::query::{parse_query, Aggregate, Operator, Query};
+use lab1::{Data, Product};
+
+fn product(name: &str, price: f64, brand: Option<&str>) -> Product {
+    Product {
+        name: name.to_string(),
+        price,
+        link: format!("https://xstore.md/product/{}", name.to_lowercase().replace(' ', "-")),
+        description: String::new(),
+        image_url: None,
+        image: None,
+        sku: None,
+        brand: brand.map(str::to_string),
+        availability: None,
+    }
+}
+
+fn catalog() -> Vec<Product> {
+    vec![
+        product("iPhone 15", 12999.0, Some("Apple")),
+        product("Galaxy A55", 7499.0, Some("Samsung")),
+        product("iPhone 13", 8999.0, Some("apple")),
+        product("Pixel 8", 10999.0, None),
+        product("Galaxy S24", 15999.0, Some("Samsung")),
+    ]
+}
+
+fn names(products: &[Product]) -> Vec<&str> {
+    products.iter().map(|p| p.name.as_str()).collect()
+}
+
+fn run(expression: &str) -> Data {
+    parse_query(expression).unwrap_or_else(|e| panic!("{:?}: {}", expression, e)).run(&catalog())
+}
+
+fn field<'a>(data: &'a Data, key: &str) -> &'a Data {
+    data.get(key).unwrap_or_else(|| panic!("no {} in {:?}", key, data))
+}
+
+#[test]
+fn parses_every_stage() {
+    let query = parse_query(r#"where price >= 1000 and brand = "Apple" | sort by price desc, name | limit 3 | group by brand | count, avg price, p90 price, percentile 25 price, median price"#).unwrap();
+
+    let conditions: Vec<_> = query.conditions.iter().map(|c| (c.field.as_str(), c.operator, c.value.clone())).collect();
+    assert_eq!(conditions, [
+        ("price", Operator::Ge, Data::Float(1000.0)),
+        ("brand", Operator::Eq, Data::Text("Apple".to_string())),
+    ]);
+    let sort: Vec<_> = query.sort.iter().map(|k| (k.field.as_str(), k.descending)).collect();
+    assert_eq!(sort, [("price", true), ("name", false)]);
+    assert_eq!(query.limit, Some(3));
+    assert_eq!(query.group_by.as_deref(), Some("brand"));
+    assert_eq!(query.aggregates, [
+        Aggregate::Count,
+        Aggregate::Average("price".to_string()),
+        Aggregate::Percentile("price".to_string(), 90.0),
+        Aggregate::Percentile("price".to_string(), 25.0),
+        Aggregate::Percentile("price".to_string(), 50.0),
+    ]);
+    assert!(query.is_aggregate());
+}
+
+#[test]
+fn parses_every_operator() {
+    let operators: Vec<Operator> = ["=", "!=", "<", "<=", ">", ">=", "~"]
+        .iter()
+        .map(|op| parse_query(&format!("where name {} 'x'", op)).unwrap().conditions[0].operator)
+        .collect();
+    use Operator::*;
+    assert_eq!(operators, [Eq, Ne, Lt, Le, Gt, Ge, Contains]);
+}
+
+#[test]
+fn rejects_malformed_queries() {
+    for expression in [
+        "",
+        "where",
+        "where price",
+        "where price >",
+        "where price => 5",
+        "where name = \"unterminated",
+        "limit -1",
+        "limit 2.5",
+        "limit many",
+        "sort",
+        "group by",
+        "p150 price",
+        "percentile price",
+        "frobnicate price",
+        "count extra",
+        "where price > 5 |",
+    ] {
+        assert!(parse_query(expression).is_err(), "{:?} was accepted", expression);
+    }
+}
+
+#[test]
+fn filters_sorts_and_limits() {
+    let query = parse_query("where price < 11000 | sort price desc | limit 2").unwrap();
+    assert_eq!(names(&query.apply(catalog())), ["Pixel 8", "iPhone 13"]);
+    assert!(!query.is_aggregate());
+
+    // Text compares without case, and `~` finds substrings
+    let query = parse_query("where brand = apple | sort name").unwrap();
+    assert_eq!(names(&query.apply(catalog())), ["iPhone 13", "iPhone 15"]);
+    let query = parse_query("where name ~ GALAXY").unwrap();
+    assert_eq!(names(&query.apply(catalog())), ["Galaxy A55", "Galaxy S24"]);
+
+    // A product without the field never matches, not even `!=`, and sorts last either way
+    let query = parse_query("where brand != Samsung").unwrap();
+    assert_eq!(names(&query.apply(catalog())), ["iPhone 15", "iPhone 13"]);
+    let query = parse_query("sort brand desc, price").unwrap();
+    assert_eq!(names(&query.apply(catalog())), ["Galaxy A55", "Galaxy S24", "iPhone 13", "iPhone 15", "Pixel 8"]);
+
+    // Numbers against text are simply not equal
+    let query = Query::new().filter("name", Operator::Eq, Data::Float(8.0));
+    assert!(query.apply(catalog()).is_empty());
+}
+
+#[test]
+fn aggregates_over_everything() {
+    let summary = run("count, sum price, avg price, min price, max price, median price, p90 price");
+    assert_eq!(field(&summary, "count"), &Data::Int(5));
+    assert_eq!(field(&summary, "sum_price"), &Data::Float(56495.0));
+    assert_eq!(field(&summary, "avg_price"), &Data::Float(11299.0));
+    assert_eq!(field(&summary, "min_price"), &Data::Float(7499.0));
+    assert_eq!(field(&summary, "max_price"), &Data::Float(15999.0));
+    assert_eq!(field(&summary, "p50_price"), &Data::Float(10999.0));
+    // Interpolated between the two highest prices: 12999 + (15999 - 12999) * 0.6
+    assert_eq!(field(&summary, "p90_price"), &Data::Float(14799.0));
+
+    // Nothing to average once the filter removes every product
+    let summary = run("where price > 99999 | count, avg price, sum price");
+    assert_eq!(field(&summary, "count"), &Data::Int(0));
+    assert_eq!(field(&summary, "avg_price"), &Data::Null);
+    assert_eq!(field(&summary, "sum_price"), &Data::Float(0.0));
+}
+
+#[test]
+fn groups_keep_first_appearance_order() {
+    let Data::List(groups) = run("group by brand | count, max price") else { panic!("groups are a list") };
+    let rows: Vec<_> = groups.iter().map(|g| (field(g, "brand").clone(), field(g, "count").clone(), field(g, "max_price").clone())).collect();
+    assert_eq!(rows, [
+        (Data::Text("Apple".to_string()), Data::Int(1), Data::Float(12999.0)),
+        (Data::Text("Samsung".to_string()), Data::Int(2), Data::Float(15999.0)),
+        (Data::Text("apple".to_string()), Data::Int(1), Data::Float(8999.0)),
+        (Data::Null, Data::Int(1), Data::Float(10999.0)),
+    ]);
+
+    // Without aggregates each group lists its products
+    let Data::List(groups) = run("where brand = Samsung | group by brand") else { panic!("groups are a list") };
+    assert_eq!(groups.len(), 1);
+    assert_eq!(field(&groups[0], "count"), &Data::Int(2));
+    let Data::List(members) = field(&groups[0], "products") else { panic!("products are a list") };
+    assert_eq!(members.len(), 2);
+}
+
+#[test]
+fn aggregate_names() {
+    assert_eq!(Aggregate::Count.name(), "count");
+    assert_eq!(Aggregate::Average("price".to_string()).name(), "avg_price");
+    assert_eq!(Aggregate::Percentile("price".to_string(), 99.5).name(), "p99.5_price");
+}
@@ -0,0 +1,184 @@
+mod common;
+
+use common::{client, shop, FixtureServer, Reply, LISTING, PNG};
+use lab1::cookies::CookieJar;
+use lab1::proxy::ProxyConfig;
+use lab1::query::parse_query;
+use lab1::scraping::{parse_products, scrape_products, ScrapeResult};
+use lab1::tls::TlsOptions;
+use lab1::{Client, Rejection, Scraper};
+
+fn rejection<'a>(result: &'a ScrapeResult, name: &str) -> &'a Rejection {
+    result.rejected.iter().find(|r| r.name == name).unwrap_or_else(|| panic!("{} was not rejected", name))
+}
+
+fn rules(rejection: &Rejection) -> Vec<(&str, &str)> {
+    rejection.violations.iter().map(|v| (v.field, v.rule)).collect()
+}
+
+fn assert_recorded_shop(result: &ScrapeResult, server: &FixtureServer) {
+    let names: Vec<&str> = result.products.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["Apple iPhone 15 128GB Black", "Samsung Galaxy A55 8/256GB Awesome Navy"]);
+    assert_eq!(result.rejected.len(), 3);
+
+    let iphone = &result.products[0];
+    assert_eq!(iphone.price, 12999.0);
+    assert_eq!(iphone.link, server.url("/product/iphone-15"));
+    assert_eq!(iphone.description, "Ecran: 6.1\", Memorie: 128 GB, Culoare: Negru");
+    assert_eq!(iphone.image_url.as_deref(), Some(server.url("/img/iphone-15.png").as_str()));
+    let image = iphone.image.as_ref().expect("image downloaded");
+    assert_eq!(image.content_type, "image/png");
+    assert_eq!(image.data, PNG);
+    assert_eq!(iphone.sku, None);
+}
+
+#[test]
+fn scrapes_listing_and_detail_pages() {
+    let server = FixtureServer::http(shop());
+    let result = scrape_products(&client(), &server.url("/")).unwrap();
+    assert_recorded_shop(&result, &server);
+}
+
+#[test]
+fn structured_data_wins_over_css_fields() {
+    let server = FixtureServer::http(shop());
+    let result = scrape_products(&client(), &server.url("/")).unwrap();
+
+    let galaxy = &result.products[1];
+    assert_eq!(galaxy.price, 6299.0);
+    assert_eq!(galaxy.sku.as_deref(), Some("SM-A556B"));
+    assert_eq!(galaxy.brand.as_deref(), Some("Samsung"));
+    assert_eq!(galaxy.availability.as_deref(), Some("InStock"));
+    assert_eq!(galaxy.image_url.as_deref(), Some(server.url("/img/galaxy-a55.png").as_str()));
+}
+
+#[test]
+fn failed_detail_pages_and_bad_prices_are_rejected() {
+    let server = FixtureServer::http(shop());
+    let result = scrape_products(&client(), &server.url("/")).unwrap();
+
+    let missing = rejection(&result, "Xiaomi Redmi Note 13 8/256GB");
+    assert_eq!(rules(missing), [("link", "unreachable")]);
+    assert!(missing.violations[0].message.contains("404"), "{}", missing.violations[0].message);
+    assert_eq!(missing.link.as_deref(), Some(server.url("/product/redmi-note-13").as_str()));
+
+    let broken = rejection(&result, "Nothing Phone (2a) 12/256GB");
+    assert_eq!(rules(broken), [("link", "unreachable")]);
+    assert!(broken.violations[0].message.contains("500"), "{}", broken.violations[0].message);
+
+    let on_request = rejection(&result, "Google Pixel 8 128GB");
+    assert_eq!(rules(on_request), [("price", "unparseable")]);
+}
+
+#[test]
+fn follows_redirects_to_the_listing() {
+    let mut routes = shop();
+    routes.insert("/telefoane", Reply::redirect(301, "/smartphones"));
+    routes.insert("/smartphones", Reply::redirect(302, "/"));
+    let server = FixtureServer::http(routes);
+
+    let result = scrape_products(&client(), &server.url("/telefoane")).unwrap();
+    assert_recorded_shop(&result, &server);
+}
+
+#[test]
+fn gives_up_on_redirect_loops() {
+    let mut routes = shop();
+    routes.insert("/loop", Reply::redirect(302, "/loop"));
+    let server = FixtureServer::http(routes);
+
+    let error = scrape_products(&client(), &server.url("/loop")).unwrap_err();
+    assert_eq!(error.to_string(), "Too many redirects");
+}
+
+#[test]
+fn reads_chunked_responses() {
+    let mut routes = shop();
+    routes.insert("/", Reply::html(LISTING).chunked(100));
+    routes.insert("/img/iphone-15.png", Reply::bytes("image/png", PNG).chunked(7));
+    let server = FixtureServer::http(routes);
+
+    let result = scrape_products(&client(), &server.url("/")).unwrap();
+    assert_recorded_shop(&result, &server);
+}
+
+#[test]
+fn listing_error_status_is_an_error() {
+    let mut routes = shop();
+    routes.insert("/", Reply::status(503));
+    let server = FixtureServer::http(routes);
+
+    let error = scrape_products(&client(), &server.url("/")).unwrap_err();
+    assert!(error.to_string().contains("503"), "{}", error);
+    assert!(scrape_products(&client(), &server.url("/no-such-page")).is_err());
+}
+
+#[test]
+fn scrapes_over_https_with_a_trusted_ca() {
+    let server = FixtureServer::https(shop());
+    let result = scrape_products(&client(), &server.url("/")).unwrap();
+    assert_recorded_shop(&result, &server);
+}
+
+#[test]
+fn rejects_untrusted_certificates() {
+    let server = FixtureServer::https(shop());
+    let untrusted = Client::new(ProxyConfig::default(), CookieJar::new(), &TlsOptions::default()).unwrap();
+    assert!(scrape_products(&untrusted, &server.url("/")).is_err());
+}
+
+#[test]
+fn parses_a_recorded_listing_body() {
+    let server = FixtureServer::http(shop());
+    // Links and images are resolved against the page URL rather than where the body came from
+    let result = parse_products(&client(), LISTING, &server.url("/category/phones?page=1")).unwrap();
+    assert_recorded_shop(&result, &server);
+}
+
+#[test]
+fn detail_page_images_are_resolved_against_the_detail_page() {
+    let galaxy = include_str!("fixtures/galaxy-a55.html").replace("\"/img/galaxy-a55.png\"", "\"photos/galaxy-a55.png\"");
+    let mut routes = shop();
+    routes.insert("/product/galaxy-a55", Reply::html(&galaxy));
+    routes.insert("/product/photos/galaxy-a55.png", Reply::bytes("image/png", PNG));
+    let server = FixtureServer::http(routes);
+
+    // The listing sits at a different depth, so resolving against it would give /category/photos/...
+    let result = parse_products(&client(), LISTING, &server.url("/category/phones?page=1")).unwrap();
+    let galaxy = &result.products[1];
+    assert_eq!(galaxy.image_url.as_deref(), Some(server.url("/product/photos/galaxy-a55.png").as_str()));
+    assert_eq!(galaxy.image.as_ref().map(|image| image.data.as_slice()), Some(PNG));
+}
+
+#[test]
+fn parses_listings_without_products() {
+    let server = FixtureServer::http(shop());
+    let result = parse_products(&client(), "<html><body><p>Nimic găsit</p></body></html>", &server.url("/")).unwrap();
+    assert!(result.products.is_empty());
+    assert!(result.rejected.is_empty());
+}
+
+#[test]
+fn scraper_merges_duplicates_and_applies_the_query() {
+    let listing = LISTING.replace("</div>\n</body>", r#"
+    <figure class="xp-card">
+        <figcaption>
+            <a class="xp-title" href="/product/iphone-15?utm_source=newsletter#reviews">Apple iPhone 15 128GB Black</a>
+            <div class="xprice">12 999 lei</div>
+        </figcaption>
+    </figure>
+</div>
+</body>"#);
+    let mut routes = shop();
+    routes.insert("/", Reply::html(&listing));
+    routes.insert("/product/iphone-15?utm_source=newsletter", Reply::html(include_str!("fixtures/iphone-15.html")));
+    let server = FixtureServer::http(routes);
+
+    let query = parse_query("where price < 10000").unwrap();
+    let report = Scraper::new(client(), &server.url("/")).query(query).scrape().unwrap();
+    assert_eq!(report.scraped, 6);
+    assert_eq!(report.merged, 1);
+    assert_eq!(report.rejected.len(), 3);
+    let names: Vec<&str> = report.products.iter().map(|p| p.name.as_str()).collect();
+    assert_eq!(names, ["Samsung Galaxy A55 8/256GB Awesome Navy"]);
+}
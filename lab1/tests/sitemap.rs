@@ -0,0 +1,135 @@
+mod common;
+
+use std::io::Write;
+use chrono::{TimeZone, Utc};
+use common::{client, FixtureServer, Reply, Routes};
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use lab1::sitemap::{collect_urls, discover_sitemaps, glob_matches, parse_lastmod, SitemapFilter};
+
+fn urlset(pages: &[(&str, &str)]) -> String {
+    let mut xml = String::from(r#"<?xml version="1.0" encoding="UTF-8"?><urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#);
+    for (loc, lastmod) in pages {
+        xml.push_str(&format!("<url><loc>{}</loc><lastmod>{}</lastmod></url>", loc, lastmod));
+    }
+    xml.push_str("</urlset>");
+    xml
+}
+
+fn gzip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+fn locs(server: &FixtureServer, sitemaps: &[&str], filter: &SitemapFilter) -> Vec<String> {
+    let urls: Vec<String> = sitemaps.iter().map(|path| server.url(path)).collect();
+    collect_urls(&client(), &urls, filter).unwrap().into_iter().map(|entry| entry.loc).collect()
+}
+
+#[test]
+fn glob_stars_match_any_run_of_characters() {
+    assert!(glob_matches("*/product/*", "https://xstore.md/product/iphone-15"));
+    assert!(glob_matches("https://xstore.md/*", "https://xstore.md/"));
+    assert!(glob_matches("*", ""));
+    assert!(glob_matches("*phone*15", "https://xstore.md/product/iphone-15"));
+    assert!(glob_matches("exact", "exact"));
+
+    assert!(!glob_matches("exact", "exactly"));
+    assert!(!glob_matches("*/product/*", "https://xstore.md/category/phones"));
+    // The prefix and suffix may not overlap
+    assert!(!glob_matches("ab*ba", "aba"));
+    // Middle parts must appear in order
+    assert!(!glob_matches("*b*a*", "ab"));
+}
+
+#[test]
+fn lastmod_accepts_w3c_datetime_precisions() {
+    let may_first = Utc.with_ymd_and_hms(2024, 5, 1, 0, 0, 0).unwrap();
+    assert_eq!(parse_lastmod("2024-05-01"), Some(may_first));
+    assert_eq!(parse_lastmod("2024-05"), Some(may_first));
+    assert_eq!(parse_lastmod("2024-05-01T10:00:00+03:00"), Some(Utc.with_ymd_and_hms(2024, 5, 1, 7, 0, 0).unwrap()));
+    assert_eq!(parse_lastmod("2024-05-01T10:00Z"), Some(Utc.with_ymd_and_hms(2024, 5, 1, 10, 0, 0).unwrap()));
+    assert_eq!(parse_lastmod("2024-05-01T10:00+02:00"), Some(Utc.with_ymd_and_hms(2024, 5, 1, 8, 0, 0).unwrap()));
+
+    assert_eq!(parse_lastmod("yesterday"), None);
+    assert_eq!(parse_lastmod("2024-13-01"), None);
+}
+
+#[test]
+fn robots_txt_names_the_sitemaps() {
+    let server = FixtureServer::http(Routes::from([(
+        "/robots.txt",
+        Reply::bytes("text/plain", b"User-agent: *\nDisallow: /cart\nSitemap: /products.xml\nsitemap: /more.xml\n"),
+    )]));
+    assert_eq!(discover_sitemaps(&client(), &server.url("/")).unwrap(), [server.url("/products.xml"), server.url("/more.xml")]);
+
+    let server = FixtureServer::http(Routes::new());
+    assert_eq!(discover_sitemaps(&client(), &server.url("/")).unwrap(), [server.url("/sitemap.xml")]);
+}
+
+#[test]
+fn indexes_are_expanded_and_pages_filtered() {
+    let pages = FixtureServer::http(Routes::from([
+        ("/old.xml", Reply::bytes("application/xml", urlset(&[("https://shop.md/product/old", "2023-12-01")]).as_bytes())),
+        ("/new.xml.gz", Reply::bytes("application/gzip", &gzip(urlset(&[
+            ("https://shop.md/product/a", "2024-06-01"),
+            ("https://shop.md/category/phones", "2024-06-01"),
+            ("https://shop.md/product/b", "2024-02-01"),
+            ("https://shop.md/product/c", "2024-06-03"),
+            ("https://shop.md/product/a", "2024-06-02"),
+        ]).as_bytes()))),
+    ]));
+    // The index lives on its own server so it can point at the other one's port
+    let index = format!(
+        "<sitemapindex><sitemap><loc>{}</loc><lastmod>2024-01-01</lastmod></sitemap><sitemap><loc>{}</loc></sitemap></sitemapindex>",
+        pages.url("/old.xml"),
+        pages.url("/new.xml.gz"),
+    );
+    let server = FixtureServer::http(Routes::from([("/index.xml", Reply::bytes("application/xml", index.as_bytes()))]));
+
+    let everything = locs(&server, &["/index.xml"], &SitemapFilter::default());
+    assert_eq!(everything, [
+        "https://shop.md/product/old",
+        "https://shop.md/product/a",
+        "https://shop.md/category/phones",
+        "https://shop.md/product/b",
+        "https://shop.md/product/c",
+    ]);
+
+    // The old child sitemap isn't even fetched once `since` rules it out
+    let filter = SitemapFilter {
+        pattern: Some("*/product/*".to_string()),
+        since: parse_lastmod("2024-05-01"),
+        limit: None,
+    };
+    assert_eq!(locs(&server, &["/index.xml"], &filter), ["https://shop.md/product/a", "https://shop.md/product/c"]);
+
+    let filter = SitemapFilter { limit: Some(2), ..SitemapFilter::default() };
+    assert_eq!(locs(&server, &["/index.xml"], &filter), ["https://shop.md/product/old", "https://shop.md/product/a"]);
+}
+
+#[test]
+fn gzipped_sitemaps_are_capped_once_decompressed() {
+    // One real entry followed by padding that takes it past 50 MB uncompressed
+    let mut xml = urlset(&[("https://shop.md/product/a", "2024-06-01")]).into_bytes();
+    let closing = xml.split_off(xml.len() - "</urlset>".len());
+    xml.resize(50 * 1024 * 1024 + 1, b' ');
+    xml.extend_from_slice(&closing);
+
+    let server = FixtureServer::http(Routes::from([
+        ("/huge.xml.gz", Reply::bytes("application/gzip", &gzip(&xml))),
+        ("/small.xml.gz", Reply::bytes("application/gzip", &gzip(urlset(&[("https://shop.md/product/b", "2024-06-01")]).as_bytes()))),
+    ]));
+    // The oversized sitemap is skipped without losing the others
+    assert_eq!(locs(&server, &["/huge.xml.gz", "/small.xml.gz"], &SitemapFilter::default()), ["https://shop.md/product/b"]);
+}
+
+#[test]
+fn documents_that_are_not_sitemaps_are_skipped() {
+    let server = FixtureServer::http(Routes::from([
+        ("/feed.xml", Reply::bytes("application/xml", b"<rss><channel><item><loc>https://shop.md/x</loc></item></channel></rss>")),
+        ("/sitemap.xml", Reply::bytes("application/xml", urlset(&[("https://shop.md/product/a", "2024-06-01")]).as_bytes())),
+    ]));
+    assert_eq!(locs(&server, &["/feed.xml", "/missing.xml", "/sitemap.xml"], &SitemapFilter::default()), ["https://shop.md/product/a"]);
+}
@@ -0,0 +1,128 @@
+mod common;
+
+use std::path::PathBuf;
+use common::{client, FixtureServer, Reply, Routes};
+use lab1::cookies::CookieJar;
+use lab1::http::Limits;
+use lab1::proxy::ProxyConfig;
+use lab1::tls::{parse_tls_version, PeerCertificate, TlsOptions};
+use lab1::Client;
+use native_tls::{Certificate, Protocol};
+
+fn fixture(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/tls").join(name)
+}
+
+fn routes() -> Routes {
+    Routes::from([("/hello", Reply::html("hello"))])
+}
+
+fn fetch(tls: &TlsOptions, url: &str) -> Result<String, String> {
+    let client = Client::new(ProxyConfig::default(), CookieJar::new(), tls).map_err(|e| e.to_string())?;
+    client.fetch(url, &Limits::default()).map(|response| response.text()).map_err(|e| e.to_string())
+}
+
+fn trusting_the_fixture_ca() -> TlsOptions {
+    TlsOptions { ca_certs: vec![fixture("ca.pem")], ..TlsOptions::default() }
+}
+
+#[test]
+fn insecure_accepts_a_certificate_nobody_vouches_for() {
+    let server = FixtureServer::https(routes());
+
+    assert!(fetch(&TlsOptions::default(), &server.url("/hello")).is_err());
+    let insecure = TlsOptions { insecure: true, ..TlsOptions::default() };
+    assert_eq!(fetch(&insecure, &server.url("/hello")).unwrap(), "hello");
+}
+
+#[test]
+fn parses_minimum_tls_versions() {
+    for (input, expected) in [
+        ("1.0", Protocol::Tlsv10),
+        ("1.1", Protocol::Tlsv11),
+        ("1.2", Protocol::Tlsv12),
+        (" TLS1.2 ", Protocol::Tlsv12),
+        ("tlsv1.1", Protocol::Tlsv11),
+        ("12", Protocol::Tlsv12),
+    ] {
+        assert_eq!(format!("{:?}", parse_tls_version(input).unwrap()), format!("{:?}", expected), "{}", input);
+    }
+    for input in ["1.3", "2", "", "ssl3"] {
+        let error = parse_tls_version(input).unwrap_err();
+        assert_eq!(error.to_string(), format!("Unsupported minimum TLS version: {} (expected 1.0, 1.1 or 1.2)", input));
+    }
+}
+
+#[test]
+fn the_minimum_version_is_enforced() {
+    let server = FixtureServer::https_with(routes(), |acceptor| {
+        acceptor.max_protocol_version(Some(Protocol::Tlsv12));
+    });
+
+    let at_least_1_2 = TlsOptions { min_version: Some(parse_tls_version("1.2").unwrap()), ..trusting_the_fixture_ca() };
+    assert_eq!(fetch(&at_least_1_2, &server.url("/hello")).unwrap(), "hello");
+    // The server can't go above 1.2, so a client that insists on 1.3 never completes a handshake
+    let at_least_1_3 = TlsOptions { min_version: Some(Protocol::Tlsv13), ..trusting_the_fixture_ca() };
+    assert!(fetch(&at_least_1_3, &server.url("/hello")).is_err());
+}
+
+#[test]
+fn loads_client_identities() {
+    let server = FixtureServer::https(routes());
+
+    // A PKCS#8 PEM key with its certificate chain
+    let pem = TlsOptions {
+        client_cert: Some(fixture("server.pem")),
+        client_key: Some(fixture("server.key")),
+        ..trusting_the_fixture_ca()
+    };
+    assert_eq!(fetch(&pem, &server.url("/hello")).unwrap(), "hello");
+
+    // The same identity as a PKCS#12 bundle, made with
+    // `openssl pkcs12 -export -in server.pem -inkey server.key -out identity.p12 -passout pass:lab1`
+    let pkcs12 = |password: Option<&str>| TlsOptions {
+        client_cert: Some(fixture("identity.p12")),
+        client_cert_password: password.map(str::to_string),
+        ..trusting_the_fixture_ca()
+    };
+    assert_eq!(fetch(&pkcs12(Some("lab1")), &server.url("/hello")).unwrap(), "hello");
+    assert!(pkcs12(Some("wrong")).connector().is_err());
+    assert!(pkcs12(None).connector().is_err());
+
+    // A key that doesn't parse, and files that don't exist
+    let not_a_key = TlsOptions { client_key: Some(fixture("ca.pem")), ..pem.clone() };
+    assert!(not_a_key.connector().is_err());
+    let missing = TlsOptions { client_cert: Some(fixture("missing.pem")), ..pem.clone() };
+    let error = missing.connector().unwrap_err().to_string();
+    assert!(error.starts_with(&format!("Cannot read client certificate {}", fixture("missing.pem").display())), "{}", error);
+    let missing = TlsOptions { client_key: Some(fixture("missing.key")), ..pem };
+    let error = missing.connector().unwrap_err().to_string();
+    assert!(error.starts_with(&format!("Cannot read client key {}", fixture("missing.key").display())), "{}", error);
+}
+
+#[test]
+fn describes_the_peer_certificate() {
+    let server = FixtureServer::https(routes());
+    let presented = client().peer_certificate(&server.url("/")).unwrap();
+
+    assert_eq!(presented.subject, "CN=localhost");
+    assert_eq!(presented.issuer, "CN=lab1 test CA");
+    assert_eq!(presented.subject_alt_names, ["localhost", "127.0.0.1"]);
+    assert_eq!(presented.not_before, "Oct 18 17:45:40 2026 +00:00");
+    assert_eq!(presented.not_after, "Sep 24 17:45:40 2126 +00:00");
+
+    // The same details as reading the fixture certificate directly
+    let der = Certificate::from_pem(include_bytes!("fixtures/tls/server.pem")).unwrap().to_der().unwrap();
+    let expected = PeerCertificate::from_der(&der).unwrap();
+    assert_eq!(presented.to_string(), expected.to_string());
+    assert_eq!(presented.serial, "7b:84:19:59:54:13:cf:68:a1:1d:2c:89:cf:49:60:da:c1:e7:67:60");
+    // As `openssl x509 -noout -fingerprint -sha256` prints it
+    assert_eq!(
+        presented.sha256_fingerprint,
+        "7B:B8:28:63:58:C9:2F:FC:D8:FD:37:E8:F6:9F:A9:0D:AE:D7:4D:99:27:C3:71:84:E3:0C:6E:F1:E9:23:84:2F"
+    );
+
+    assert!(PeerCertificate::from_der(b"not a certificate").is_err());
+    let error = client().peer_certificate("http://localhost/").unwrap_err();
+    assert_eq!(error.to_string(), "http://localhost/ is not an HTTPS URL");
+}
@@ -0,0 +1,65 @@
+use lab1::validation::{validate_amount, validate_link, validate_name, MAX_PRICE_MDL};
+use lab1::Violation;
+
+// (field, rule, message) of each violation
+fn described(violations: Vec<Violation>) -> Vec<(&'static str, &'static str, String)> {
+    violations.into_iter().map(|v| (v.field, v.rule, v.message)).collect()
+}
+
+fn one(field: &'static str, rule: &'static str, message: &str) -> Vec<(&'static str, &'static str, String)> {
+    vec![(field, rule, message.to_string())]
+}
+
+#[test]
+fn names_must_be_real_names() {
+    for name in ["", "   ", "\t\n"] {
+        assert_eq!(described(validate_name(name)), one("name", "empty", "name is empty"), "{:?}", name);
+    }
+    // Placeholders are recognised without regard to case or surrounding space
+    for (name, shown) in [
+        ("Product name not found", "Product name not found"),
+        ("  N/A ", "N/A"),
+        ("undefined", "undefined"),
+        ("-", "-"),
+        ("***", "***"),
+    ] {
+        let message = format!("{:?} is a placeholder, not a name", shown);
+        assert_eq!(described(validate_name(name)), one("name", "placeholder", &message), "{:?}", name);
+    }
+    for name in ["Apple iPhone 15", "Смартфон Redmi 13", "X"] {
+        assert!(validate_name(name).is_empty(), "{:?}", name);
+    }
+}
+
+#[test]
+fn amounts_must_be_plausible_prices() {
+    assert_eq!(described(validate_amount(-1.5)), one("price", "negative", "price -1.50 MDL is negative"));
+    assert_eq!(described(validate_amount(0.0)), one("price", "absurd", "price is zero"));
+    assert_eq!(described(validate_amount(MAX_PRICE_MDL + 0.01)), one("price", "absurd", "price 10000000.01 MDL exceeds 10000000 MDL"));
+    for amount in [f64::NAN, f64::INFINITY] {
+        assert_eq!(described(validate_amount(amount)), one("price", "absurd", "price is not a finite number"), "{}", amount);
+    }
+    for amount in [0.01, 12999.0, MAX_PRICE_MDL] {
+        assert!(validate_amount(amount).is_empty(), "{}", amount);
+    }
+}
+
+#[test]
+fn links_must_be_absolute_web_urls() {
+    assert_eq!(described(validate_link(None)), one("link", "missing", "product has no link"));
+    assert_eq!(described(validate_link(Some("ftp://xstore.md/p/1"))), one("link", "broken", "unsupported link scheme \"ftp\""));
+    assert_eq!(described(validate_link(Some("mailto:shop@xstore.md"))), one("link", "broken", "unsupported link scheme \"mailto\""));
+    assert_eq!(
+        described(validate_link(Some("/product/iphone-15"))),
+        one("link", "broken", "invalid link \"/product/iphone-15\": relative URL without a base")
+    );
+    for link in ["https://xstore.md/product/iphone-15", "http://127.0.0.1:8080/p?id=1"] {
+        assert!(validate_link(Some(link)).is_empty(), "{}", link);
+    }
+}
+
+#[test]
+fn violations_show_their_rule() {
+    let violation = Violation::new("price", "negative", "price -1.50 MDL is negative");
+    assert_eq!(violation.to_string(), "price: price -1.50 MDL is negative [negative]");
+}
@@ -0,0 +1,28 @@
+-- Full-text index over product names and descriptions, stored as an external-content
+-- table so the text itself lives only in `products`
+CREATE VIRTUAL TABLE products_fts USING fts5(
+    name,
+    description,
+    content = 'products',
+    content_rowid = 'id',
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+-- Keep the index in step with every insert, update and delete
+CREATE TRIGGER products_fts_after_insert AFTER INSERT ON products BEGIN
+    INSERT INTO products_fts (rowid, name, description) VALUES (new.id, new.name, new.description);
+END;
+
+CREATE TRIGGER products_fts_after_delete AFTER DELETE ON products BEGIN
+    INSERT INTO products_fts (products_fts, rowid, name, description)
+    VALUES ('delete', old.id, old.name, old.description);
+END;
+
+CREATE TRIGGER products_fts_after_update AFTER UPDATE OF name, description ON products BEGIN
+    INSERT INTO products_fts (products_fts, rowid, name, description)
+    VALUES ('delete', old.id, old.name, old.description);
+    INSERT INTO products_fts (rowid, name, description) VALUES (new.id, new.name, new.description);
+END;
+
+-- Index the products that already exist
+INSERT INTO products_fts (products_fts) VALUES ('rebuild');
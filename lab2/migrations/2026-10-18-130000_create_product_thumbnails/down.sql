@@ -0,0 +1,3 @@
+DROP TRIGGER IF EXISTS product_thumbnails_after_image_update;
+DROP TRIGGER IF EXISTS product_thumbnails_after_product_delete;
+DROP TABLE IF EXISTS product_thumbnails;
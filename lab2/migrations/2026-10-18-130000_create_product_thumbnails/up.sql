@@ -0,0 +1,17 @@
+-- Resized copies of each product image, generated when the image is uploaded
+CREATE TABLE product_thumbnails (
+    product_id INTEGER NOT NULL REFERENCES products (id),
+    size TEXT NOT NULL,
+    data BLOB NOT NULL,
+    PRIMARY KEY (product_id, size)
+);
+
+-- SQLite leaves foreign keys unenforced unless every connection turns them on, so drop
+-- thumbnails here once the product or the image they were made from is gone
+CREATE TRIGGER product_thumbnails_after_product_delete AFTER DELETE ON products BEGIN
+    DELETE FROM product_thumbnails WHERE product_id = old.id;
+END;
+
+CREATE TRIGGER product_thumbnails_after_image_update AFTER UPDATE OF image ON products BEGIN
+    DELETE FROM product_thumbnails WHERE product_id = old.id;
+END;
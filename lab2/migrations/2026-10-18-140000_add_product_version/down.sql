@@ -0,0 +1,2 @@
+DROP TRIGGER IF EXISTS products_version_after_update;
+ALTER TABLE products DROP COLUMN version;
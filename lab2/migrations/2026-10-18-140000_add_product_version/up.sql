@@ -0,0 +1,8 @@
+-- Counts the changes to each product, so clients can tell whether the copy they edited is
+-- still current (ETag / If-Match)
+ALTER TABLE products ADD COLUMN version INTEGER NOT NULL DEFAULT 1;
+
+-- Every write to a product moves it to the next version, whichever code made it
+CREATE TRIGGER products_version_after_update AFTER UPDATE ON products WHEN new.version = old.version BEGIN
+    UPDATE products SET version = old.version + 1 WHERE id = old.id;
+END;
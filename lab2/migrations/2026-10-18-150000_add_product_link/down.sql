@@ -0,0 +1,3 @@
+DROP INDEX IF EXISTS products_name;
+DROP INDEX IF EXISTS products_link;
+ALTER TABLE products DROP COLUMN link;
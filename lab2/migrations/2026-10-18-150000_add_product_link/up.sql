@@ -0,0 +1,6 @@
+-- The page a product was scraped from, which imports match existing products on
+ALTER TABLE products ADD COLUMN link TEXT;
+
+-- Imports look products up by link, then by name
+CREATE INDEX products_link ON products (link);
+CREATE INDEX products_name ON products (name);
@@ -0,0 +1,144 @@
+use actix_web::error::BlockingError;
+use actix_web::http::StatusCode;
+use actix_web::{HttpResponse, ResponseError};
+use serde::Serialize;
+use thiserror::Error;
+use crate::images::ImageError;
+
+#[derive(Error, Debug)]
+pub enum ApiError {
+    #[error("Database error")]
+    DatabaseError(diesel::result::Error),
+    #[error("Database connection unavailable")]
+    PoolError(#[from] r2d2::Error),
+    #[error("Internal server error")]
+    BlockingError(#[from] BlockingError),
+    #[error("Product not found")]
+    NotFound,
+    #[error("Product has changed since it was read")]
+    PreconditionFailed,
+    #[error("Product has no image")]
+    ImageNotFound,
+    // Images stored before thumbnails existed have none until they are uploaded again
+    #[error("Product image has no {0} thumbnail; upload the image again to create it")]
+    ThumbnailNotFound(&'static str),
+    #[error("Could not read the request body")]
+    PayloadError(#[from] actix_web::error::PayloadError),
+    #[error(transparent)]
+    ImageError(#[from] ImageError),
+    #[error("Invalid multipart body: {0}")]
+    MultipartError(#[from] actix_multipart::MultipartError),
+    #[error("Invalid JSON body: {0}")]
+    InvalidJson(String),
+    #[error("Request body is larger than {0} bytes")]
+    BodyTooLarge(usize),
+    #[error("Content-Type must be application/json, application/xml or text/plain")]
+    UnsupportedContentType,
+    #[error("Could not read the products: {0}")]
+    InvalidImport(String),
+    #[error("Invalid query parameters")]
+    InvalidQuery(Vec<FieldError>),
+    #[error("Invalid product fields")]
+    Validation(Vec<FieldError>),
+}
+
+// A field or query parameter that could not be used, and why
+#[derive(Debug, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: &str, message: impl Into<String>) -> FieldError {
+        FieldError { field: field.to_string(), message: message.into() }
+    }
+}
+
+// Every error response has this shape; `errors` is empty unless particular fields were at fault
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    code: &'static str,
+    message: String,
+    errors: &'a [FieldError],
+}
+
+// A row that isn't there is the client's mistake, anything else from the database is ours
+impl From<diesel::result::Error> for ApiError {
+    fn from(error: diesel::result::Error) -> ApiError {
+        match error {
+            diesel::result::Error::NotFound => ApiError::NotFound,
+            error => ApiError::DatabaseError(error),
+        }
+    }
+}
+
+impl ApiError {
+    // Stable, machine-readable name for the error; the message may change
+    fn code(&self) -> &'static str {
+        match self {
+            ApiError::DatabaseError(_) => "database_error",
+            ApiError::PoolError(_) => "database_unavailable",
+            ApiError::BlockingError(_) => "internal_error",
+            ApiError::NotFound => "product_not_found",
+            ApiError::PreconditionFailed => "precondition_failed",
+            ApiError::ImageNotFound => "image_not_found",
+            ApiError::ThumbnailNotFound(_) => "thumbnail_not_found",
+            ApiError::PayloadError(_) => "invalid_body",
+            ApiError::ImageError(ImageError::TooLarge) => "image_too_large",
+            ApiError::ImageError(ImageError::Unsupported) => "unsupported_image",
+            ApiError::ImageError(ImageError::Invalid(_)) => "invalid_image",
+            ApiError::MultipartError(_) => "invalid_multipart",
+            ApiError::InvalidJson(_) => "invalid_json",
+            ApiError::BodyTooLarge(_) => "body_too_large",
+            ApiError::UnsupportedContentType => "unsupported_content_type",
+            ApiError::InvalidImport(_) => "invalid_import",
+            ApiError::InvalidQuery(_) => "invalid_query",
+            ApiError::Validation(_) => "validation_failed",
+        }
+    }
+
+    fn field_errors(&self) -> &[FieldError] {
+        match self {
+            ApiError::InvalidQuery(errors) | ApiError::Validation(errors) => errors,
+            _ => &[],
+        }
+    }
+}
+
+impl ResponseError for ApiError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiError::DatabaseError(_) | ApiError::PoolError(_) | ApiError::BlockingError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            ApiError::NotFound | ApiError::ImageNotFound | ApiError::ThumbnailNotFound(_) => StatusCode::NOT_FOUND,
+            ApiError::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            ApiError::ImageError(ImageError::TooLarge) | ApiError::BodyTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            ApiError::ImageError(ImageError::Unsupported) | ApiError::UnsupportedContentType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            ApiError::ImageError(ImageError::Invalid(_)) | ApiError::Validation(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            ApiError::PayloadError(_) | ApiError::MultipartError(_) | ApiError::InvalidJson(_) | ApiError::InvalidImport(_) | ApiError::InvalidQuery(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        HttpResponse::build(self.status_code()).json(ErrorBody {
+            code: self.code(),
+            message: self.to_string(),
+            errors: self.field_errors(),
+        })
+    }
+}
+
+// Extractor failures, hooked in through the extractor configs in main, answer like any other error
+
+pub fn json_error(error: actix_web::error::JsonPayloadError, _: &actix_web::HttpRequest) -> actix_web::Error {
+    ApiError::InvalidJson(error.to_string()).into()
+}
+
+pub fn query_error(error: actix_web::error::QueryPayloadError, _: &actix_web::HttpRequest) -> actix_web::Error {
+    ApiError::InvalidQuery(vec![FieldError::new("query", error.to_string())]).into()
+}
+
+// Ids are integers, so anything else can't name a product
+pub fn path_error(_: actix_web::error::PathError, _: &actix_web::HttpRequest) -> actix_web::Error {
+    ApiError::NotFound.into()
+}
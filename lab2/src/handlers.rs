@@ -1,51 +1,24 @@
-use actix_web::{web, HttpResponse};
+use actix_web::http::header::ETag;
+use actix_web::{web, HttpMessage, HttpRequest, HttpResponse};
 use actix_multipart::Multipart;
 use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Text};
 use futures_util::StreamExt as _;
-use serde::{Deserialize, Serialize};
-use thiserror::Error;
-use actix_web::error::BlockingError;
+use serde::Serialize;
+use std::collections::HashMap;
 use crate::db::DbPool;
-use crate::models::{NewProduct, Product};
+use crate::errors::{ApiError, FieldError};
+use crate::import;
+use crate::images::{image_response, process_upload, ImageError, MAX_IMAGE_BYTES};
+use crate::models::{Count, NewThumbnail, Product, ProductReplacement, SearchResult};
+use crate::preconditions::{expected_versions, none_match, product_etag, write_if_match};
+use crate::query::{ImageQuery, ProductQuery, SearchQuery};
+use crate::schema::product_thumbnails;
 use crate::schema::products::dsl::*;
+use crate::validation::{parse_patch, ProductInput, MAX_DESCRIPTION_LENGTH};
 
-#[derive(Error, Debug)]
-pub enum ApiError {
-    #[error("Database error")]
-    DatabaseError(#[from] diesel::result::Error),
-    #[error("Connection pool error")]
-    PoolError(#[from] r2d2::Error),
-    #[error("Blocking error")]
-    BlockingError(#[from] BlockingError),
-    #[error("Product not found")]
-    NotFound,
-    #[error("Multipart error")]
-    MultipartError(#[from] actix_multipart::MultipartError),
-    #[error("UTF-8 conversion error")]
-    Utf8Error(#[from] std::string::FromUtf8Error),
-    #[error("Float parse error")]
-    ParseFloatError(#[from] std::num::ParseFloatError),
-}
-
-impl actix_web::ResponseError for ApiError {
-    fn error_response(&self) -> HttpResponse {
-        match self {
-            ApiError::DatabaseError(_) => HttpResponse::InternalServerError().json("Database error"),
-            ApiError::PoolError(_) => HttpResponse::InternalServerError().json("Connection pool error"),
-            ApiError::BlockingError(_) => HttpResponse::InternalServerError().json("Blocking operation error"),
-            ApiError::NotFound => HttpResponse::NotFound().json("Product not found"),
-            ApiError::MultipartError(_) => HttpResponse::BadRequest().json("Multipart error"),
-            ApiError::Utf8Error(_) => HttpResponse::BadRequest().json("Invalid UTF-8 data"),
-            ApiError::ParseFloatError(_) => HttpResponse::BadRequest().json("Invalid float value"),
-        }
-    }
-}
-
-#[derive(Deserialize)]
-pub struct PaginationParams {
-    offset: Option<i64>,
-    limit: Option<i64>,
-}
+// Longest text part read from a product form: the longest field, at four UTF-8 bytes a character
+const MAX_TEXT_FIELD_BYTES: usize = 4 * MAX_DESCRIPTION_LENGTH;
 
 #[derive(Serialize)]
 pub struct PaginatedResponse<T> {
@@ -57,99 +30,54 @@ pub struct PaginatedResponse<T> {
 
 pub async fn create_product(
     pool: web::Data<DbPool>,
-    mut payload: Multipart,
+    payload: Multipart,
 ) -> Result<HttpResponse, ApiError> {
-    let mut product_name: Option<String> = None;
-    let mut product_price: Option<f64> = None;
-    let mut product_description: Option<String> = None;
-    let mut product_image_data: Option<Vec<u8>> = None;
+    let (input, product_image_data) = read_product_form(payload).await?;
+    let mut new_product = input.validate().map_err(ApiError::Validation)?;
 
-    // Process the multipart payload
-    while let Some(item) = payload.next().await {
-        let mut field = item?;
-        let content_disposition = field.content_disposition().unwrap();
-
-        if let Some(field_name) = content_disposition.get_name() {
-            match field_name {
-                "name" => {
-                    let mut value = Vec::new();
-                    while let Some(chunk) = field.next().await {
-                        value.extend_from_slice(&chunk?);
-                    }
-                    product_name = Some(String::from_utf8(value)?);
-                }
-                "price" => {
-                    let mut value = Vec::new();
-                    while let Some(chunk) = field.next().await {
-                        value.extend_from_slice(&chunk?);
-                    }
-                    product_price = Some(String::from_utf8(value)?.parse()?);
-                }
-                "description" => {
-                    let mut value = Vec::new();
-                    while let Some(chunk) = field.next().await {
-                        value.extend_from_slice(&chunk?);
-                    }
-                    product_description = Some(String::from_utf8(value)?);
-                }
-                "image" => {
-                    let mut image_data = Vec::new();
-                    while let Some(chunk) = field.next().await {
-                        image_data.extend_from_slice(&chunk?);
-                    }
-                    product_image_data = Some(image_data);
-                }
-                _ => {}
-            }
-        }
-    }
-
-    // Validate required fields
-    let product_name = product_name.ok_or(ApiError::NotFound)?;
-    let product_price = product_price.ok_or(ApiError::NotFound)?;
+    // Validate the image and make its thumbnails before anything is written
+    let (original, thumbnails) = prepare_image(product_image_data).await?;
 
     // Insert into the database
-    let new_product = NewProduct {
-        name: product_name,
-        price: product_price,
-        description: product_description,
-        image: product_image_data,
-    };
+    new_product.image = original;
 
     let mut conn = pool.get()?;
     let inserted_product = web::block(move || {
-        diesel::insert_into(products)
-            .values(&new_product)
-            .execute(&mut conn)?;
-        products.order(id.desc()).first::<Product>(&mut conn)
+        conn.transaction::<_, diesel::result::Error, _>(|conn| {
+            diesel::insert_into(products)
+                .values(&new_product)
+                .execute(conn)?;
+            let product = products.order(id.desc()).select(Product::as_select()).first(conn)?;
+            if let Some(product_id) = product.id {
+                save_thumbnails(conn, product_id, &thumbnails)?;
+            }
+            Ok(product)
+        })
     })
     .await??;
 
-    Ok(HttpResponse::Created().json(inserted_product))
+    Ok(HttpResponse::Created().insert_header(ETag(product_etag(inserted_product.version))).json(inserted_product))
 }
 
 pub async fn get_products(
     pool: web::Data<DbPool>,
-    web::Query(pagination): web::Query<PaginationParams>,
+    web::Query(params): web::Query<HashMap<String, String>>,
 ) -> Result<HttpResponse, ApiError> {
+    let query = ProductQuery::parse(&params).map_err(ApiError::InvalidQuery)?;
+    let page_offset = query.offset;
+    let page_limit = query.limit;
     let mut conn = pool.get()?;
-    
-    // Default values for pagination
-    let page_offset = pagination.offset.unwrap_or(0);
-    let page_limit = pagination.limit.unwrap_or(10).min(100); // Limit max to 100 items per page
-
-    // Get paginated results
-    let result = web::block(move || -> Result<(Vec<Product>, i64), diesel::result::Error> {
-        // First, get the total count of products
-        let count = products.count().get_result::<i64>(&mut conn)?;
-        
-        // Then get the paginated results
-        let results = products
-            .offset(page_offset)
-            .limit(page_limit)
-            .load::<Product>(&mut conn)?;
-        
-        Ok((results, count))
+
+    // Get the requested page of the filtered products
+    let result = web::block(move || -> Result<(Vec<serde_json::Value>, i64), diesel::result::Error> {
+        // First, count every product matching the filters
+        let count = query.filtered().count().get_result::<i64>(&mut conn)?;
+
+        // Then get the sorted page, keeping only the selected fields
+        let results = query.page().select(Product::as_select()).load(&mut conn)?;
+        let selected = results.iter().map(|product| query.select_fields(product)).collect();
+
+        Ok((selected, count))
     })
     .await??;
 
@@ -164,55 +92,364 @@ pub async fn get_products(
     Ok(HttpResponse::Ok().json(response))
 }
 
+pub async fn search_products(
+    pool: web::Data<DbPool>,
+    web::Query(params): web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse, ApiError> {
+    let query = SearchQuery::parse(&params).map_err(ApiError::InvalidQuery)?;
+    let page_offset = query.offset;
+    let page_limit = query.limit;
+    let mut conn = pool.get()?;
+
+    let result = web::block(move || -> Result<(Vec<SearchResult>, i64), diesel::result::Error> {
+        let count = diesel::sql_query("SELECT COUNT(*) AS count FROM products_fts WHERE products_fts MATCH ?")
+            .bind::<Text, _>(&query.expression)
+            .get_result::<Count>(&mut conn)?
+            .count;
+
+        // bm25 ranks lower for better matches; name matches weigh ten times a description match.
+        // Matches are marked with MATCH_START and MATCH_END, char(2) and char(3).
+        let results = diesel::sql_query(
+            "SELECT p.id, p.name, p.price, p.description, \
+                    highlight(products_fts, 0, char(2), char(3)) AS highlighted_name, \
+                    snippet(products_fts, 1, char(2), char(3), '…', 16) AS snippet, \
+                    -bm25(products_fts, 10.0, 1.0) AS score \
+             FROM products_fts JOIN products p ON p.id = products_fts.rowid \
+             WHERE products_fts MATCH ? \
+             ORDER BY score DESC, p.id \
+             LIMIT ? OFFSET ?",
+        )
+        .bind::<Text, _>(&query.expression)
+        .bind::<BigInt, _>(query.limit)
+        .bind::<BigInt, _>(query.offset)
+        .load::<SearchResult>(&mut conn)?;
+
+        Ok((results.into_iter().map(SearchResult::into_html).collect(), count))
+    })
+    .await??;
+
+    let response = PaginatedResponse {
+        data: result.0,
+        total_count: result.1,
+        offset: page_offset,
+        limit: page_limit,
+    };
+
+    Ok(HttpResponse::Ok().json(response))
+}
+
+// Upserts a whole scrape result from lab1 in one transaction; products that fail validation
+// are reported back instead of failing the import
+pub async fn import_products(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    mut payload: web::Payload,
+) -> Result<HttpResponse, ApiError> {
+    let format = import::format_for(req.content_type()).ok_or(ApiError::UnsupportedContentType)?;
+    let mut body = Vec::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if body.len() + chunk.len() > import::MAX_IMPORT_BYTES {
+            return Err(ApiError::BodyTooLarge(import::MAX_IMPORT_BYTES));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    let text = String::from_utf8(body).map_err(|_| ApiError::InvalidImport("the body is not UTF-8 text".to_string()))?;
+    let scraped = web::block(move || format.decode(&text).map_err(|error| error.to_string()))
+        .await?
+        .map_err(ApiError::InvalidImport)?;
+
+    let mut conn = pool.get()?;
+    let report = web::block(move || conn.immediate_transaction(|conn| import::import_products(conn, scraped))).await??;
+
+    Ok(HttpResponse::Ok().json(report))
+}
+
 pub async fn get_product(
+    req: HttpRequest,
     pool: web::Data<DbPool>,
     product_id: web::Path<i32>,
 ) -> Result<HttpResponse, ApiError> {
     let mut conn = pool.get()?;
     let product = web::block(move ||
-        products.filter(id.eq(product_id.into_inner())).first::<Product>(&mut conn)
+        products.filter(id.eq(product_id.into_inner())).select(Product::as_select()).first::<Product>(&mut conn)
     )
-    .await.map_err(|_| ApiError::NotFound)??;
-    
-    Ok(HttpResponse::Ok().json(product))
+    .await??;
+
+    let tag = product_etag(product.version);
+    if none_match(&req, &tag) {
+        return Ok(HttpResponse::NotModified().insert_header(ETag(tag)).finish());
+    }
+    Ok(HttpResponse::Ok().insert_header(ETag(tag)).json(product))
 }
 
+// Replaces the product's fields, from JSON or from a multipart form like the one that created
+// it. The image is only replaced when the form has one.
 pub async fn update_product(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    product_id: web::Path<i32>,
+    payload: web::Payload,
+) -> Result<HttpResponse, ApiError> {
+    let (input, product_image_data) = if req.content_type().starts_with("multipart/") {
+        read_product_form(Multipart::new(req.headers(), payload)).await?
+    } else {
+        let input = web::JsonBody::<ProductInput>::new(&req, &mut payload.into_inner(), None, true)
+            .await
+            .map_err(|error| ApiError::InvalidJson(error.to_string()))?;
+        (input, None)
+    };
+    let mut updated_product = input.validate().map_err(ApiError::Validation)?;
+    let (original, thumbnails) = prepare_image(product_image_data).await?;
+    updated_product.image = original;
+
+    let expected = expected_versions(&req);
+    let mut conn = pool.get()?;
+    let product_id_inner = product_id.into_inner();
+    let updated = web::block(move || {
+        write_if_match(&mut conn, product_id_inner, expected.as_deref(), |conn| {
+            diesel::update(products.filter(id.eq(product_id_inner)))
+                .set(&ProductReplacement::from(updated_product))
+                .execute(conn)?;
+            save_thumbnails(conn, product_id_inner, &thumbnails)?;
+
+            // SQLite has no RETURNING through diesel here, so fetch the updated product
+            products.filter(id.eq(product_id_inner)).select(Product::as_select()).first::<Product>(conn)
+        })
+    })
+    .await??
+    .ok_or(ApiError::PreconditionFailed)?;
+
+    Ok(HttpResponse::Ok().insert_header(ETag(product_etag(updated.version))).json(updated))
+}
+
+// Applies a JSON Merge Patch, so clients send only the fields they change
+pub async fn patch_product(
+    req: HttpRequest,
     pool: web::Data<DbPool>,
     product_id: web::Path<i32>,
-    updated_product: web::Json<NewProduct>,
+    web::Json(patch): web::Json<serde_json::Value>,
 ) -> Result<HttpResponse, ApiError> {
+    let serde_json::Value::Object(patch) = patch else {
+        return Err(ApiError::InvalidJson("a merge patch must be a JSON object".to_string()));
+    };
+    let changes = parse_patch(patch).map_err(ApiError::Validation)?;
+
+    let expected = expected_versions(&req);
     let mut conn = pool.get()?;
     let product_id_inner = product_id.into_inner();
     let updated = web::block(move || {
-        // For SQLite, we'll update and then manually fetch the updated product
-        diesel::update(products.filter(id.eq(product_id_inner)))
-            .set(&updated_product.into_inner())
-            .execute(&mut conn)?;
-        
-        // Fetch the updated product
-        products.filter(id.eq(product_id_inner)).first::<Product>(&mut conn)
+        write_if_match(&mut conn, product_id_inner, expected.as_deref(), |conn| {
+            if !changes.is_empty() {
+                diesel::update(products.filter(id.eq(product_id_inner)))
+                    .set(&changes)
+                    .execute(conn)?;
+            }
+            products.filter(id.eq(product_id_inner)).select(Product::as_select()).first::<Product>(conn)
+        })
     })
-    .await.map_err(|_| ApiError::NotFound)??;
-    
-    Ok(HttpResponse::Ok().json(updated))
+    .await??
+    .ok_or(ApiError::PreconditionFailed)?;
+
+    Ok(HttpResponse::Ok().insert_header(ETag(product_etag(updated.version))).json(updated))
 }
 
 pub async fn delete_product(
+    req: HttpRequest,
     pool: web::Data<DbPool>,
     product_id: web::Path<i32>,
 ) -> Result<HttpResponse, ApiError> {
+    let expected = expected_versions(&req);
     let mut conn = pool.get()?;
     let product_id_inner = product_id.into_inner();
-    let deleted_count = web::block(move ||
-        diesel::delete(products.filter(id.eq(product_id_inner)))
-            .execute(&mut conn)
+    web::block(move ||
+        write_if_match(&mut conn, product_id_inner, expected.as_deref(), |conn| {
+            diesel::delete(products.filter(id.eq(product_id_inner))).execute(conn)
+        })
     )
+    .await??
+    .ok_or(ApiError::PreconditionFailed)?;
+
+    Ok(HttpResponse::Ok().json(format!("Product {} deleted", product_id_inner)))
+}
+
+pub async fn get_product_image(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    product_id: web::Path<i32>,
+    web::Query(params): web::Query<HashMap<String, String>>,
+) -> Result<HttpResponse, ApiError> {
+    let query = ImageQuery::parse(&params).map_err(ApiError::InvalidQuery)?;
+    let mut conn = pool.get()?;
+    let product_id_inner = product_id.into_inner();
+    let stored = web::block(move || -> QueryResult<StoredImage> {
+        let Some(original) = products.filter(id.eq(product_id_inner)).select(image).first::<Option<Vec<u8>>>(&mut conn).optional()? else {
+            return Ok(StoredImage::NoProduct);
+        };
+        let Some(original) = original else {
+            return Ok(StoredImage::NoImage);
+        };
+        let Some(size) = query.size else {
+            return Ok(StoredImage::Found(original));
+        };
+        // Thumbnails are made when the image is uploaded; reading one never writes
+        let thumbnail = product_thumbnails::table
+            .find((product_id_inner, size))
+            .select(product_thumbnails::data)
+            .first::<Vec<u8>>(&mut conn)
+            .optional()?;
+        Ok(thumbnail.map_or(StoredImage::NoThumbnail(size), StoredImage::Found))
+    })
     .await??;
-    
-    if deleted_count == 0 {
-        return Err(ApiError::NotFound);
+
+    match stored {
+        StoredImage::NoProduct => Err(ApiError::NotFound),
+        StoredImage::NoImage => Err(ApiError::ImageNotFound),
+        StoredImage::NoThumbnail(size) => Err(ApiError::ThumbnailNotFound(size)),
+        StoredImage::Found(data) => Ok(image_response(&req, data)),
     }
-    
-    Ok(HttpResponse::Ok().json(format!("Product {} deleted", product_id_inner)))
-}
\ No newline at end of file
+}
+
+// What GET /products/{id}/image found for the requested size
+enum StoredImage {
+    NoProduct,
+    NoImage,
+    NoThumbnail(&'static str),
+    Found(Vec<u8>),
+}
+
+// Replaces the image, honouring If-Match like the other writes. The ETag returned is the
+// product's new version, not the image's.
+pub async fn put_product_image(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    product_id: web::Path<i32>,
+    mut payload: web::Payload,
+) -> Result<HttpResponse, ApiError> {
+    let mut body = Vec::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if body.len() + chunk.len() > MAX_IMAGE_BYTES {
+            return Err(ImageError::TooLarge.into());
+        }
+        body.extend_from_slice(&chunk);
+    }
+
+    let upload = web::block(move || process_upload(body)).await??;
+    let expected = expected_versions(&req);
+    let mut conn = pool.get()?;
+    let product_id_inner = product_id.into_inner();
+    let updated_version = web::block(move || {
+        write_if_match(&mut conn, product_id_inner, expected.as_deref(), |conn| {
+            // Replacing the image drops the old thumbnails, see the product_thumbnails migration
+            diesel::update(products.filter(id.eq(product_id_inner)))
+                .set(image.eq(&upload.original))
+                .execute(conn)?;
+            save_thumbnails(conn, product_id_inner, &upload.thumbnails)?;
+            products.filter(id.eq(product_id_inner)).select(version).first::<i32>(conn)
+        })
+    })
+    .await??
+    .ok_or(ApiError::PreconditionFailed)?;
+
+    Ok(HttpResponse::NoContent().insert_header(ETag(product_etag(updated_version))).finish())
+}
+
+pub async fn delete_product_image(
+    req: HttpRequest,
+    pool: web::Data<DbPool>,
+    product_id: web::Path<i32>,
+) -> Result<HttpResponse, ApiError> {
+    let expected = expected_versions(&req);
+    let mut conn = pool.get()?;
+    let product_id_inner = product_id.into_inner();
+    let updated_version = web::block(move ||
+        write_if_match(&mut conn, product_id_inner, expected.as_deref(), |conn| {
+            diesel::update(products.filter(id.eq(product_id_inner)))
+                .set(image.eq(None::<Vec<u8>>))
+                .execute(conn)?;
+            products.filter(id.eq(product_id_inner)).select(version).first::<i32>(conn)
+        })
+    )
+    .await??
+    .ok_or(ApiError::PreconditionFailed)?;
+
+    Ok(HttpResponse::NoContent().insert_header(ETag(product_etag(updated_version))).finish())
+}
+
+// The text fields and image of a product form, as sent to POST /products or PUT /products/{id}
+async fn read_product_form(mut payload: Multipart) -> Result<(ProductInput, Option<Vec<u8>>), ApiError> {
+    let mut input = ProductInput::default();
+    let mut product_image_data: Option<Vec<u8>> = None;
+
+    while let Some(item) = payload.next().await {
+        let mut field = item?;
+        let content_disposition = field.content_disposition()
+            .ok_or(actix_multipart::MultipartError::ContentDispositionMissing)?;
+
+        if let Some(field_name) = content_disposition.get_name() {
+            match field_name {
+                "name" => input.name = Some(read_text(&mut field, "name").await?),
+                "price" => input.price = Some(serde_json::Value::String(read_text(&mut field, "price").await?)),
+                "description" => input.description = Some(read_text(&mut field, "description").await?),
+                "link" => input.link = Some(read_text(&mut field, "link").await?),
+                "image" => {
+                    let mut image_data = Vec::new();
+                    while let Some(chunk) = field.next().await {
+                        let chunk = chunk?;
+                        // Stop reading as soon as the image is too large rather than buffering all of it
+                        if image_data.len() + chunk.len() > MAX_IMAGE_BYTES {
+                            return Err(ImageError::TooLarge.into());
+                        }
+                        image_data.extend_from_slice(&chunk);
+                    }
+                    // Forms send an empty part when no file was chosen
+                    if !image_data.is_empty() {
+                        product_image_data = Some(image_data);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+    Ok((input, product_image_data))
+}
+
+// Validate an uploaded image and make its thumbnails, before anything is written
+async fn prepare_image(data: Option<Vec<u8>>) -> Result<(Option<Vec<u8>>, Vec<(&'static str, Vec<u8>)>), ApiError> {
+    match data {
+        Some(data) => {
+            let upload = web::block(move || process_upload(data)).await??;
+            Ok((Some(upload.original), upload.thumbnails))
+        }
+        None => Ok((None, Vec::new())),
+    }
+}
+
+fn save_thumbnails(
+    conn: &mut SqliteConnection,
+    product_id: i32,
+    thumbnails: &[(&str, Vec<u8>)],
+) -> QueryResult<usize> {
+    let rows: Vec<NewThumbnail> = thumbnails.iter()
+        .map(|(size, data)| NewThumbnail { product_id, size, data })
+        .collect();
+    diesel::replace_into(product_thumbnails::table)
+        .values(&rows)
+        .execute(conn)
+}
+
+// A text part of a multipart form, which must be UTF-8 like the rest of the API. Reading stops
+// at MAX_TEXT_FIELD_BYTES so a huge part is never buffered just to fail validation.
+async fn read_text(field: &mut actix_multipart::Field, field_name: &str) -> Result<String, ApiError> {
+    let mut value = Vec::new();
+    while let Some(chunk) = field.next().await {
+        let chunk = chunk?;
+        if value.len() + chunk.len() > MAX_TEXT_FIELD_BYTES {
+            let message = format!("must be at most {} bytes", MAX_TEXT_FIELD_BYTES);
+            return Err(ApiError::Validation(vec![FieldError::new(field_name, message)]));
+        }
+        value.extend_from_slice(&chunk);
+    }
+    String::from_utf8(value).map_err(|_| ApiError::Validation(vec![FieldError::new(field_name, "must be UTF-8 text")]))
+}
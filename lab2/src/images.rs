@@ -0,0 +1,159 @@
+use actix_web::http::header::{
+    ContentRange, ContentRangeSpec, ContentType, ETag, EntityTag, IfRange, Range, ACCEPT_RANGES,
+};
+use actix_web::{HttpMessage, HttpRequest, HttpResponse};
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader, Limits};
+use sha1::{Digest, Sha1};
+use std::io::Cursor;
+use thiserror::Error;
+use crate::preconditions::none_match;
+
+// Largest image accepted on upload
+pub const MAX_IMAGE_BYTES: usize = 5 * 1024 * 1024;
+// Longest side, in pixels, of an uploaded image
+pub const MAX_DIMENSION: u32 = 4096;
+// Memory a decoder may use: a MAX_DIMENSION square in RGBA with room for the decoder's own buffers
+const MAX_DECODE_BYTES: u64 = 2 * 4 * MAX_DIMENSION as u64 * MAX_DIMENSION as u64;
+// Names a client can pass as `size=`, and the longest side of each in pixels
+pub const THUMBNAIL_SIZES: [(&str, u32); 3] = [("small", 64), ("medium", 256), ("large", 512)];
+
+#[derive(Error, Debug)]
+pub enum ImageError {
+    #[error("Image is larger than {MAX_IMAGE_BYTES} bytes")]
+    TooLarge,
+    #[error("Unsupported image format; expected PNG, JPEG or GIF")]
+    Unsupported,
+    #[error("Invalid image: {0}")]
+    Invalid(String),
+}
+
+// An upload that passed validation, with its thumbnails as PNGs
+pub struct ProcessedImage {
+    pub original: Vec<u8>,
+    pub thumbnails: Vec<(&'static str, Vec<u8>)>,
+}
+
+// Check an uploaded image's size, format and dimensions, then make its thumbnails.
+// Decoding is CPU-bound, so call this from a blocking task.
+pub fn process_upload(data: Vec<u8>) -> Result<ProcessedImage, ImageError> {
+    if data.len() > MAX_IMAGE_BYTES {
+        return Err(ImageError::TooLarge);
+    }
+    let format = match sniff_content_type(&data) {
+        Some("image/png") => ImageFormat::Png,
+        Some("image/jpeg") => ImageFormat::Jpeg,
+        Some("image/gif") => ImageFormat::Gif,
+        _ => return Err(ImageError::Unsupported),
+    };
+    // The JPEG decoder fills in whatever is missing from a cut-off file rather than failing,
+    // so check that it ends with the end-of-image marker
+    if format == ImageFormat::Jpeg && !data.ends_with(&[0xff, 0xd9]) {
+        return Err(ImageError::Invalid("the JPEG data is incomplete".to_string()));
+    }
+
+    // The decoder that reports the dimensions is the one that decodes, and its limits stop it
+    // allocating for anything bigger than was checked here
+    let invalid = |error: image::ImageError| ImageError::Invalid(error.to_string());
+    let mut decoder = ImageReader::with_format(Cursor::new(&data), format).into_decoder().map_err(invalid)?;
+    let (width, height) = decoder.dimensions();
+    if width > MAX_DIMENSION || height > MAX_DIMENSION {
+        return Err(ImageError::Invalid(format!(
+            "it is {}x{} pixels; at most {}x{} are allowed",
+            width, height, MAX_DIMENSION, MAX_DIMENSION
+        )));
+    }
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_DIMENSION);
+    limits.max_image_height = Some(MAX_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_BYTES);
+    decoder.set_limits(limits).map_err(invalid)?;
+    let image = DynamicImage::from_decoder(decoder).map_err(invalid)?;
+
+    let thumbnails = thumbnails(&image).map_err(invalid)?;
+    Ok(ProcessedImage { original: data, thumbnails })
+}
+
+// The image shrunk to fit each preset size, never enlarged
+fn thumbnails(image: &DynamicImage) -> Result<Vec<(&'static str, Vec<u8>)>, image::ImageError> {
+    THUMBNAIL_SIZES.iter().map(|&(name, size)| {
+        let fitted = if image.width() <= size && image.height() <= size {
+            image.clone()
+        } else {
+            image.resize(size, size, FilterType::Triangle)
+        };
+        let mut png = Vec::new();
+        fitted.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+        Ok((name, png))
+    }).collect()
+}
+
+// The image format, going by the first bytes of the file rather than what the client claims
+pub fn sniff_content_type(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(&[0xff, 0xd8, 0xff]) {
+        Some("image/jpeg")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else if data.len() >= 14 && data.starts_with(b"BM") {
+        Some("image/bmp")
+    } else {
+        None
+    }
+}
+
+// Strong validator derived from the image bytes, so it changes whenever the image does
+pub fn etag(data: &[u8]) -> EntityTag {
+    let hash: String = Sha1::digest(data).iter().map(|byte| format!("{:02x}", byte)).collect();
+    EntityTag::new_strong(hash)
+}
+
+// Serve an image honouring If-None-Match, and a single byte range unless If-Range no longer matches
+pub fn image_response(req: &HttpRequest, data: Vec<u8>) -> HttpResponse {
+    let tag = etag(&data);
+    let length = data.len() as u64;
+    let content_type = sniff_content_type(&data).unwrap_or("application/octet-stream");
+
+    if none_match(req, &tag) {
+        return HttpResponse::NotModified().insert_header(ETag(tag)).finish();
+    }
+
+    // A range meant for an older version of the image would splice the two, so send it whole
+    let range_applies = match req.get_header::<IfRange>() {
+        Some(IfRange::EntityTag(other)) => other.strong_eq(&tag),
+        Some(IfRange::Date(_)) => false,
+        None => true,
+    };
+    // Several ranges would need a multipart body; the whole image is a valid answer to those too
+    let range = match req.get_header::<Range>() {
+        Some(Range::Bytes(specs)) if range_applies && specs.len() == 1 => Some(specs[0].to_satisfiable_range(length)),
+        _ => None,
+    };
+
+    let (mut response, body) = match range {
+        None => (HttpResponse::Ok(), data),
+        Some(Some((start, end))) => {
+            let mut response = HttpResponse::PartialContent();
+            response.insert_header(ContentRange(ContentRangeSpec::Bytes {
+                range: Some((start, end)),
+                instance_length: Some(length),
+            }));
+            (response, data[start as usize..=end as usize].to_vec())
+        }
+        Some(None) => {
+            return HttpResponse::RangeNotSatisfiable()
+                .insert_header(ContentRange(ContentRangeSpec::Bytes { range: None, instance_length: Some(length) }))
+                .insert_header(ETag(tag))
+                .finish();
+        }
+    };
+
+    response
+        .insert_header(ContentType(content_type.parse().unwrap()))
+        .insert_header(ETag(tag))
+        .insert_header((ACCEPT_RANGES, "bytes"))
+        .body(body)
+}
@@ -0,0 +1,97 @@
+use diesel::prelude::*;
+use serde::Serialize;
+use serde_json::Value;
+use crate::errors::FieldError;
+use crate::models::NewProduct;
+use crate::scraped::{Format, ScrapedProduct, UnreadableRecord};
+use crate::schema::products::dsl::*;
+use crate::validation::ProductInput;
+
+// Largest scrape result accepted by POST /products/import
+pub const MAX_IMPORT_BYTES: usize = 10 * 1024 * 1024;
+
+// Which of lab1's formats a Content-Type names: its JSON envelope, the productList XML, or BI
+// text. Suffixes like application/ld+json and application/atom+xml count as their base type.
+pub fn format_for(content_type: &str) -> Option<Format> {
+    let mime: mime::Mime = content_type.parse().ok()?;
+    match (mime.type_(), mime.subtype(), mime.suffix()) {
+        (_, mime::JSON, _) | (_, _, Some(mime::JSON)) => Some(Format::Json),
+        (mime::APPLICATION | mime::TEXT, mime::XML, _) | (_, _, Some(mime::XML)) => Some(Format::Xml),
+        (mime::TEXT, mime::PLAIN, _) => Some(Format::Bi),
+        _ => None,
+    }
+}
+
+#[derive(Serialize, Debug, Default)]
+pub struct ImportReport {
+    pub inserted: usize,
+    pub updated: usize,
+    pub rejected: usize,
+    pub rejections: Vec<Rejection>,
+}
+
+// A scraped product that was left out, and every reason it failed validation
+#[derive(Serialize, Debug)]
+pub struct Rejection {
+    // Position in the uploaded list, counting from zero
+    pub index: usize,
+    pub name: String,
+    pub errors: Vec<FieldError>,
+}
+
+// Insert or update every valid product, matching existing ones by link and then by name; records
+// that couldn't be read and products that fail validation are both rejected. The
+// caller runs this in one transaction, so a database error leaves nothing half imported.
+// Scraped images are only URLs, so stored images are left alone.
+pub fn import_products(
+    conn: &mut SqliteConnection,
+    scraped: Vec<Result<ScrapedProduct, UnreadableRecord>>,
+) -> QueryResult<ImportReport> {
+    let mut report = ImportReport::default();
+    for (index, scraped) in scraped.into_iter().enumerate() {
+        let scraped = match scraped {
+            Ok(scraped) => scraped,
+            Err(UnreadableRecord { name: record_name, errors }) => {
+                report.rejections.push(Rejection { index, name: record_name, errors });
+                continue;
+            }
+        };
+        let input = ProductInput {
+            name: Some(scraped.name.clone()),
+            price: Some(Value::from(scraped.price)),
+            description: scraped.description,
+            link: Some(scraped.link),
+        };
+        let product = match input.validate() {
+            Ok(product) => product,
+            Err(errors) => {
+                report.rejections.push(Rejection { index, name: scraped.name, errors });
+                continue;
+            }
+        };
+
+        match find_existing(conn, &product)? {
+            Some(existing_id) => {
+                diesel::update(products.filter(id.eq(existing_id))).set(&product).execute(conn)?;
+                report.updated += 1;
+            }
+            None => {
+                diesel::insert_into(products).values(&product).execute(conn)?;
+                report.inserted += 1;
+            }
+        }
+    }
+    report.rejected = report.rejections.len();
+    Ok(report)
+}
+
+// The oldest product with the same link or, failing that, the same name
+fn find_existing(conn: &mut SqliteConnection, product: &NewProduct) -> QueryResult<Option<i32>> {
+    if let Some(product_link) = &product.link {
+        let by_link = products.filter(link.eq(product_link)).select(id.assume_not_null()).order(id).first(conn).optional()?;
+        if by_link.is_some() {
+            return Ok(by_link);
+        }
+    }
+    products.filter(name.eq(&product.name)).select(id.assume_not_null()).order(id).first(conn).optional()
+}
@@ -0,0 +1,35 @@
+pub mod db;
+pub mod errors;
+pub mod handlers;
+pub mod images;
+pub mod import;
+pub mod models;
+pub mod preconditions;
+pub mod query;
+pub mod schema;
+pub mod scraped;
+pub mod validation;
+
+use actix_web::web;
+
+// The product API: extractor error handling and routes. The server and the tests both mount it
+// on an app that already has the connection pool
+pub fn configure(cfg: &mut web::ServiceConfig) {
+    cfg.app_data(web::JsonConfig::default().error_handler(errors::json_error))
+        .app_data(web::QueryConfig::default().error_handler(errors::query_error))
+        .app_data(web::PathConfig::default().error_handler(errors::path_error))
+        .route("/products", web::post().to(handlers::create_product))
+        .route("/products", web::get().to(handlers::get_products))
+        .route("/products/search", web::get().to(handlers::search_products))
+        .route("/products/import", web::post().to(handlers::import_products))
+        .route("/products/{id}", web::get().to(handlers::get_product))
+        .route("/products/{id}", web::put().to(handlers::update_product))
+        .route("/products/{id}", web::patch().to(handlers::patch_product))
+        .route("/products/{id}", web::delete().to(handlers::delete_product))
+        .service(
+            web::resource("/products/{id}/image")
+                .route(web::get().to(handlers::get_product_image))
+                .route(web::put().to(handlers::put_product_image))
+                .route(web::delete().to(handlers::delete_product_image)),
+        );
+}
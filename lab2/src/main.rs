@@ -1,12 +1,10 @@
+// The chat predates the lint gate; its warnings are allowed rather than reworked here
+#[allow(dead_code, clippy::let_unit_value, clippy::type_complexity)]
 mod websocket;
 mod tcp_server;
-mod db;
-mod handlers;
-mod models;
-mod schema;
 
 use actix_web::{middleware, web, App, HttpServer};
-use db::establish_connection;
+use lab2::db::establish_connection;
 use env_logger::Target;
 use log::info;
 use std::collections::HashMap;
@@ -47,11 +45,7 @@ async fn main() -> std::io::Result<()> {
             App::new()
                 .app_data(web::Data::new(pool.clone()))
                 .wrap(middleware::Logger::default())
-                .route("/products", web::post().to(handlers::create_product))
-                .route("/products", web::get().to(handlers::get_products))
-                .route("/products/{id}", web::get().to(handlers::get_product))
-                .route("/products/{id}", web::put().to(handlers::update_product))
-                .route("/products/{id}", web::delete().to(handlers::delete_product))
+                .configure(lab2::configure)
         })
         .bind("0.0.0.0:8080")?
         .run()
@@ -1,21 +1,167 @@
 use diesel::prelude::*;
-use serde::{Deserialize, Serialize};
+use diesel::sql_types::{Double, Integer, Nullable, Text};
+use serde::ser::SerializeStruct;
+use serde::{Serialize, Serializer};
 
-#[derive(Queryable, Selectable, Serialize, Deserialize, Debug, Clone)]
+// A product as the API shows it: the image itself is served from its own URL
+#[derive(Queryable, Selectable, Debug, Clone)]
 #[diesel(table_name = crate::schema::products)]
 pub struct Product {
     pub id: Option<i32>,
     pub name: String,
     pub price: f64,
     pub description: Option<String>,
-    pub image: Option<Vec<u8>>,
+    // Page the product was scraped from
+    pub link: Option<String>,
+    #[diesel(select_expression = crate::schema::products::image.is_not_null())]
+    #[diesel(select_expression_type = diesel::dsl::IsNotNull<crate::schema::products::image>)]
+    pub has_image: bool,
+    // Goes up with every change; sent as the ETag rather than in the JSON
+    pub version: i32,
+}
+
+impl Product {
+    pub fn image_url(&self) -> Option<String> {
+        match (self.id, self.has_image) {
+            (Some(id), true) => Some(format!("/products/{}/image", id)),
+            _ => None,
+        }
+    }
+}
+
+impl Serialize for Product {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut product = serializer.serialize_struct("Product", 6)?;
+        product.serialize_field("id", &self.id)?;
+        product.serialize_field("name", &self.name)?;
+        product.serialize_field("price", &self.price)?;
+        product.serialize_field("description", &self.description)?;
+        product.serialize_field("link", &self.link)?;
+        product.serialize_field("image_url", &self.image_url())?;
+        product.end()
+    }
 }
 
-#[derive(Insertable, Deserialize, AsChangeset)]
+// Built from a validated ProductInput, see validation.rs
+#[derive(Insertable, AsChangeset)]
 #[diesel(table_name = crate::schema::products)]
 pub struct NewProduct {
     pub name: String,
     pub price: f64,
     pub description: Option<String>,
+    pub link: Option<String>,
+    // Uploaded through multipart or /products/{id}/image, never as JSON
     pub image: Option<Vec<u8>>,
-}
\ No newline at end of file
+}
+
+// The columns a PUT sets. Every field is replaced, so a description or link the client left out
+// is cleared; the image is only replaced when a new one was uploaded.
+#[derive(AsChangeset)]
+#[diesel(table_name = crate::schema::products, treat_none_as_null = true)]
+pub struct ProductReplacement {
+    pub name: String,
+    pub price: f64,
+    pub description: Option<String>,
+    pub link: Option<String>,
+    #[diesel(treat_none_as_null = false)]
+    pub image: Option<Vec<u8>>,
+}
+
+impl From<NewProduct> for ProductReplacement {
+    fn from(product: NewProduct) -> ProductReplacement {
+        ProductReplacement {
+            name: product.name,
+            price: product.price,
+            description: product.description,
+            link: product.link,
+            image: product.image,
+        }
+    }
+}
+
+// The columns a PATCH sets. None leaves a column as it is, Some(None) clears it.
+#[derive(AsChangeset, Default, Debug)]
+#[diesel(table_name = crate::schema::products)]
+pub struct ProductChanges {
+    pub name: Option<String>,
+    pub price: Option<f64>,
+    pub description: Option<Option<String>>,
+    pub link: Option<Option<String>>,
+    pub image: Option<Option<Vec<u8>>>,
+}
+
+impl ProductChanges {
+    // Diesel refuses to run an UPDATE with nothing to set
+    pub fn is_empty(&self) -> bool {
+        self.name.is_none() && self.price.is_none() && self.description.is_none() && self.link.is_none() && self.image.is_none()
+    }
+}
+
+// A resized copy of a product's image, named after its preset size
+#[derive(Insertable)]
+#[diesel(table_name = crate::schema::product_thumbnails)]
+pub struct NewThumbnail<'a> {
+    pub product_id: i32,
+    pub size: &'a str,
+    pub data: &'a [u8],
+}
+
+// A full-text search match, with the matched terms marked in its name and description
+#[derive(QueryableByName, Serialize, Debug)]
+pub struct SearchResult {
+    #[diesel(sql_type = Integer)]
+    pub id: i32,
+    #[diesel(sql_type = Text)]
+    pub name: String,
+    #[diesel(sql_type = Double)]
+    pub price: f64,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub description: Option<String>,
+    #[diesel(sql_type = Text)]
+    pub highlighted_name: String,
+    // Fragment of the description around the best match
+    #[diesel(sql_type = Nullable<Text>)]
+    pub snippet: Option<String>,
+    // Higher is a better match; only comparable within one search
+    #[diesel(sql_type = Double)]
+    pub score: f64,
+}
+
+// FTS5 wraps matched terms in these control characters rather than in tags, so the product text
+// can be HTML-escaped before the markers become <mark> elements
+pub const MATCH_START: char = '\u{2}';
+pub const MATCH_END: char = '\u{3}';
+
+impl SearchResult {
+    // The highlighted name and snippet as safe HTML
+    pub fn into_html(self) -> SearchResult {
+        SearchResult {
+            highlighted_name: highlight_html(&self.highlighted_name),
+            snippet: self.snippet.as_deref().map(highlight_html),
+            ..self
+        }
+    }
+}
+
+fn highlight_html(text: &str) -> String {
+    let mut html = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => html.push_str("&amp;"),
+            '<' => html.push_str("&lt;"),
+            '>' => html.push_str("&gt;"),
+            '"' => html.push_str("&quot;"),
+            '\'' => html.push_str("&#39;"),
+            MATCH_START => html.push_str("<mark>"),
+            MATCH_END => html.push_str("</mark>"),
+            c => html.push(c),
+        }
+    }
+    html
+}
+
+#[derive(QueryableByName, Debug)]
+pub struct Count {
+    #[diesel(sql_type = diesel::sql_types::BigInt)]
+    pub count: i64,
+}
@@ -0,0 +1,49 @@
+use actix_web::http::header::{EntityTag, IfMatch, IfNoneMatch};
+use actix_web::{HttpMessage, HttpRequest};
+use diesel::prelude::*;
+use crate::schema::products::dsl::*;
+
+// A product's ETag is its version, which the products_version_after_update trigger bumps
+pub fn product_etag(product_version: i32) -> EntityTag {
+    EntityTag::new_strong(product_version.to_string())
+}
+
+// Whether If-None-Match already has this representation, so 304 Not Modified will do
+pub fn none_match(req: &HttpRequest, tag: &EntityTag) -> bool {
+    match req.get_header::<IfNoneMatch>() {
+        Some(IfNoneMatch::Any) => true,
+        Some(IfNoneMatch::Items(tags)) => tags.iter().any(|other| other.weak_eq(tag)),
+        None => false,
+    }
+}
+
+// The product versions If-Match allows a write to; None when any version will do
+pub fn expected_versions(req: &HttpRequest) -> Option<Vec<i32>> {
+    match req.get_header::<IfMatch>() {
+        Some(IfMatch::Items(tags)) => Some(
+            tags.iter()
+                .filter(|tag| !tag.weak)
+                .filter_map(|tag| tag.tag().parse().ok())
+                .collect(),
+        ),
+        Some(IfMatch::Any) | None => None,
+    }
+}
+
+// Run `write` only if the product is at one of the `expected` versions, inside a transaction
+// that takes the write lock first so nobody can change the product between the check and the
+// write. A missing product is diesel's NotFound; Ok(None) means the precondition failed.
+pub fn write_if_match<T>(
+    conn: &mut SqliteConnection,
+    product_id: i32,
+    expected: Option<&[i32]>,
+    write: impl FnOnce(&mut SqliteConnection) -> QueryResult<T>,
+) -> QueryResult<Option<T>> {
+    conn.immediate_transaction(|conn| {
+        let current = products.filter(id.eq(product_id)).select(version).first::<i32>(conn)?;
+        if expected.is_some_and(|expected| !expected.contains(&current)) {
+            return Ok(None);
+        }
+        write(conn).map(Some)
+    })
+}
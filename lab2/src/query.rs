@@ -0,0 +1,288 @@
+use std::collections::HashMap;
+use diesel::prelude::*;
+use diesel::sql_types::Text;
+use diesel::sqlite::Sqlite;
+use serde_json::Value;
+use crate::errors::FieldError;
+use crate::images::THUMBNAIL_SIZES;
+use crate::models::Product;
+use crate::schema::products;
+use crate::validation::MAX_NAME_LENGTH;
+
+// Fields a client can ask for with `fields=`
+pub const FIELDS: [&str; 6] = ["id", "name", "price", "description", "link", "image_url"];
+const MAX_LIMIT: i64 = 100;
+
+define_sql_function!(fn lower(text: Text) -> Text);
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortKey {
+    Id,
+    Name,
+    Price,
+}
+
+// Listing options for GET /products, validated from the query string
+#[derive(Debug)]
+pub struct ProductQuery {
+    pub offset: i64,
+    pub limit: i64,
+    // Case-insensitive substring of the product name
+    pub name: Option<String>,
+    pub min_price: Option<f64>,
+    pub max_price: Option<f64>,
+    pub has_image: Option<bool>,
+    pub sort: SortKey,
+    pub descending: bool,
+    // Fields to include in each product; all of them when not given
+    pub fields: Option<Vec<String>>,
+}
+
+impl Default for ProductQuery {
+    fn default() -> Self {
+        ProductQuery {
+            offset: 0,
+            limit: 10,
+            name: None,
+            min_price: None,
+            max_price: None,
+            has_image: None,
+            sort: SortKey::Id,
+            descending: false,
+            fields: None,
+        }
+    }
+}
+
+impl ProductQuery {
+    // Every problem with the parameters is reported, not just the first one
+    pub fn parse(params: &HashMap<String, String>) -> Result<ProductQuery, Vec<FieldError>> {
+        let mut query = ProductQuery::default();
+        let mut errors = Vec::new();
+
+        let mut names: Vec<&String> = params.keys().collect();
+        names.sort();
+        for parameter in names {
+            let value = params[parameter].trim();
+            let result = match parameter.as_str() {
+                "offset" => parse_integer(value, 0).map(|offset| query.offset = offset),
+                // Larger pages are capped rather than refused
+                "limit" => parse_integer(value, 1).map(|limit| query.limit = limit.min(MAX_LIMIT)),
+                "name" => parse_name(value).map(|name| query.name = Some(name)),
+                "min_price" => parse_price(value).map(|price| query.min_price = Some(price)),
+                "max_price" => parse_price(value).map(|price| query.max_price = Some(price)),
+                "has_image" => parse_bool(value).map(|has_image| query.has_image = Some(has_image)),
+                "sort" => parse_sort(value).map(|(key, descending)| {
+                    query.sort = key;
+                    query.descending = descending;
+                }),
+                "fields" => parse_fields(value).map(|fields| query.fields = Some(fields)),
+                _ => Err("unknown parameter".to_string()),
+            };
+            if let Err(message) = result {
+                errors.push(FieldError::new(parameter, message));
+            }
+        }
+
+        if let (Some(min), Some(max)) = (query.min_price, query.max_price) {
+            if min > max {
+                errors.push(FieldError::new("min_price", format!("must not be greater than max_price ({})", max)));
+            }
+        }
+
+        if errors.is_empty() { Ok(query) } else { Err(errors) }
+    }
+
+    // Products matching the filters, without ordering or paging
+    pub fn filtered(&self) -> products::BoxedQuery<'static, Sqlite> {
+        let mut query = products::table.into_boxed();
+        if let Some(name) = &self.name {
+            let pattern = format!("%{}%", name.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_"));
+            query = query.filter(products::name.like(pattern).escape('\\'));
+        }
+        if let Some(min_price) = self.min_price {
+            query = query.filter(products::price.ge(min_price));
+        }
+        if let Some(max_price) = self.max_price {
+            query = query.filter(products::price.le(max_price));
+        }
+        match self.has_image {
+            Some(true) => query = query.filter(products::image.is_not_null()),
+            Some(false) => query = query.filter(products::image.is_null()),
+            None => {}
+        }
+        query
+    }
+
+    // The requested page of matching products, sorted with the id breaking ties
+    pub fn page(&self) -> products::BoxedQuery<'static, Sqlite> {
+        let query = self.filtered();
+        let query = match (self.sort, self.descending) {
+            (SortKey::Id, false) => query.order(products::id.asc()),
+            (SortKey::Id, true) => query.order(products::id.desc()),
+            (SortKey::Name, false) => query.order(lower(products::name).asc()).then_order_by(products::id.asc()),
+            (SortKey::Name, true) => query.order(lower(products::name).desc()).then_order_by(products::id.asc()),
+            (SortKey::Price, false) => query.order(products::price.asc()).then_order_by(products::id.asc()),
+            (SortKey::Price, true) => query.order(products::price.desc()).then_order_by(products::id.asc()),
+        };
+        query.offset(self.offset).limit(self.limit)
+    }
+
+    // A product as JSON with only the selected fields
+    pub fn select_fields(&self, product: &Product) -> Value {
+        let mut value = serde_json::to_value(product).unwrap_or(Value::Null);
+        if let (Some(fields), Value::Object(object)) = (&self.fields, &mut value) {
+            object.retain(|key, _| fields.iter().any(|field| field == key));
+        }
+        value
+    }
+}
+
+// Paging and terms for GET /products/search
+#[derive(Debug)]
+pub struct SearchQuery {
+    // Every term quoted and prefix-matched, ready for FTS5 MATCH
+    pub expression: String,
+    pub offset: i64,
+    pub limit: i64,
+}
+
+impl SearchQuery {
+    pub fn parse(params: &HashMap<String, String>) -> Result<SearchQuery, Vec<FieldError>> {
+        let mut query = SearchQuery { expression: String::new(), offset: 0, limit: 10 };
+        let mut errors = Vec::new();
+
+        if !params.contains_key("q") {
+            errors.push(FieldError::new("q", "is required"));
+        }
+        let mut names: Vec<&String> = params.keys().collect();
+        names.sort();
+        for parameter in names {
+            let value = params[parameter].trim();
+            let result = match parameter.as_str() {
+                "q" => parse_search_terms(value).map(|expression| query.expression = expression),
+                "offset" => parse_integer(value, 0).map(|offset| query.offset = offset),
+                "limit" => parse_integer(value, 1).map(|limit| query.limit = limit.min(MAX_LIMIT)),
+                _ => Err("unknown parameter".to_string()),
+            };
+            if let Err(message) = result {
+                errors.push(FieldError::new(parameter, message));
+            }
+        }
+
+        if errors.is_empty() { Ok(query) } else { Err(errors) }
+    }
+}
+
+// Which version of a product's image GET /products/{id}/image serves
+#[derive(Debug, Default)]
+pub struct ImageQuery {
+    // A thumbnail size from THUMBNAIL_SIZES, or the original image when not given
+    pub size: Option<&'static str>,
+}
+
+impl ImageQuery {
+    pub fn parse(params: &HashMap<String, String>) -> Result<ImageQuery, Vec<FieldError>> {
+        let mut query = ImageQuery::default();
+        let mut errors = Vec::new();
+
+        let mut names: Vec<&String> = params.keys().collect();
+        names.sort();
+        for parameter in names {
+            let value = params[parameter].trim();
+            let result = match parameter.as_str() {
+                "size" => parse_size(value).map(|size| query.size = Some(size)),
+                _ => Err("unknown parameter".to_string()),
+            };
+            if let Err(message) = result {
+                errors.push(FieldError::new(parameter, message));
+            }
+        }
+
+        if errors.is_empty() { Ok(query) } else { Err(errors) }
+    }
+}
+
+fn parse_size(value: &str) -> Result<&'static str, String> {
+    THUMBNAIL_SIZES.iter().map(|&(name, _)| name).find(|&name| name == value).ok_or_else(|| {
+        let names: Vec<&str> = THUMBNAIL_SIZES.iter().map(|&(name, _)| name).collect();
+        format!("must be one of {}", names.join(", "))
+    })
+}
+
+// Turn free text into an FTS5 query matching products that contain every word, or a word
+// starting with it. Quoting each term keeps FTS5 operators and syntax errors out of user input.
+fn parse_search_terms(value: &str) -> Result<String, String> {
+    if value.chars().count() > MAX_NAME_LENGTH {
+        return Err(format!("must be at most {} characters", MAX_NAME_LENGTH));
+    }
+    let terms: Vec<String> = value.split_whitespace()
+        .filter(|term| term.chars().any(char::is_alphanumeric))
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() {
+        return Err("must contain at least one word".to_string());
+    }
+    Ok(terms.join(" "))
+}
+
+fn parse_integer(value: &str, min: i64) -> Result<i64, String> {
+    match value.parse::<i64>() {
+        Ok(number) if number >= min => Ok(number),
+        Ok(_) => Err(format!("must be at least {}", min)),
+        Err(_) => Err("must be an integer".to_string()),
+    }
+}
+
+fn parse_name(value: &str) -> Result<String, String> {
+    if value.is_empty() {
+        Err("must not be empty".to_string())
+    } else if value.chars().count() > MAX_NAME_LENGTH {
+        Err(format!("must be at most {} characters", MAX_NAME_LENGTH))
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+fn parse_price(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(price) if price.is_finite() && price >= 0.0 => Ok(price),
+        Ok(_) => Err("must be a non-negative number".to_string()),
+        Err(_) => Err("must be a number".to_string()),
+    }
+}
+
+fn parse_bool(value: &str) -> Result<bool, String> {
+    match value.to_lowercase().as_str() {
+        "true" | "1" | "yes" => Ok(true),
+        "false" | "0" | "no" => Ok(false),
+        _ => Err("must be true or false".to_string()),
+    }
+}
+
+// `price` sorts ascending and `-price` descending; likewise for name and id
+fn parse_sort(value: &str) -> Result<(SortKey, bool), String> {
+    let (field, descending) = match value.strip_prefix('-') {
+        Some(field) => (field, true),
+        None => (value, false),
+    };
+    let key = match field {
+        "id" => SortKey::Id,
+        "name" => SortKey::Name,
+        "price" => SortKey::Price,
+        _ => return Err("must be one of id, name, price, optionally prefixed with '-' for descending order".to_string()),
+    };
+    Ok((key, descending))
+}
+
+fn parse_fields(value: &str) -> Result<Vec<String>, String> {
+    let fields: Vec<String> = value.split(',').map(|field| field.trim().to_string()).filter(|f| !f.is_empty()).collect();
+    if fields.is_empty() {
+        return Err(format!("must list at least one of {}", FIELDS.join(", ")));
+    }
+    let unknown: Vec<&str> = fields.iter().map(String::as_str).filter(|field| !FIELDS.contains(field)).collect();
+    if !unknown.is_empty() {
+        return Err(format!("unknown field(s) {}; expected {}", unknown.join(", "), FIELDS.join(", ")));
+    }
+    Ok(fields)
+}
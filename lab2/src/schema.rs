@@ -1,5 +1,13 @@
 // @generated automatically by Diesel CLI.
 
+diesel::table! {
+    product_thumbnails (product_id, size) {
+        product_id -> Integer,
+        size -> Text,
+        data -> Binary,
+    }
+}
+
 diesel::table! {
     products (id) {
         id -> Nullable<Integer>,
@@ -7,5 +15,14 @@ diesel::table! {
         price -> Double,
         description -> Nullable<Text>,
         image -> Nullable<Binary>,
+        version -> Integer,
+        link -> Nullable<Text>,
     }
 }
+
+diesel::joinable!(product_thumbnails -> products (product_id));
+
+diesel::allow_tables_to_appear_in_same_query!(
+    product_thumbnails,
+    products,
+);
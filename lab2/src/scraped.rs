@@ -0,0 +1,244 @@
+use serde_json::{Map, Value};
+use crate::errors::FieldError;
+
+// lab1 writes this when a product page lists no attributes; it is not a description
+const MISSING_DESCRIPTION: &str = "Attributes not found";
+// Deeply nested BI is rejected instead of overflowing the stack
+const MAX_BI_DEPTH: usize = 128;
+
+// The formats lab1 saves scrape results in: a JSON envelope, a productList XML document, or
+// Bracketed-Indented (BI) text. They are read here rather than through lab1 so the API builds on
+// its own.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Json,
+    Xml,
+    Bi,
+}
+
+// The parts of a scraped product the API stores. Images are left out; they are uploaded to
+// /products/{id}/image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ScrapedProduct {
+    pub name: String,
+    pub price: f64,
+    pub link: String,
+    pub description: Option<String>,
+}
+
+// A record that could not be read as a product: what it was called, if anything, and why
+#[derive(Debug)]
+pub struct UnreadableRecord {
+    pub name: String,
+    pub errors: Vec<FieldError>,
+}
+
+impl Format {
+    // One result per record, so a bad record is reported on its own. Err is for a body that
+    // can't be read as a list of records at all.
+    pub fn decode(self, text: &str) -> Result<Vec<Result<ScrapedProduct, UnreadableRecord>>, String> {
+        let text = text.trim_start_matches('\u{feff}');
+        let records = match self {
+            Format::Json => json_records(text)?,
+            Format::Xml => xml_records(text)?,
+            Format::Bi => bi_records(text)?,
+        };
+        Ok(records.iter().map(scraped_product).collect())
+    }
+}
+
+// `{"timestamp": ..., "products": [...]}`
+fn json_records(text: &str) -> Result<Vec<Value>, String> {
+    let document: Value = serde_json::from_str(text).map_err(|error| format!("invalid JSON: {}", error))?;
+    match document.get("products") {
+        Some(Value::Array(records)) => Ok(records.clone()),
+        _ => Err("the JSON has no \"products\" list".to_string()),
+    }
+}
+
+// `<productList><product><name>...</name>...</product>...</productList>`, where each child of a
+// product is one of its fields
+fn xml_records(text: &str) -> Result<Vec<Value>, String> {
+    let document = roxmltree::Document::parse(text).map_err(|error| format!("invalid XML: {}", error))?;
+    let root = document.root_element();
+    if root.tag_name().name() != "productList" {
+        return Err(format!("the XML root is <{}>, not <productList>", root.tag_name().name()));
+    }
+    let records = root.children()
+        .filter(|node| node.is_element() && node.tag_name().name() == "product")
+        .map(|product| {
+            let fields = product.children()
+                .filter(|node| node.is_element())
+                .map(|field| {
+                    let text: String = field.descendants().filter(|node| node.is_text()).filter_map(|node| node.text()).collect();
+                    (field.tag_name().name().to_string(), Value::String(text.trim().to_string()))
+                })
+                .collect();
+            Value::Object(fields)
+        })
+        .collect();
+    Ok(records)
+}
+
+// `Products [ ... ]`, as lab1's serialize_products_to_bi writes it
+fn bi_records(text: &str) -> Result<Vec<Value>, String> {
+    let list = text.trim_start()
+        .strip_prefix("Products")
+        .ok_or("the BI does not start with \"Products\"")?;
+    match parse_bi(list)? {
+        Value::Array(records) => Ok(records),
+        _ => Err("the BI \"Products\" is not a list".to_string()),
+    }
+}
+
+// `key [ ... ]` blocks become object entries and bare `[ ... ]` blocks arrays; every other line
+// is a JSON value. The format does not delimit objects inside a list, so a list item ends where
+// one of its keys repeats.
+fn parse_bi(text: &str) -> Result<Value, String> {
+    let lines: Vec<(usize, &str)> = text.lines()
+        .enumerate()
+        .map(|(number, line)| (number + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty())
+        .collect();
+    let mut parser = BiParser { lines, position: 0 };
+
+    let items = parser.parse_items(0)?;
+    if let Some((number, line)) = parser.lines.get(parser.position) {
+        return Err(format!("unexpected {:?} on line {} of the BI", line, number));
+    }
+    block_value(items)
+}
+
+enum BiItem {
+    Entry(String, Value),
+    Value(Value),
+}
+
+struct BiParser<'a> {
+    lines: Vec<(usize, &'a str)>,
+    position: usize,
+}
+
+impl BiParser<'_> {
+    // Items up to the next unmatched `]` or the end of input
+    fn parse_items(&mut self, depth: usize) -> Result<Vec<BiItem>, String> {
+        if depth > MAX_BI_DEPTH {
+            return Err("the BI is nested too deeply".to_string());
+        }
+
+        let mut items = Vec::new();
+        while let Some(&(number, line)) = self.lines.get(self.position) {
+            if line == "]" {
+                break;
+            }
+            self.position += 1;
+
+            if line == "[" {
+                let list = self.parse_items(depth + 1)?;
+                self.expect_close(number)?;
+                items.push(BiItem::Value(Value::Array(list_items(list))));
+            } else if let Some(key) = line.strip_suffix('[').filter(|_| !line.starts_with('"')) {
+                let value = self.parse_items(depth + 1)?;
+                self.expect_close(number)?;
+                items.push(BiItem::Entry(key.trim().to_string(), block_value(value)?));
+            } else {
+                let value = serde_json::from_str(line).map_err(|error| format!("invalid value on line {} of the BI: {}", number, error))?;
+                items.push(BiItem::Value(value));
+            }
+        }
+        Ok(items)
+    }
+
+    fn expect_close(&mut self, opened_on: usize) -> Result<(), String> {
+        match self.lines.get(self.position) {
+            Some((_, "]")) => {
+                self.position += 1;
+                Ok(())
+            }
+            _ => Err(format!("the '[' on line {} of the BI is never closed", opened_on)),
+        }
+    }
+}
+
+// The contents of `key [ ... ]`: a single value, or the entries of an object
+fn block_value(items: Vec<BiItem>) -> Result<Value, String> {
+    let mut object = Map::new();
+    let count = items.len();
+    for item in items {
+        match item {
+            BiItem::Entry(key, value) => {
+                object.insert(key, value);
+            }
+            BiItem::Value(value) if count == 1 => return Ok(value),
+            BiItem::Value(_) => return Err("a BI block holds either one value or key entries".to_string()),
+        }
+    }
+    Ok(Value::Object(object))
+}
+
+fn list_items(items: Vec<BiItem>) -> Vec<Value> {
+    let mut list = Vec::new();
+    let mut current: Option<Map<String, Value>> = None;
+
+    for item in items {
+        match item {
+            BiItem::Entry(key, value) => {
+                let object = current.get_or_insert_with(Map::new);
+                if object.contains_key(&key) {
+                    list.push(Value::Object(std::mem::take(object)));
+                }
+                object.insert(key, value);
+            }
+            BiItem::Value(value) => {
+                if let Some(object) = current.take() {
+                    list.push(Value::Object(object));
+                }
+                list.push(value);
+            }
+        }
+    }
+    if let Some(object) = current {
+        list.push(Value::Object(object));
+    }
+    list
+}
+
+// A product record from any of the formats. lab1 has called the description `attribut` in JSON,
+// `attribute` in XML and `attributes` in BI; XML writes missing fields as empty elements.
+fn scraped_product(record: &Value) -> Result<ScrapedProduct, UnreadableRecord> {
+    let text = |key: &str| record.get(key).and_then(Value::as_str);
+
+    let name = match record.get("name") {
+        Some(Value::String(name)) => Ok(name.clone()),
+        Some(_) => Err("must be a string"),
+        None => Err("is required"),
+    };
+    let price = match record.get("price") {
+        Some(Value::String(price)) => price.trim().parse().map_err(|_| "must be a number"),
+        Some(price) => price.as_f64().ok_or("must be a number"),
+        None => Err("is required"),
+    };
+    let (name, price) = match (name, price) {
+        (Ok(name), Ok(price)) => (name, price),
+        (name, price) => {
+            let mut errors = Vec::new();
+            if let Err(message) = &name {
+                errors.push(FieldError::new("name", *message));
+            }
+            if let Err(message) = price {
+                errors.push(FieldError::new("price", message));
+            }
+            return Err(UnreadableRecord { name: name.unwrap_or_default(), errors });
+        }
+    };
+
+    let description = text("attributes").or_else(|| text("attribut")).or_else(|| text("attribute"))
+        .filter(|description| !description.trim().is_empty() && *description != MISSING_DESCRIPTION)
+        .map(str::to_string);
+    Ok(ScrapedProduct {
+        name,
+        price,
+        link: text("link").unwrap_or_default().to_string(),
+        description,
+    })
+}
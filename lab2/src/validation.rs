@@ -0,0 +1,138 @@
+use serde::Deserialize;
+use serde_json::{Map, Value};
+use crate::errors::FieldError;
+use crate::models::{NewProduct, ProductChanges};
+
+pub const MAX_NAME_LENGTH: usize = 200;
+pub const MAX_DESCRIPTION_LENGTH: usize = 5000;
+pub const MAX_LINK_LENGTH: usize = 2000;
+
+// Product fields as a client sent them, before any checks. The price is kept loosely typed so
+// a wrong one is reported against its field instead of failing the whole body.
+#[derive(Debug, Default, Deserialize)]
+pub struct ProductInput {
+    pub name: Option<String>,
+    pub price: Option<Value>,
+    pub description: Option<String>,
+    pub link: Option<String>,
+}
+
+impl ProductInput {
+    // Check every field, collecting all the problems rather than stopping at the first.
+    // The product comes back without an image; that is uploaded separately.
+    pub fn validate(self) -> Result<NewProduct, Vec<FieldError>> {
+        let mut errors = Vec::new();
+        let name = check(&mut errors, "name", self.name.as_deref().ok_or_else(required).and_then(validate_name));
+        let price = check(&mut errors, "price", self.price.as_ref().ok_or_else(required).and_then(validate_price));
+        let description = check(&mut errors, "description", self.description.as_deref().map(validate_description).transpose());
+        let link = check(&mut errors, "link", self.link.as_deref().map(validate_link).transpose());
+
+        match (name, price, description, link) {
+            (Some(name), Some(price), Some(description), Some(link)) => Ok(NewProduct {
+                name,
+                price,
+                description: description.flatten(),
+                link: link.flatten(),
+                image: None,
+            }),
+            _ => Err(errors),
+        }
+    }
+}
+
+// Read a JSON Merge Patch (RFC 7396) of a product as shown by the API: fields it leaves out stay
+// as they are and null clears one. Only the image can be cleared this way, not set; uploads go
+// to /products/{id}/image.
+pub fn parse_patch(patch: Map<String, Value>) -> Result<ProductChanges, Vec<FieldError>> {
+    let mut changes = ProductChanges::default();
+    let mut errors = Vec::new();
+    for (field, value) in &patch {
+        let result = match (field.as_str(), value) {
+            ("name" | "price", Value::Null) => Err("is required and cannot be cleared".to_string()),
+            ("name", Value::String(text)) => validate_name(text).map(|name| changes.name = Some(name)),
+            ("name", _) => Err("must be a string".to_string()),
+            ("price", value) => validate_price(value).map(|price| changes.price = Some(price)),
+            ("description", Value::Null) => {
+                changes.description = Some(None);
+                Ok(())
+            }
+            ("description", Value::String(text)) => validate_description(text).map(|description| changes.description = Some(description)),
+            ("description", _) => Err("must be a string or null".to_string()),
+            ("link", Value::Null) => {
+                changes.link = Some(None);
+                Ok(())
+            }
+            ("link", Value::String(text)) => validate_link(text).map(|link| changes.link = Some(link)),
+            ("link", _) => Err("must be a string or null".to_string()),
+            ("image_url", Value::Null) => {
+                changes.image = Some(None);
+                Ok(())
+            }
+            ("image_url", _) => Err("can only be set to null; upload images to /products/{id}/image".to_string()),
+            ("id", _) => Err("cannot be changed".to_string()),
+            _ => Err("is not a product field".to_string()),
+        };
+        check(&mut errors, field, result);
+    }
+    if errors.is_empty() { Ok(changes) } else { Err(errors) }
+}
+
+fn check<T>(errors: &mut Vec<FieldError>, field: &str, result: Result<T, String>) -> Option<T> {
+    result.map_err(|message| errors.push(FieldError::new(field, message))).ok()
+}
+
+fn required() -> String {
+    "is required".to_string()
+}
+
+// Names are stored without surrounding whitespace
+pub fn validate_name(value: &str) -> Result<String, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        Err("must not be empty".to_string())
+    } else if value.chars().count() > MAX_NAME_LENGTH {
+        Err(format!("must be at most {} characters", MAX_NAME_LENGTH))
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+// JSON clients send a number, forms send text; both must come to a non-negative finite amount
+pub fn validate_price(value: &Value) -> Result<f64, String> {
+    let price = match value {
+        Value::Number(number) => number.as_f64(),
+        Value::String(text) => text.trim().parse::<f64>().ok(),
+        _ => None,
+    };
+    match price {
+        Some(price) if price.is_finite() && price >= 0.0 => Ok(price),
+        Some(_) => Err("must be a non-negative number".to_string()),
+        None => Err("must be a number".to_string()),
+    }
+}
+
+// A blank description is the same as none
+pub fn validate_description(value: &str) -> Result<Option<String>, String> {
+    let value = value.trim();
+    if value.chars().count() > MAX_DESCRIPTION_LENGTH {
+        Err(format!("must be at most {} characters", MAX_DESCRIPTION_LENGTH))
+    } else if value.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(value.to_string()))
+    }
+}
+
+// A blank link is the same as none; anything else must be an absolute web address
+pub fn validate_link(value: &str) -> Result<Option<String>, String> {
+    let value = value.trim();
+    if value.is_empty() {
+        Ok(None)
+    } else if value.chars().count() > MAX_LINK_LENGTH {
+        Err(format!("must be at most {} characters", MAX_LINK_LENGTH))
+    } else if !(value.starts_with("http://") || value.starts_with("https://")) || value.contains(char::is_whitespace) {
+        Err("must be an http or https URL".to_string())
+    } else {
+        Ok(Some(value.to_string()))
+    }
+}
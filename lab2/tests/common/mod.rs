@@ -0,0 +1,105 @@
+// The product API on a fresh SQLite database with the migrations applied, called in-process
+// through actix's test service
+
+// Every test crate includes this module but uses only part of it
+#![allow(dead_code)]
+
+use std::fs;
+use std::io::Cursor;
+use actix_http::Request;
+use actix_web::dev::{Service, ServiceResponse};
+use actix_web::{test, web, App};
+use diesel::connection::SimpleConnection;
+use diesel::r2d2::ConnectionManager;
+use diesel::sqlite::SqliteConnection;
+use image::{DynamicImage, ImageFormat};
+use lab2::db::DbPool;
+use serde_json::Value;
+use tempfile::TempDir;
+
+const BOUNDARY: &str = "lab2-test-boundary";
+
+// A database that lives as long as this value does
+pub struct Database {
+    pub pool: DbPool,
+    _dir: TempDir,
+}
+
+impl Database {
+    // Runs every migration's up.sql in order, as the diesel CLI would
+    pub fn new() -> Database {
+        let dir = tempfile::tempdir().unwrap();
+        let manager = ConnectionManager::<SqliteConnection>::new(dir.path().join("products.db").to_str().unwrap());
+        let pool = r2d2::Pool::builder().max_size(2).build(manager).unwrap();
+
+        let mut migrations: Vec<_> = fs::read_dir(concat!(env!("CARGO_MANIFEST_DIR"), "/migrations"))
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        migrations.sort();
+        let mut conn = pool.get().unwrap();
+        for migration in migrations {
+            let sql = fs::read_to_string(migration.join("up.sql")).unwrap();
+            conn.batch_execute(&sql).unwrap_or_else(|e| panic!("{}: {}", migration.display(), e));
+        }
+        Database { pool, _dir: dir }
+    }
+}
+
+// The app as the tests call it
+pub trait TestApp: Service<Request, Response = ServiceResponse, Error = actix_web::Error> {}
+
+impl<S: Service<Request, Response = ServiceResponse, Error = actix_web::Error>> TestApp for S {}
+
+// The routes main serves, on this database
+pub async fn app(database: &Database) -> impl TestApp {
+    test::init_service(App::new().app_data(web::Data::new(database.pool.clone())).configure(lab2::configure)).await
+}
+
+// A multipart/form-data body with the text fields and, when given, an image part of that
+// content type
+pub fn form(request: test::TestRequest, fields: &[(&str, &str)], image: Option<(&str, &[u8])>) -> test::TestRequest {
+    let mut body = Vec::new();
+    for (field, value) in fields {
+        body.extend_from_slice(format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+            BOUNDARY, field, value
+        ).as_bytes());
+    }
+    if let Some((content_type, data)) = image {
+        body.extend_from_slice(format!(
+            "--{}\r\nContent-Disposition: form-data; name=\"image\"; filename=\"upload\"\r\nContent-Type: {}\r\n\r\n",
+            BOUNDARY, content_type
+        ).as_bytes());
+        body.extend_from_slice(data);
+        body.extend_from_slice(b"\r\n");
+    }
+    body.extend_from_slice(format!("--{}--\r\n", BOUNDARY).as_bytes());
+    request
+        .insert_header(("Content-Type", format!("multipart/form-data; boundary={}", BOUNDARY)))
+        .set_payload(body)
+}
+
+// Creates a product through POST /products and returns it as the API does
+pub async fn create_product(app: &impl TestApp, fields: &[(&str, &str)]) -> Value {
+    let request = form(test::TestRequest::post().uri("/products"), fields, None).to_request();
+    let response = test::call_service(app, request).await;
+    assert_eq!(response.status(), 201, "creating {:?}", fields);
+    test::read_body_json(response).await
+}
+
+// The status and JSON body of a response
+pub async fn json(app: &impl TestApp, request: test::TestRequest) -> (u16, Value) {
+    let response = test::call_service(app, request.to_request()).await;
+    let status = response.status().as_u16();
+    let body = test::read_body(response).await;
+    let value = if body.is_empty() { Value::Null } else { serde_json::from_slice(&body).unwrap() };
+    (status, value)
+}
+
+// A plain image of that size, encoded in the given format
+pub fn encoded_image(width: u32, height: u32, format: ImageFormat) -> Vec<u8> {
+    let mut data = Vec::new();
+    DynamicImage::new_rgb8(width, height).write_to(&mut Cursor::new(&mut data), format).unwrap();
+    data
+}
@@ -0,0 +1,184 @@
+mod common;
+
+use actix_web::test::{self, TestRequest};
+use common::{app, create_product, encoded_image, form, json, Database};
+use diesel::prelude::*;
+use image::{GenericImageView, ImageFormat};
+use lab2::images::{MAX_DIMENSION, MAX_IMAGE_BYTES};
+
+fn put_image(product_id: i64, content_type: &str, data: Vec<u8>) -> TestRequest {
+    TestRequest::put()
+        .uri(&format!("/products/{}/image", product_id))
+        .insert_header(("Content-Type", content_type))
+        .set_payload(data)
+}
+
+fn get_image(product_id: i64, size: Option<&str>) -> TestRequest {
+    let uri = match size {
+        Some(size) => format!("/products/{}/image?size={}", product_id, size),
+        None => format!("/products/{}/image", product_id),
+    };
+    TestRequest::get().uri(&uri)
+}
+
+#[actix_web::test]
+async fn valid_images_are_stored_as_sent() {
+    let database = Database::new();
+    let app = app(&database).await;
+
+    for (format, content_type) in [(ImageFormat::Png, "image/png"), (ImageFormat::Jpeg, "image/jpeg"), (ImageFormat::Gif, "image/gif")] {
+        let product = create_product(&app, &[("name", "Phone"), ("price", "100")]).await;
+        let product_id = product["id"].as_i64().unwrap();
+        assert!(product["image_url"].is_null());
+
+        let data = encoded_image(40, 30, format);
+        let response = test::call_service(&app, put_image(product_id, content_type, data.clone()).to_request()).await;
+        assert_eq!(response.status(), 204, "{:?}", format);
+        assert!(response.headers().contains_key("ETag"));
+
+        let response = test::call_service(&app, get_image(product_id, None).to_request()).await;
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.headers().get("Content-Type").unwrap(), content_type);
+        assert_eq!(test::read_body(response).await, data);
+
+        let (_, product) = json(&app, TestRequest::get().uri(&format!("/products/{}", product_id))).await;
+        assert_eq!(product["image_url"], format!("/products/{}/image", product_id));
+    }
+
+    // A form can carry the image along with the fields
+    let data = encoded_image(40, 30, ImageFormat::Png);
+    let request = form(TestRequest::post().uri("/products"), &[("name", "Tablet"), ("price", "200")], Some(("image/png", &data)));
+    let (status, product) = json(&app, request).await;
+    assert_eq!(status, 201);
+    let response = test::call_service(&app, get_image(product["id"].as_i64().unwrap(), None).to_request()).await;
+    assert_eq!(test::read_body(response).await, data);
+}
+
+#[actix_web::test]
+async fn thumbnails_fit_each_size_without_enlarging() {
+    let database = Database::new();
+    let app = app(&database).await;
+
+    for (width, height, expected) in [
+        (1000, 500, [(64, 32), (256, 128), (512, 256)]),
+        (100, 300, [(21, 64), (85, 256), (100, 300)]),
+    ] {
+        let product_id = create_product(&app, &[("name", "Phone"), ("price", "100")]).await["id"].as_i64().unwrap();
+        let request = put_image(product_id, "image/jpeg", encoded_image(width, height, ImageFormat::Jpeg));
+        assert_eq!(test::call_service(&app, request.to_request()).await.status(), 204);
+
+        for (size, dimensions) in ["small", "medium", "large"].into_iter().zip(expected) {
+            let response = test::call_service(&app, get_image(product_id, Some(size)).to_request()).await;
+            assert_eq!(response.status(), 200, "{}", size);
+            // Thumbnails are PNGs whatever the original was
+            assert_eq!(response.headers().get("Content-Type").unwrap(), "image/png");
+            let thumbnail = image::load_from_memory(&test::read_body(response).await).unwrap();
+            assert_eq!(thumbnail.dimensions(), dimensions, "{} of {}x{}", size, width, height);
+        }
+    }
+}
+
+#[actix_web::test]
+async fn truncated_images_are_rejected() {
+    let database = Database::new();
+    let app = app(&database).await;
+    let product_id = create_product(&app, &[("name", "Phone"), ("price", "100")]).await["id"].as_i64().unwrap();
+
+    for (format, content_type) in [(ImageFormat::Png, "image/png"), (ImageFormat::Jpeg, "image/jpeg"), (ImageFormat::Gif, "image/gif")] {
+        let mut data = encoded_image(200, 200, format);
+        data.truncate(data.len() / 2);
+        let (status, body) = json(&app, put_image(product_id, content_type, data)).await;
+        assert_eq!(status, 422, "{:?}", format);
+        assert_eq!(body["code"], "invalid_image");
+    }
+
+    // Nothing was stored, and a rejected form creates no product
+    let (status, _) = json(&app, get_image(product_id, None)).await;
+    assert_eq!(status, 404);
+    let mut data = encoded_image(200, 200, ImageFormat::Png);
+    data.truncate(100);
+    let request = form(TestRequest::post().uri("/products"), &[("name", "Tablet"), ("price", "200")], Some(("image/png", &data)));
+    assert_eq!(json(&app, request).await.0, 422);
+    let (_, page) = json(&app, TestRequest::get().uri("/products")).await;
+    assert_eq!(page["total_count"], 1);
+}
+
+#[actix_web::test]
+async fn oversized_images_are_rejected() {
+    let database = Database::new();
+    let app = app(&database).await;
+    let product_id = create_product(&app, &[("name", "Phone"), ("price", "100")]).await["id"].as_i64().unwrap();
+
+    // Too many bytes, whether or not they are an image
+    let mut data = encoded_image(10, 10, ImageFormat::Png);
+    data.resize(MAX_IMAGE_BYTES + 1, 0);
+    let (status, body) = json(&app, put_image(product_id, "image/png", data.clone())).await;
+    assert_eq!((status, body["code"].as_str()), (413, Some("image_too_large")));
+    let request = form(TestRequest::post().uri("/products"), &[("name", "Tablet"), ("price", "200")], Some(("image/png", &data)));
+    assert_eq!(json(&app, request).await.0, 413);
+
+    // Too many pixels, however well compressed
+    let data = encoded_image(MAX_DIMENSION + 1, 1, ImageFormat::Png);
+    assert!(data.len() < MAX_IMAGE_BYTES);
+    let (status, body) = json(&app, put_image(product_id, "image/png", data)).await;
+    assert_eq!((status, body["code"].as_str()), (422, Some("invalid_image")));
+    assert!(body["message"].as_str().unwrap().contains("4097x1 pixels"), "{}", body);
+
+    let (status, _) = json(&app, get_image(product_id, None)).await;
+    assert_eq!(status, 404);
+}
+
+#[actix_web::test]
+async fn the_bytes_decide_the_type_not_the_declared_mime_type() {
+    let database = Database::new();
+    let app = app(&database).await;
+    let product_id = create_product(&app, &[("name", "Phone"), ("price", "100")]).await["id"].as_i64().unwrap();
+
+    // A PNG sent as a JPEG is still a PNG
+    let data = encoded_image(20, 20, ImageFormat::Png);
+    let response = test::call_service(&app, put_image(product_id, "image/jpeg", data.clone()).to_request()).await;
+    assert_eq!(response.status(), 204);
+    let response = test::call_service(&app, get_image(product_id, None).to_request()).await;
+    assert_eq!(response.headers().get("Content-Type").unwrap(), "image/png");
+    assert_eq!(test::read_body(response).await, data);
+
+    // Something that isn't an image is refused whatever it claims to be
+    let (status, body) = json(&app, put_image(product_id, "image/png", b"<svg xmlns=\"http://www.w3.org/2000/svg\"/>".to_vec())).await;
+    assert_eq!((status, body["code"].as_str()), (415, Some("unsupported_image")));
+    let request = form(TestRequest::post().uri("/products"), &[("name", "Tablet"), ("price", "200")], Some(("image/gif", b"GIF89a?")));
+    let (status, body) = json(&app, request).await;
+    assert_eq!((status, body["code"].as_str()), (422, Some("invalid_image")));
+    let request = form(TestRequest::post().uri("/products"), &[("name", "Tablet"), ("price", "200")], Some(("image/jpeg", b"plain text")));
+    let (status, body) = json(&app, request).await;
+    assert_eq!((status, body["code"].as_str()), (415, Some("unsupported_image")));
+
+    // The PNG from before is untouched
+    let response = test::call_service(&app, get_image(product_id, None).to_request()).await;
+    assert_eq!(test::read_body(response).await, data);
+}
+
+#[actix_web::test]
+async fn reading_a_missing_thumbnail_does_not_create_it() {
+    use lab2::schema::{product_thumbnails, products};
+
+    let database = Database::new();
+    let app = app(&database).await;
+    let product_id = create_product(&app, &[("name", "Phone"), ("price", "100")]).await["id"].as_i64().unwrap();
+
+    // An image stored before thumbnails existed; writing it drops any thumbnails
+    let mut conn = database.pool.get().unwrap();
+    diesel::update(products::table.filter(products::id.eq(product_id as i32)))
+        .set(products::image.eq(encoded_image(20, 20, ImageFormat::Png)))
+        .execute(&mut conn)
+        .unwrap();
+
+    let (status, body) = json(&app, get_image(product_id, Some("small"))).await;
+    assert_eq!((status, body["code"].as_str()), (404, Some("thumbnail_not_found")));
+    let thumbnails: i64 = product_thumbnails::table.count().get_result(&mut conn).unwrap();
+    assert_eq!(thumbnails, 0);
+
+    // The original is still there, and the size must be one of the presets
+    assert_eq!(test::call_service(&app, get_image(product_id, None).to_request()).await.status(), 200);
+    let (status, body) = json(&app, get_image(product_id, Some("huge"))).await;
+    assert_eq!((status, body["errors"][0]["field"].as_str()), (400, Some("size")));
+}
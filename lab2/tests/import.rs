@@ -0,0 +1,243 @@
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{app, create_product, json, Database, TestApp};
+use serde_json::Value;
+
+// Scrape results as lab1 writes them
+const JSON: &str = r#"{
+    "timestamp": "2026-10-18T12:00:00+00:00",
+    "products": [
+        {
+    "name": "iPhone 15",
+    "price": 12999,
+    "link": "https://xstore.md/p/1",
+    "attribut": "Ecran: 6.1\"",
+    "image_url": "https://xstore.md/i/1.jpg",
+    "sku": null,
+    "brand": "Apple",
+    "availability": null
+},
+        {
+    "name": "Pixel 8",
+    "price": 10999.5,
+    "link": "",
+    "attribut": "Attributes not found",
+    "image_url": null,
+    "sku": null,
+    "brand": null,
+    "availability": null
+}
+    ]
+}"#;
+
+const XML: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<productList>
+    <timestamp>2026-10-18T12:00:00+00:00</timestamp>
+    <product>
+    <name>Galaxy A55 &amp; case</name>
+    <price>7499</price>
+    <link>https://xstore.md/p/2</link>
+    <attribute>Ecran: 6.6&quot;</attribute>
+    <image_url></image_url>
+    <sku></sku>
+    <brand></brand>
+    <availability></availability>
+</product>
+</productList>"#;
+
+const BI: &str = r#"Products [
+    price [
+        8999
+    ]
+    name [
+        "Nokia G42"
+    ]
+    link [
+        "https://xstore.md/p/3"
+    ]
+    attributes [
+        "Baterie: 5000 mAh"
+    ]
+    name [
+        "Nokia 3310"
+    ]
+    attributes [
+        "Attributes not found"
+    ]
+    link [
+        ""
+    ]
+    price [
+        999
+    ]
+]"#;
+
+fn import(content_type: &str, body: &str) -> TestRequest {
+    TestRequest::post()
+        .uri("/products/import")
+        .insert_header(("Content-Type", content_type))
+        .set_payload(body.to_string())
+}
+
+// Every product as (name, price, description, link), in id order
+async fn stored(app: &impl TestApp) -> Vec<(String, f64, Value, Value)> {
+    let (_, page) = json(app, TestRequest::get().uri("/products?limit=100")).await;
+    page["data"].as_array().unwrap().iter().map(|product| (
+        product["name"].as_str().unwrap().to_string(),
+        product["price"].as_f64().unwrap(),
+        product["description"].clone(),
+        product["link"].clone(),
+    )).collect()
+}
+
+fn row(name: &str, price: f64, description: Option<&str>, link: Option<&str>) -> (String, f64, Value, Value) {
+    (name.to_string(), price, description.map_or(Value::Null, Value::from), link.map_or(Value::Null, Value::from))
+}
+
+#[actix_web::test]
+async fn each_format_is_imported() {
+    let database = Database::new();
+    let app = app(&database).await;
+
+    for (content_type, body) in [("application/json", JSON), ("application/xml; charset=utf-8", XML), ("text/plain", BI)] {
+        let (status, report) = json(&app, import(content_type, body)).await;
+        assert_eq!(status, 200, "{}: {}", content_type, report);
+        assert_eq!((report["updated"].as_u64(), report["rejected"].as_u64()), (Some(0), Some(0)), "{}", report);
+    }
+
+    assert_eq!(stored(&app).await, [
+        row("iPhone 15", 12999.0, Some("Ecran: 6.1\""), Some("https://xstore.md/p/1")),
+        // lab1's placeholder is no description, and a blank link no link
+        row("Pixel 8", 10999.5, None, None),
+        row("Galaxy A55 & case", 7499.0, Some("Ecran: 6.6\""), Some("https://xstore.md/p/2")),
+        row("Nokia G42", 8999.0, Some("Baterie: 5000 mAh"), Some("https://xstore.md/p/3")),
+        row("Nokia 3310", 999.0, None, None),
+    ]);
+}
+
+#[actix_web::test]
+async fn existing_products_are_matched_by_link_then_name() {
+    let database = Database::new();
+    let app = app(&database).await;
+    create_product(&app, &[("name", "Apple iPhone 15 (old name)"), ("price", "14999"), ("link", "https://xstore.md/p/1")]).await;
+    create_product(&app, &[("name", "Pixel 8"), ("price", "11999"), ("description", "Ecran: 6.2\"")]).await;
+
+    let (status, report) = json(&app, import("application/json", JSON)).await;
+    assert_eq!(status, 200);
+    assert_eq!((report["inserted"].as_u64(), report["updated"].as_u64()), (Some(0), Some(2)), "{}", report);
+
+    assert_eq!(stored(&app).await, [
+        // Same link: everything but the link follows the scrape
+        row("iPhone 15", 12999.0, Some("Ecran: 6.1\""), Some("https://xstore.md/p/1")),
+        // Same name; the placeholder doesn't wipe the description already there
+        row("Pixel 8", 10999.5, Some("Ecran: 6.2\""), None),
+    ]);
+
+    // Importing again changes nothing more
+    let (_, report) = json(&app, import("application/json", JSON)).await;
+    assert_eq!((report["inserted"].as_u64(), report["updated"].as_u64()), (Some(0), Some(2)));
+    assert_eq!(stored(&app).await.len(), 2);
+}
+
+#[actix_web::test]
+async fn invalid_products_are_reported_and_skipped() {
+    let database = Database::new();
+    let app = app(&database).await;
+    let body = r#"{"products": [
+        {"name": "Phone", "price": -1, "link": "ftp://xstore.md/p/1", "attribut": ""},
+        {"name": "Tablet", "price": "1299.50", "link": "https://xstore.md/p/2", "attribut": ""},
+        {"name": "  ", "price": 5, "link": "", "attribut": ""}
+    ]}"#;
+
+    let (status, report) = json(&app, import("application/json", body)).await;
+    assert_eq!(status, 200);
+    assert_eq!((report["inserted"].as_u64(), report["rejected"].as_u64()), (Some(1), Some(2)), "{}", report);
+    let rejection = &report["rejections"][0];
+    assert_eq!((rejection["index"].as_u64(), rejection["name"].as_str()), (Some(0), Some("Phone")));
+    let mut fields: Vec<_> = rejection["errors"].as_array().unwrap().iter().map(|e| e["field"].as_str().unwrap()).collect();
+    fields.sort();
+    assert_eq!(fields, ["link", "price"]);
+    assert_eq!(report["rejections"][1]["index"], 2);
+    assert_eq!(report["rejections"][1]["errors"][0]["field"], "name");
+    // A price written as text is read like a number
+    assert_eq!(stored(&app).await, [row("Tablet", 1299.5, None, Some("https://xstore.md/p/2"))]);
+}
+
+// (index, name, [(field, message)]) of a rejected record
+type Rejection<'a> = (u64, &'a str, Vec<(&'a str, &'a str)>);
+
+#[actix_web::test]
+async fn unreadable_records_are_rejected_on_their_own() {
+    let database = Database::new();
+    let app = app(&database).await;
+
+    let cases: [(&str, &str, Vec<Rejection>); 3] = [
+        (
+            "application/json",
+            r#"{"products": [
+                {"name": "Phone", "price": "cheap"},
+                {"price": 1},
+                {"name": "Tablet", "price": 200},
+                {"name": 7}
+            ]}"#,
+            vec![
+                (0, "Phone", vec![("price", "must be a number")]),
+                (1, "", vec![("name", "is required")]),
+                (3, "", vec![("name", "must be a string"), ("price", "is required")]),
+            ],
+        ),
+        (
+            "application/xml",
+            "<productList><product><name>Laptop</name></product><product><name>Watch</name><price>99</price></product></productList>",
+            vec![(0, "Laptop", vec![("price", "is required")])],
+        ),
+        (
+            "text/plain",
+            "Products [\n    name [\n        \"Camera\"\n    ]\n    price [\n        \"n/a\"\n    ]\n]",
+            vec![(0, "Camera", vec![("price", "must be a number")])],
+        ),
+    ];
+    for (content_type, body, expected) in cases {
+        let (status, report) = json(&app, import(content_type, body)).await;
+        assert_eq!(status, 200, "{}: {}", content_type, report);
+        assert_eq!(report["rejected"].as_u64(), Some(expected.len() as u64), "{}", report);
+        let rejections: Vec<Rejection> = report["rejections"].as_array().unwrap().iter()
+            .map(|rejection| (
+                rejection["index"].as_u64().unwrap(),
+                rejection["name"].as_str().unwrap(),
+                rejection["errors"].as_array().unwrap().iter()
+                    .map(|error| (error["field"].as_str().unwrap(), error["message"].as_str().unwrap()))
+                    .collect(),
+            ))
+            .collect();
+        assert_eq!(rejections, expected, "{}", content_type);
+    }
+
+    // The readable records around them were still imported
+    assert_eq!(stored(&app).await, [row("Tablet", 200.0, None, None), row("Watch", 99.0, None, None)]);
+}
+
+#[actix_web::test]
+async fn malformed_bodies_are_rejected_whole() {
+    let database = Database::new();
+    let app = app(&database).await;
+
+    for (content_type, body) in [
+        ("application/json", "{\"products\": [{\"name\": \"Phone\", \"price\": 1}"),
+        ("application/json", "{\"items\": []}"),
+        ("application/xml", "<productList><product><name>Phone</name></productList>"),
+        ("application/xml", "<products><product><name>Phone</name><price>1</price></product></products>"),
+        ("application/xml", "<!DOCTYPE productList [<!ENTITY a \"aaaa\">]><productList/>"),
+        ("text/plain", "Products [\n    name [\n        \"Phone\"\n    ]\n"),
+        ("text/plain", "Items [\n]"),
+        ("text/plain", "Products [\n    name [\n        Phone\n    ]\n]"),
+    ] {
+        let (status, body) = json(&app, import(content_type, body)).await;
+        assert_eq!((status, body["code"].as_str()), (400, Some("invalid_import")), "{}: {}", content_type, body);
+    }
+
+    let (status, body) = json(&app, import("text/csv", "name,price\nPhone,1\n")).await;
+    assert_eq!((status, body["code"].as_str()), (415, Some("unsupported_content_type")));
+    assert!(stored(&app).await.is_empty());
+}
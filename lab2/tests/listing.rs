@@ -0,0 +1,79 @@
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{app, create_product, json, Database, TestApp};
+use serde_json::Value;
+
+fn list(query: &str) -> TestRequest {
+    TestRequest::get().uri(&format!("/products?{}", query))
+}
+
+fn names(page: &Value) -> Vec<&str> {
+    page["data"].as_array().unwrap().iter().map(|product| product["name"].as_str().unwrap()).collect()
+}
+
+async fn catalog(app: &impl TestApp) {
+    for (name, price) in [("iPhone 15", "12999"), ("pixel 8", "10999"), ("Galaxy 100%", "7499"), ("Nokia 3310", "999")] {
+        create_product(app, &[("name", name), ("price", price)]).await;
+    }
+}
+
+#[actix_web::test]
+async fn products_are_filtered_sorted_and_paged() {
+    let database = Database::new();
+    let app = app(&database).await;
+    catalog(&app).await;
+
+    let (status, page) = json(&app, list("sort=-price&limit=2&offset=1")).await;
+    assert_eq!(status, 200);
+    assert_eq!(names(&page), ["pixel 8", "Galaxy 100%"]);
+    assert_eq!((page["total_count"].as_i64(), page["offset"].as_i64(), page["limit"].as_i64()), (Some(4), Some(1), Some(2)));
+
+    // Names sort and match without regard to case
+    assert_eq!(names(&json(&app, list("sort=name")).await.1), ["Galaxy 100%", "iPhone 15", "Nokia 3310", "pixel 8"]);
+    assert_eq!(names(&json(&app, list("name=PIXEL")).await.1), ["pixel 8"]);
+    // LIKE wildcards in the name are matched literally
+    assert_eq!(names(&json(&app, list("name=0%25")).await.1), ["Galaxy 100%"]);
+
+    let (_, page) = json(&app, list("min_price=1000&max_price=11000&has_image=false&sort=price")).await;
+    assert_eq!(names(&page), ["Galaxy 100%", "pixel 8"]);
+    assert_eq!(page["total_count"], 2);
+
+    // Only the selected fields, and oversized pages are capped
+    let (_, page) = json(&app, list("fields=name,price&limit=1000")).await;
+    assert_eq!(page["data"][0], serde_json::json!({"name": "iPhone 15", "price": 12999.0}));
+    assert_eq!(page["limit"], 100);
+}
+
+#[actix_web::test]
+async fn unknown_and_invalid_parameters_are_refused_together() {
+    let database = Database::new();
+    let app = app(&database).await;
+    catalog(&app).await;
+
+    let (status, body) = json(&app, list("colour=red&limit=0&offset=-1&min_price=abc&has_image=maybe&sort=-colour&fields=name,sku")).await;
+    assert_eq!(status, 400);
+    assert_eq!((body["code"].as_str(), body["message"].as_str()), (Some("invalid_query"), Some("Invalid query parameters")));
+    let errors: Vec<(&str, &str)> = body["errors"].as_array().unwrap().iter()
+        .map(|error| (error["field"].as_str().unwrap(), error["message"].as_str().unwrap()))
+        .collect();
+    // Reported in parameter order, every one of them
+    assert_eq!(errors, [
+        ("colour", "unknown parameter"),
+        ("fields", "unknown field(s) sku; expected id, name, price, description, link, image_url"),
+        ("has_image", "must be true or false"),
+        ("limit", "must be at least 1"),
+        ("min_price", "must be a number"),
+        ("offset", "must be at least 0"),
+        ("sort", "must be one of id, name, price, optionally prefixed with '-' for descending order"),
+    ]);
+
+    let (status, body) = json(&app, list("min_price=100&max_price=10")).await;
+    assert_eq!((status, body["errors"][0]["field"].as_str()), (400, Some("min_price")));
+
+    for query in ["limit=ten", "name=", "max_price=-5", "fields=,"] {
+        let (status, body) = json(&app, list(query)).await;
+        assert_eq!((status, body["code"].as_str()), (400, Some("invalid_query")), "{}", query);
+        assert_eq!(body["errors"].as_array().unwrap().len(), 1, "{}: {}", query, body);
+    }
+}
@@ -0,0 +1,112 @@
+mod common;
+
+use actix_web::test::{self, TestRequest};
+use common::{app, create_product, encoded_image, form, json, Database, TestApp};
+use image::ImageFormat;
+use serde_json::json;
+
+const URI: &str = "/products/1";
+
+async fn etag(app: &impl TestApp, request: TestRequest) -> (u16, Option<String>) {
+    let response = test::call_service(app, request.to_request()).await;
+    let tag = response.headers().get("ETag").map(|tag| tag.to_str().unwrap().to_string());
+    (response.status().as_u16(), tag)
+}
+
+#[actix_web::test]
+async fn if_none_match_saves_sending_an_unchanged_product() {
+    let database = Database::new();
+    let app = app(&database).await;
+    create_product(&app, &[("name", "Phone"), ("price", "100")]).await;
+
+    assert_eq!(etag(&app, TestRequest::get().uri(URI)).await, (200, Some("\"1\"".to_string())));
+
+    for (header, status) in [("\"1\"", 304), ("W/\"1\"", 304), ("\"0\", \"1\"", 304), ("*", 304), ("\"2\"", 200)] {
+        let request = TestRequest::get().uri(URI).insert_header(("If-None-Match", header));
+        let response = test::call_service(&app, request.to_request()).await;
+        assert_eq!(response.status(), status, "If-None-Match: {}", header);
+        assert_eq!(response.headers().get("ETag").unwrap(), "\"1\"");
+        if status == 304 {
+            assert!(test::read_body(response).await.is_empty());
+        }
+    }
+
+    // Once the product changes, the old tag no longer matches
+    json(&app, TestRequest::patch().uri(URI).set_json(json!({"price": 90}))).await;
+    let request = TestRequest::get().uri(URI).insert_header(("If-None-Match", "\"1\""));
+    assert_eq!(etag(&app, request).await, (200, Some("\"2\"".to_string())));
+}
+
+#[actix_web::test]
+async fn if_match_refuses_writes_to_a_changed_product() {
+    let database = Database::new();
+    let app = app(&database).await;
+    let (status, tag) = etag(&app, form(TestRequest::post().uri("/products"), &[("name", "Phone"), ("price", "100")], None)).await;
+    assert_eq!((status, tag.as_deref()), (201, Some("\"1\"")));
+
+    let patch = |if_match: &str, price: i32| {
+        TestRequest::patch().uri(URI).insert_header(("If-Match", if_match.to_string())).set_json(json!({"price": price}))
+    };
+    assert_eq!(etag(&app, patch("\"1\"", 90)).await, (200, Some("\"2\"".to_string())));
+
+    // A second client still holding version 1 loses
+    let (status, body) = json(&app, patch("\"1\"", 80)).await;
+    assert_eq!((status, body["code"].as_str()), (412, Some("precondition_failed")));
+    // If-Match compares strongly, so a weak tag never matches
+    assert_eq!(json(&app, patch("W/\"2\"", 80)).await.0, 412);
+    let (_, stored) = json(&app, TestRequest::get().uri(URI)).await;
+    assert_eq!(stored["price"], 90.0);
+
+    // Any listed version will do, as will `*`
+    assert_eq!(etag(&app, patch("\"1\", \"2\"", 80)).await, (200, Some("\"3\"".to_string())));
+    assert_eq!(etag(&app, patch("*", 70)).await, (200, Some("\"4\"".to_string())));
+
+    let put = |if_match: &str| {
+        TestRequest::put().uri(URI).insert_header(("If-Match", if_match.to_string())).set_json(json!({"name": "Phone", "price": 60}))
+    };
+    assert_eq!(json(&app, put("\"3\"")).await.0, 412);
+    assert_eq!(etag(&app, put("\"4\"")).await, (200, Some("\"5\"".to_string())));
+
+    // The image endpoints check If-Match too, and answer with the product's new tag
+    let put_image = |if_match: &str| {
+        TestRequest::put().uri("/products/1/image")
+            .insert_header(("If-Match", if_match.to_string()))
+            .set_payload(encoded_image(10, 10, ImageFormat::Png))
+    };
+    assert_eq!(json(&app, put_image("\"4\"")).await.0, 412);
+    let (_, stored) = json(&app, TestRequest::get().uri(URI)).await;
+    assert!(stored["image_url"].is_null());
+    assert_eq!(etag(&app, put_image("\"5\"")).await, (204, Some("\"6\"".to_string())));
+
+    let delete_image = |if_match: &str| TestRequest::delete().uri("/products/1/image").insert_header(("If-Match", if_match.to_string()));
+    assert_eq!(json(&app, delete_image("\"5\"")).await.0, 412);
+    let (_, stored) = json(&app, TestRequest::get().uri(URI)).await;
+    assert_eq!(stored["image_url"], "/products/1/image");
+    assert_eq!(etag(&app, delete_image("\"6\"")).await, (204, Some("\"7\"".to_string())));
+
+    let delete = |if_match: &str| TestRequest::delete().uri(URI).insert_header(("If-Match", if_match.to_string()));
+    assert_eq!(json(&app, delete("\"6\"")).await.0, 412);
+    assert_eq!(json(&app, delete("\"7\"")).await.0, 200);
+
+    // A product that is gone is not found rather than changed
+    let (status, body) = json(&app, patch("\"7\"", 50)).await;
+    assert_eq!((status, body["code"].as_str()), (404, Some("product_not_found")));
+    assert_eq!(json(&app, put_image("*")).await.0, 404);
+    assert_eq!(json(&app, delete_image("*")).await.0, 404);
+}
+
+#[actix_web::test]
+async fn writes_without_if_match_always_apply() {
+    let database = Database::new();
+    let app = app(&database).await;
+    create_product(&app, &[("name", "Phone"), ("price", "100")]).await;
+
+    for price in [90, 80] {
+        let (status, tag) = etag(&app, TestRequest::patch().uri(URI).set_json(json!({"price": price}))).await;
+        assert_eq!(status, 200);
+        assert!(tag.is_some());
+    }
+    // A patch with no fields writes nothing, so the version stays
+    assert_eq!(etag(&app, TestRequest::get().uri(URI)).await.1.as_deref(), Some("\"3\""));
+    assert_eq!(etag(&app, TestRequest::patch().uri(URI).set_json(json!({}))).await.1.as_deref(), Some("\"3\""));
+}
@@ -0,0 +1,108 @@
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{app, create_product, json, Database};
+use serde_json::{json, Value};
+
+fn search(query: &str) -> TestRequest {
+    TestRequest::get().uri(&format!("/products/search?{}", query))
+}
+
+fn names(page: &Value) -> Vec<&str> {
+    page["data"].as_array().unwrap().iter().map(|result| result["name"].as_str().unwrap()).collect()
+}
+
+#[actix_web::test]
+async fn name_matches_rank_above_description_matches() {
+    let database = Database::new();
+    let app = app(&database).await;
+    create_product(&app, &[("name", "Husa pentru telefon"), ("price", "99"), ("description", "Compatibila cu iPhone 15")]).await;
+    create_product(&app, &[("name", "iPhone 15"), ("price", "12999"), ("description", "Ecran: 6.1\"")]).await;
+    create_product(&app, &[("name", "Pixel 8"), ("price", "10999")]).await;
+
+    let (status, page) = json(&app, search("q=iphone")).await;
+    assert_eq!(status, 200);
+    assert_eq!(names(&page), ["iPhone 15", "Husa pentru telefon"]);
+    assert_eq!(page["total_count"], 2);
+    assert!(page["data"][0]["score"].as_f64() > page["data"][1]["score"].as_f64());
+
+    // Every word must match, each as a prefix, and accents don't matter
+    assert_eq!(names(&json(&app, search("q=iph%2015")).await.1), ["iPhone 15", "Husa pentru telefon"]);
+    assert_eq!(names(&json(&app, search("q=compatibil%C4%83%20iphone")).await.1), ["Husa pentru telefon"]);
+    assert!(names(&json(&app, search("q=iphone%20pixel")).await.1).is_empty());
+
+    // Paging counts every match
+    let (_, page) = json(&app, search("q=iphone&limit=1&offset=1")).await;
+    assert_eq!((names(&page), page["total_count"].as_i64()), (vec!["Husa pentru telefon"], Some(2)));
+
+    // The index follows changes to the products
+    let pixel = format!("/products/{}", json(&app, search("q=pixel")).await.1["data"][0]["id"]);
+    json(&app, TestRequest::patch().uri(&pixel).set_json(json!({"name": "Google Pixel 8"}))).await;
+    assert_eq!(names(&json(&app, search("q=google")).await.1), ["Google Pixel 8"]);
+    json(&app, TestRequest::delete().uri(&pixel)).await;
+    assert!(names(&json(&app, search("q=google")).await.1).is_empty());
+}
+
+#[actix_web::test]
+async fn matches_are_highlighted_in_escaped_html() {
+    let database = Database::new();
+    let app = app(&database).await;
+    create_product(&app, &[
+        ("name", "Cablu <USB-C> & \"Lightning\""),
+        ("price", "150"),
+        ("description", "Cablu de 1m pentru <b>iPhone</b> & iPad, rezistent la 'îndoire'"),
+    ])
+    .await;
+
+    let (_, page) = json(&app, search("q=cablu%20iphone")).await;
+    let result = &page["data"][0];
+    assert_eq!(result["name"], "Cablu <USB-C> & \"Lightning\"");
+    assert_eq!(result["highlighted_name"], "<mark>Cablu</mark> &lt;USB-C&gt; &amp; &quot;Lightning&quot;");
+    assert_eq!(
+        result["snippet"],
+        "<mark>Cablu</mark> de 1m pentru &lt;b&gt;<mark>iPhone</mark>&lt;/b&gt; &amp; iPad, rezistent la &#39;îndoire&#39;"
+    );
+    // The description itself comes back as it was stored
+    assert_eq!(result["description"], "Cablu de 1m pentru <b>iPhone</b> & iPad, rezistent la 'îndoire'");
+
+    // Markup typed into the search is just text to look for
+    let (_, page) = json(&app, search("q=%3Cusb")).await;
+    assert_eq!(page["data"][0]["highlighted_name"], "Cablu &lt;<mark>USB</mark>-C&gt; &amp; &quot;Lightning&quot;");
+}
+
+#[actix_web::test]
+async fn search_syntax_is_not_passed_to_fts() {
+    let database = Database::new();
+    let app = app(&database).await;
+    create_product(&app, &[("name", "iPhone 15"), ("price", "12999")]).await;
+
+    // FTS5 operators are words to look for, never query syntax
+    for query in ["q=iphone%20OR%20pixel", "q=NEAR(iphone)", "q=name%3Aiphone", "q=iphone%20NOT%2015"] {
+        let (status, page) = json(&app, search(query)).await;
+        assert_eq!(status, 200, "{}: {}", query, page);
+        assert!(names(&page).is_empty(), "{}: {}", query, page);
+    }
+    // Quotes, stars and dashes are punctuation around the words
+    for query in ["q=%22iphone", "q=%22iphone%22%20*15", "q=iphone%20-15"] {
+        assert_eq!(names(&json(&app, search(query)).await.1), ["iPhone 15"], "{}", query);
+    }
+}
+
+#[actix_web::test]
+async fn search_parameters_are_checked() {
+    let database = Database::new();
+    let app = app(&database).await;
+
+    for (query, field, message) in [
+        ("", "q", "is required"),
+        ("q=%20%2B%2B%20", "q", "must contain at least one word"),
+        ("q=phone&limit=0", "limit", "must be at least 1"),
+        ("q=phone&page=2", "page", "unknown parameter"),
+    ] {
+        let (status, body) = json(&app, search(query)).await;
+        assert_eq!((status, body["code"].as_str()), (400, Some("invalid_query")), "{}", query);
+        assert_eq!(body["errors"], json!([{"field": field, "message": message}]), "{}", query);
+    }
+    let (_, body) = json(&app, search(&format!("q={}", "a".repeat(201)))).await;
+    assert_eq!(body["errors"][0]["message"], "must be at most 200 characters");
+}
@@ -0,0 +1,143 @@
+mod common;
+
+use actix_web::test::{self, TestRequest};
+use common::{app, create_product, encoded_image, form, json, Database};
+use image::ImageFormat;
+use serde_json::json;
+
+fn product_uri(product: &serde_json::Value) -> String {
+    format!("/products/{}", product["id"])
+}
+
+#[actix_web::test]
+async fn put_replaces_every_field() {
+    let database = Database::new();
+    let app = app(&database).await;
+    let product = create_product(&app, &[
+        ("name", "Phone"),
+        ("price", "100"),
+        ("description", "Ecran: 6.1\""),
+        ("link", "https://xstore.md/p/1"),
+    ])
+    .await;
+
+    // Fields left out of a PUT are cleared, not kept
+    let request = TestRequest::put().uri(&product_uri(&product)).set_json(json!({"name": "Phone 2", "price": 90}));
+    let (status, updated) = json(&app, request).await;
+    assert_eq!(status, 200);
+    assert_eq!(updated["name"], "Phone 2");
+    assert_eq!(updated["price"], 90.0);
+    assert!(updated["description"].is_null(), "{}", updated);
+    assert!(updated["link"].is_null(), "{}", updated);
+
+    let (_, stored) = json(&app, TestRequest::get().uri(&product_uri(&product))).await;
+    assert_eq!(stored, updated);
+
+    // Null and blank clear them as well
+    let request = TestRequest::put().uri(&product_uri(&product)).set_json(json!({
+        "name": "Phone 2", "price": 90, "description": "Ecran: 6.1\"", "link": "https://xstore.md/p/1",
+    }));
+    assert_eq!(json(&app, request).await.1["description"], "Ecran: 6.1\"");
+    let request = TestRequest::put().uri(&product_uri(&product)).set_json(json!({
+        "name": "Phone 2", "price": 90, "description": null, "link": " ",
+    }));
+    let (_, updated) = json(&app, request).await;
+    assert!(updated["description"].is_null() && updated["link"].is_null(), "{}", updated);
+}
+
+#[actix_web::test]
+async fn put_as_a_form_keeps_the_image_unless_a_new_one_is_sent() {
+    let database = Database::new();
+    let app = app(&database).await;
+    let image = encoded_image(20, 20, ImageFormat::Png);
+    let request = form(TestRequest::post().uri("/products"), &[
+        ("name", "Phone"),
+        ("price", "100"),
+        ("description", "Ecran: 6.1\""),
+    ], Some(("image/png", &image)));
+    let (_, product) = json(&app, request).await;
+    let image_uri = format!("{}/image", product_uri(&product));
+
+    let request = form(TestRequest::put().uri(&product_uri(&product)), &[("name", "Phone"), ("price", "80")], None);
+    let (status, updated) = json(&app, request).await;
+    assert_eq!(status, 200);
+    assert_eq!(updated["price"], 80.0);
+    assert!(updated["description"].is_null(), "{}", updated);
+    assert_eq!(updated["image_url"], image_uri);
+    let response = test::call_service(&app, TestRequest::get().uri(&image_uri).to_request()).await;
+    assert_eq!(test::read_body(response).await, image);
+
+    let replacement = encoded_image(30, 10, ImageFormat::Gif);
+    let request = form(TestRequest::put().uri(&product_uri(&product)), &[("name", "Phone"), ("price", "80")], Some(("image/gif", &replacement)));
+    assert_eq!(json(&app, request).await.0, 200);
+    let response = test::call_service(&app, TestRequest::get().uri(&image_uri).to_request()).await;
+    assert_eq!(test::read_body(response).await, replacement);
+}
+
+#[actix_web::test]
+async fn patch_changes_only_the_fields_it_names() {
+    let database = Database::new();
+    let app = app(&database).await;
+    let product = create_product(&app, &[
+        ("name", "Phone"),
+        ("price", "100"),
+        ("description", "Ecran: 6.1\""),
+        ("link", "https://xstore.md/p/1"),
+    ])
+    .await;
+
+    let patch = |body| TestRequest::patch().uri(&product_uri(&product)).set_json(body);
+
+    // Absent fields stay as they are
+    let (status, updated) = json(&app, patch(json!({"price": 75.5}))).await;
+    assert_eq!(status, 200);
+    assert_eq!(updated["price"], 75.5);
+    assert_eq!(updated["name"], "Phone");
+    assert_eq!(updated["description"], "Ecran: 6.1\"");
+    assert_eq!(updated["link"], "https://xstore.md/p/1");
+
+    // Null clears a field
+    let (_, updated) = json(&app, patch(json!({"description": null}))).await;
+    assert!(updated["description"].is_null());
+    assert_eq!(updated["link"], "https://xstore.md/p/1");
+
+    // An empty patch changes nothing
+    let (status, unchanged) = json(&app, patch(json!({}))).await;
+    assert_eq!((status, unchanged), (200, updated));
+
+    // Required fields can't be cleared, and nothing is written when any field is wrong
+    let (status, body) = json(&app, patch(json!({"name": null, "link": "not a url", "price": 1}))).await;
+    assert_eq!((status, body["code"].as_str()), (422, Some("validation_failed")));
+    let mut fields: Vec<_> = body["errors"].as_array().unwrap().iter().map(|e| e["field"].as_str().unwrap()).collect();
+    fields.sort();
+    assert_eq!(fields, ["link", "name"]);
+    let (_, stored) = json(&app, TestRequest::get().uri(&product_uri(&product))).await;
+    assert_eq!(stored["price"], 75.5);
+
+    let (status, body) = json(&app, patch(json!([{"op": "replace"}]))).await;
+    assert_eq!((status, body["code"].as_str()), (400, Some("invalid_json")));
+}
+
+#[actix_web::test]
+async fn malformed_forms_are_rejected() {
+    let database = Database::new();
+    let app = app(&database).await;
+
+    // A part without a Content-Disposition header
+    let body = "--b\r\nContent-Type: text/plain\r\n\r\nPhone\r\n--b--\r\n";
+    let request = TestRequest::post()
+        .uri("/products")
+        .insert_header(("Content-Type", "multipart/form-data; boundary=b"))
+        .set_payload(body);
+    let (status, body) = json(&app, request).await;
+    assert_eq!((status, body["code"].as_str()), (400, Some("invalid_multipart")));
+
+    // A text part far longer than any field may be
+    let description = "x".repeat(100_000);
+    let request = form(TestRequest::post().uri("/products"), &[("name", "Phone"), ("price", "1"), ("description", &description)], None);
+    let (status, body) = json(&app, request).await;
+    assert_eq!((status, body["errors"][0]["field"].as_str()), (422, Some("description")));
+
+    let (_, page) = json(&app, TestRequest::get().uri("/products")).await;
+    assert_eq!(page["total_count"], 0);
+}
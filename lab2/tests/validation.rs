@@ -0,0 +1,130 @@
+mod common;
+
+use actix_web::test::TestRequest;
+use common::{app, create_product, form, json, Database};
+use serde_json::{json, Value};
+
+// The (field, message) pairs of an error body, sorted by field
+fn field_errors(body: &Value) -> Vec<(String, String)> {
+    let mut errors: Vec<_> = body["errors"].as_array().unwrap().iter()
+        .map(|error| (error["field"].as_str().unwrap().to_string(), error["message"].as_str().unwrap().to_string()))
+        .collect();
+    errors.sort();
+    errors
+}
+
+fn pairs(expected: &[(&str, &str)]) -> Vec<(String, String)> {
+    expected.iter().map(|(field, message)| (field.to_string(), message.to_string())).collect()
+}
+
+#[actix_web::test]
+async fn every_bad_field_of_a_form_is_reported() {
+    let database = Database::new();
+    let app = app(&database).await;
+
+    let long_name = "x".repeat(201);
+    let long_description = "x".repeat(5001);
+    for (fields, expected) in [
+        (vec![("colour", "red")], vec![("name", "is required"), ("price", "is required")]),
+        (vec![("name", "  "), ("price", "free")], vec![("name", "must not be empty"), ("price", "must be a number")]),
+        (vec![("name", long_name.as_str()), ("price", "-1")], vec![("name", "must be at most 200 characters"), ("price", "must be a non-negative number")]),
+        (vec![("name", "Phone"), ("price", "inf")], vec![("price", "must be a non-negative number")]),
+        (
+            vec![("name", "Phone"), ("price", "1"), ("description", long_description.as_str()), ("link", "xstore.md/p/1")],
+            vec![("description", "must be at most 5000 characters"), ("link", "must be an http or https URL")],
+        ),
+    ] {
+        let (status, body) = json(&app, form(TestRequest::post().uri("/products"), &fields, None)).await;
+        assert_eq!(status, 422, "{:?}", fields);
+        assert_eq!((body["code"].as_str(), body["message"].as_str()), (Some("validation_failed"), Some("Invalid product fields")));
+        assert_eq!(field_errors(&body), pairs(&expected), "{:?}", fields);
+    }
+
+    // Text parts must be UTF-8
+    let request = TestRequest::post()
+        .uri("/products")
+        .insert_header(("Content-Type", "multipart/form-data; boundary=b"))
+        .set_payload(&b"--b\r\nContent-Disposition: form-data; name=\"name\"\r\n\r\n\xff\xfe\r\n--b--\r\n"[..]);
+    let (status, body) = json(&app, request).await;
+    assert_eq!((status, field_errors(&body)), (422, pairs(&[("name", "must be UTF-8 text")])));
+
+    let (_, page) = json(&app, TestRequest::get().uri("/products")).await;
+    assert_eq!(page["total_count"], 0);
+}
+
+#[actix_web::test]
+async fn valid_fields_are_normalized() {
+    let database = Database::new();
+    let app = app(&database).await;
+
+    let product = create_product(&app, &[
+        ("name", "  Phone  "),
+        ("price", " 12.50 "),
+        ("description", "   "),
+        ("link", " https://xstore.md/p/1 "),
+    ])
+    .await;
+    assert_eq!(product["name"], "Phone");
+    assert_eq!(product["price"], 12.5);
+    // Blank is the same as missing
+    assert!(product["description"].is_null());
+    assert_eq!(product["link"], "https://xstore.md/p/1");
+}
+
+#[actix_web::test]
+async fn json_bodies_are_checked_the_same_way() {
+    let database = Database::new();
+    let app = app(&database).await;
+    let product = create_product(&app, &[("name", "Phone"), ("price", "100")]).await;
+    let uri = format!("/products/{}", product["id"]);
+
+    let (status, body) = json(&app, TestRequest::put().uri(&uri).set_json(json!({"name": "", "price": true}))).await;
+    assert_eq!(status, 422);
+    assert_eq!(field_errors(&body), pairs(&[("name", "must not be empty"), ("price", "must be a number")]));
+
+    // A price sent as text is accepted from JSON too
+    let (status, updated) = json(&app, TestRequest::put().uri(&uri).set_json(json!({"name": "Phone", "price": "99.9"}))).await;
+    assert_eq!((status, updated["price"].as_f64()), (200, Some(99.9)));
+
+    let (status, body) = json(&app, TestRequest::patch().uri(&uri).set_json(json!({
+        "name": 5, "description": [], "link": "https://xstore.md/p 1", "image_url": "https://x/i.png", "id": 7, "colour": "red",
+    })))
+    .await;
+    assert_eq!(status, 422);
+    assert_eq!(field_errors(&body), pairs(&[
+        ("colour", "is not a product field"),
+        ("description", "must be a string or null"),
+        ("id", "cannot be changed"),
+        ("image_url", "can only be set to null; upload images to /products/{id}/image"),
+        ("link", "must be an http or https URL"),
+        ("name", "must be a string"),
+    ]));
+
+    // Bodies that aren't JSON at all fail before any field is looked at
+    let request = TestRequest::patch().uri(&uri).insert_header(("Content-Type", "application/json")).set_payload("{\"name\": ");
+    let (status, body) = json(&app, request).await;
+    assert_eq!((status, body["code"].as_str()), (400, Some("invalid_json")));
+    assert_eq!(body["errors"], json!([]));
+    let request = TestRequest::put().uri(&uri).insert_header(("Content-Type", "application/json")).set_payload("[1, 2]");
+    assert_eq!(json(&app, request).await.1["code"], "invalid_json");
+
+    let (_, stored) = json(&app, TestRequest::get().uri(&uri)).await;
+    assert_eq!((stored["name"].as_str(), stored["price"].as_f64()), (Some("Phone"), Some(99.9)));
+}
+
+#[actix_web::test]
+async fn unknown_products_are_not_found() {
+    let database = Database::new();
+    let app = app(&database).await;
+
+    for request in [
+        TestRequest::get().uri("/products/42"),
+        TestRequest::get().uri("/products/not-a-number"),
+        TestRequest::patch().uri("/products/42").set_json(json!({"price": 1})),
+        TestRequest::put().uri("/products/42").set_json(json!({"name": "Phone", "price": 1})),
+        TestRequest::delete().uri("/products/42"),
+    ] {
+        let (status, body) = json(&app, request).await;
+        assert_eq!((status, body["code"].as_str()), (404, Some("product_not_found")));
+    }
+}